@@ -0,0 +1,66 @@
+//! actix-web integration for QPay callbacks (requires the `actix` feature).
+//!
+//! ```no_run
+//! use std::sync::Arc;
+//! use actix_web::App;
+//! use qpay::callback::{CallbackHandler, CallbackProcessor, HandlerError, VerifiedCallback};
+//!
+//! struct Orders;
+//!
+//! impl CallbackHandler for Orders {
+//!     async fn on_payment(&self, payment: VerifiedCallback) -> Result<(), HandlerError> {
+//!         println!("paid: {}", payment.payment_id);
+//!         Ok(())
+//!     }
+//! }
+//!
+//! # fn run(client: qpay::QPayClient) {
+//! let processor = Arc::new(CallbackProcessor::new(Arc::new(client), Orders));
+//! let app = App::new().configure(qpay::actix::callback_config("/qpay/callback", processor));
+//! # }
+//! ```
+
+use std::sync::Arc;
+
+use actix_web::body::BoxBody;
+use actix_web::http::StatusCode;
+use actix_web::web::{self, Bytes, ServiceConfig};
+use actix_web::{HttpRequest, HttpResponse, Responder};
+
+use crate::callback::{CallbackHandler, CallbackOutcome, CallbackProcessor};
+
+/// Service configuration answering QPay callbacks (GET or POST) on `path`,
+/// for use with `App::configure`.
+pub fn callback_config<H: CallbackHandler>(
+    path: &str,
+    processor: Arc<CallbackProcessor<H>>,
+) -> impl FnOnce(&mut ServiceConfig) {
+    let path = path.to_string();
+    move |cfg: &mut ServiceConfig| {
+        cfg.app_data(web::Data::from(processor))
+            .route(&path, web::route().to(handle_callback::<H>));
+    }
+}
+
+/// Handler for use in an existing app with `web::Data<CallbackProcessor<H>>`.
+pub async fn handle_callback<H: CallbackHandler>(
+    processor: web::Data<CallbackProcessor<H>>,
+    req: HttpRequest,
+    body: Bytes,
+) -> CallbackOutcome {
+    let query = req.query_string();
+    let query = (!query.is_empty()).then_some(query);
+    processor.process(query, &body).await
+}
+
+impl Responder for CallbackOutcome {
+    type Body = BoxBody;
+
+    fn respond_to(self, _req: &HttpRequest) -> HttpResponse<Self::Body> {
+        let status =
+            StatusCode::from_u16(self.status_code()).unwrap_or(StatusCode::INTERNAL_SERVER_ERROR);
+        HttpResponse::build(status)
+            .content_type("text/plain; charset=utf-8")
+            .body(self.body())
+    }
+}
@@ -0,0 +1,61 @@
+//! axum integration for QPay callbacks (requires the `axum` feature).
+//!
+//! ```no_run
+//! use std::sync::Arc;
+//! use qpay::callback::{CallbackHandler, CallbackProcessor, HandlerError, VerifiedCallback};
+//!
+//! struct Orders;
+//!
+//! impl CallbackHandler for Orders {
+//!     async fn on_payment(&self, payment: VerifiedCallback) -> Result<(), HandlerError> {
+//!         println!("paid: {}", payment.payment_id);
+//!         Ok(())
+//!     }
+//! }
+//!
+//! # async fn run(client: qpay::QPayClient) {
+//! let processor = Arc::new(CallbackProcessor::new(Arc::new(client), Orders));
+//! let app = axum::Router::new().merge(qpay::axum::callback_router("/qpay/callback", processor));
+//! # }
+//! ```
+
+use std::sync::Arc;
+
+use ::axum::body::Bytes;
+use ::axum::extract::{RawQuery, State};
+use ::axum::http::StatusCode;
+use ::axum::response::{IntoResponse, Response};
+use ::axum::routing::any;
+use ::axum::Router;
+
+use crate::callback::{CallbackHandler, CallbackOutcome, CallbackProcessor};
+
+/// A router answering QPay callbacks (GET or POST) on `path`.
+pub fn callback_router<H: CallbackHandler>(
+    path: &str,
+    processor: Arc<CallbackProcessor<H>>,
+) -> Router {
+    Router::new()
+        .route(path, any(handle_callback::<H>))
+        .with_state(processor)
+}
+
+/// Handler for use in an existing router with `Arc<CallbackProcessor<H>>` state.
+pub async fn handle_callback<H: CallbackHandler>(
+    State(processor): State<Arc<CallbackProcessor<H>>>,
+    RawQuery(query): RawQuery,
+    body: Bytes,
+) -> Response {
+    processor
+        .process(query.as_deref(), &body)
+        .await
+        .into_response()
+}
+
+impl IntoResponse for CallbackOutcome {
+    fn into_response(self) -> Response {
+        let status =
+            StatusCode::from_u16(self.status_code()).unwrap_or(StatusCode::INTERNAL_SERVER_ERROR);
+        (status, self.body()).into_response()
+    }
+}
@@ -0,0 +1,131 @@
+//! Creating many invoices at once.
+
+use std::sync::OnceLock;
+use std::time::Duration;
+
+use futures::stream::{self, StreamExt};
+
+use crate::client::QPayClient;
+use crate::error::QPayError;
+use crate::models::{CreateInvoiceRequest, InvoiceResponse};
+
+/// Attempts per invoice in [`QPayClient::create_invoices_bulk`], including the first.
+pub const BULK_MAX_ATTEMPTS: u32 = 3;
+
+/// Delay before the first retry; doubled for each further retry.
+pub const BULK_RETRY_DELAY: Duration = Duration::from_millis(200);
+
+/// Outcome of one invoice in a bulk creation.
+#[derive(Debug)]
+pub struct BulkInvoiceResult {
+    /// `sender_invoice_no` of the request, for correlation.
+    pub sender_invoice_no: String,
+    /// The created invoice, or the last error.
+    pub result: Result<InvoiceResponse, QPayError>,
+    /// Number of requests sent for this invoice.
+    pub attempts: u32,
+}
+
+impl QPayClient {
+    /// Create invoices with up to `concurrency` requests in flight.
+    ///
+    /// All requests share the client's token. Failures where the request
+    /// never reached QPay (connection errors, 429 rate limiting, and token
+    /// requests that failed on the network or with 429/5xx) are retried with
+    /// backoff up to [`BULK_MAX_ATTEMPTS`] times. Timeouts and server errors
+    /// are not retried, since QPay may already have created the invoice; they
+    /// are recorded and the batch continues. If QPay rejects the credentials,
+    /// every remaining invoice fails with that [`QPayError::Token`] without
+    /// being sent. Results are returned in input order.
+    pub async fn create_invoices_bulk<I>(
+        &self,
+        requests: I,
+        concurrency: usize,
+    ) -> Vec<BulkInvoiceResult>
+    where
+        I: IntoIterator<Item = CreateInvoiceRequest>,
+    {
+        // Fetch the token once up front so parallel requests don't all
+        // authenticate. After a transient failure each request retries on
+        // its own; a rejection fails the whole batch.
+        let rejected = OnceLock::new();
+        if let Err(e) = self.try_ensure_token().await {
+            log::warn!("qpay: bulk invoice token request failed: {}", e);
+            if !e.is_transient() {
+                let _ = rejected.set(e.to_string());
+            }
+        }
+
+        stream::iter(requests)
+            .map(|req| self.create_invoice_with_retry(req, &rejected))
+            .buffered(concurrency.max(1))
+            .collect()
+            .await
+    }
+
+    /// Create one invoice, retrying unsent requests. `rejected` holds the
+    /// token rejection shared by the batch, once there is one.
+    async fn create_invoice_with_retry(
+        &self,
+        req: CreateInvoiceRequest,
+        rejected: &OnceLock<String>,
+    ) -> BulkInvoiceResult {
+        let mut attempts = 0;
+        let mut delay = BULK_RETRY_DELAY;
+        loop {
+            if let Some(reason) = rejected.get() {
+                return BulkInvoiceResult {
+                    sender_invoice_no: req.sender_invoice_no,
+                    result: Err(QPayError::Token(reason.clone())),
+                    attempts,
+                };
+            }
+
+            attempts += 1;
+            let (result, retryable) = match self.try_ensure_token().await {
+                Ok(()) => {
+                    let result = self.create_invoice(&req).await;
+                    let retryable = result.as_ref().is_err_and(never_reached_server);
+                    (result, retryable)
+                }
+                // The token request failed before the invoice was sent
+                Err(e) => {
+                    let retryable = e.is_transient();
+                    if !retryable {
+                        let _ = rejected.set(e.to_string());
+                    }
+                    (Err(QPayError::Token(e.to_string())), retryable)
+                }
+            };
+            match result {
+                Err(e) if retryable && attempts < BULK_MAX_ATTEMPTS => {
+                    log::warn!(
+                        "qpay: retrying invoice {} after attempt {}: {}",
+                        req.sender_invoice_no,
+                        attempts,
+                        e
+                    );
+                    tokio::time::sleep(delay).await;
+                    delay *= 2;
+                }
+                result => {
+                    return BulkInvoiceResult {
+                        sender_invoice_no: req.sender_invoice_no,
+                        result,
+                        attempts,
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// Whether `err` shows the request was not processed, so resending it
+/// cannot create a second invoice.
+fn never_reached_server(err: &QPayError) -> bool {
+    match err {
+        QPayError::Http(e) => e.is_connect(),
+        QPayError::Api { status_code, .. } => *status_code == 429,
+        _ => false,
+    }
+}
@@ -0,0 +1,697 @@
+//! Handling QPay payment callbacks.
+//!
+//! QPay calls the invoice's `callback_url` with the payment id in the query
+//! string. Anyone can call that URL, so a callback is only a hint: it is
+//! parsed into a [`CallbackEvent`] and confirmed against the API before the
+//! payment is trusted.
+
+use std::borrow::Cow;
+use std::collections::BTreeMap;
+use std::future::Future;
+use std::sync::Arc;
+use std::time::Duration;
+
+use hmac::{Hmac, Mac};
+use serde_json::Value;
+use sha2::Sha256;
+
+use crate::client::QPayClient;
+use crate::config::QPayConfig;
+use crate::dedup::{CallbackDedupStore, MemoryDedupStore};
+use crate::error::{QPayError, ERR_PAYMENT_NOT_FOUND};
+use crate::file_log::unix_now;
+use crate::models::{
+    CreateEbarimtInvoiceRequest, CreateInvoiceRequest, CreateSimpleInvoiceRequest,
+    PaymentCheckRequest,
+};
+use crate::polling::PAYMENT_STATUS_PAID;
+
+/// Body QPay expects in the response to a handled callback.
+pub const CALLBACK_SUCCESS_BODY: &str = "SUCCESS";
+
+/// Query parameters QPay uses for the payment id.
+pub(crate) const PAYMENT_ID_PARAMS: &[&str] = &["payment_id", "qpay_payment_id"];
+
+/// Query parameter carrying the invoice reference of a signed callback URL.
+pub const SIGNED_REF_PARAM: &str = "qpay_ref";
+
+/// Query parameter carrying the expiry (Unix seconds) of a signed callback URL.
+pub const SIGNED_EXPIRES_PARAM: &str = "qpay_exp";
+
+/// Query parameter carrying the HMAC-SHA256 signature of a signed callback URL.
+pub const SIGNED_SIGNATURE_PARAM: &str = "qpay_sig";
+
+/// Amounts closer than this are considered equal.
+pub(crate) const AMOUNT_TOLERANCE: f64 = 0.005;
+
+/// An unverified callback request.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct CallbackEvent {
+    /// Payment id sent by QPay, if present.
+    pub payment_id: Option<String>,
+    /// All query parameters, including any added to the callback URL by the
+    /// merchant (e.g. an order number).
+    pub params: BTreeMap<String, String>,
+}
+
+impl CallbackEvent {
+    /// Parse a query string (with or without the leading `?`).
+    pub fn from_query(query: &str) -> Self {
+        let query = query.strip_prefix('?').unwrap_or(query);
+        let params: BTreeMap<String, String> = url::form_urlencoded::parse(query.as_bytes())
+            .into_owned()
+            .collect();
+        let payment_id = PAYMENT_ID_PARAMS
+            .iter()
+            .find_map(|key| params.get(*key))
+            .filter(|id| !id.is_empty())
+            .cloned();
+        Self { payment_id, params }
+    }
+
+    /// Parse the query string of a full URL or a request target such as
+    /// `/qpay/callback?payment_id=...`.
+    pub fn from_url(url: &str) -> Self {
+        match url.split_once('?') {
+            Some((_, query)) => Self::from_query(query.split('#').next().unwrap_or("")),
+            None => Self::default(),
+        }
+    }
+
+    /// Parse a callback from its query string and body. A JSON body with a
+    /// `payment_id` field is used when the query has none.
+    pub fn from_request(query: Option<&str>, body: &[u8]) -> Self {
+        let mut event = Self::from_query(query.unwrap_or(""));
+        if event.payment_id.is_none() {
+            if let Ok(Value::Object(body)) = serde_json::from_slice::<Value>(body) {
+                event.payment_id = PAYMENT_ID_PARAMS
+                    .iter()
+                    .find_map(|key| match body.get(*key) {
+                        Some(Value::String(id)) if !id.is_empty() => Some(id.clone()),
+                        Some(Value::Number(id)) => Some(id.to_string()),
+                        _ => None,
+                    });
+            }
+        }
+        event
+    }
+
+    /// A query parameter by name.
+    pub fn param(&self, name: &str) -> Option<&str> {
+        self.params.get(name).map(String::as_str)
+    }
+}
+
+/// What a callback is expected to confirm.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ExpectedPayment {
+    /// The invoice (or other object) the payment must belong to.
+    pub object_id: Option<String>,
+    /// The amount that must have been paid.
+    pub amount: Option<f64>,
+}
+
+impl ExpectedPayment {
+    /// Expect a payment of `amount` for `invoice_id`.
+    pub fn invoice(invoice_id: impl Into<String>, amount: f64) -> Self {
+        Self {
+            object_id: Some(invoice_id.into()),
+            amount: Some(amount),
+        }
+    }
+}
+
+/// A callback confirmed against the QPay API.
+#[derive(Debug, Clone, PartialEq)]
+pub struct VerifiedCallback {
+    /// The callback as received.
+    pub event: CallbackEvent,
+    pub payment_id: String,
+    pub object_id: String,
+    pub amount: f64,
+    pub currency: String,
+    /// The invoice reference of the signed callback URL, if signed URLs are
+    /// in use. It has been checked against `object_id`.
+    pub signed_ref: Option<String>,
+}
+
+/// Why a callback was not accepted.
+#[derive(Debug, thiserror::Error)]
+pub enum CallbackRejection {
+    #[error("callback has no payment id")]
+    MissingPaymentId,
+
+    #[error("payment {0} does not exist")]
+    UnknownPayment(String),
+
+    #[error("no paid payment for {0}")]
+    NoPayment(String),
+
+    #[error("payment {payment_id} has status {status}")]
+    NotPaid { payment_id: String, status: String },
+
+    #[error("payment belongs to {actual}, expected {expected}")]
+    ObjectMismatch { expected: String, actual: String },
+
+    #[error("paid amount {actual} does not match expected {expected}")]
+    AmountMismatch { expected: f64, actual: f64 },
+
+    #[error("callback signature is missing or invalid")]
+    InvalidSignature,
+
+    #[error("callback URL expired at {0}")]
+    Expired(u64),
+
+    #[error("callback URL was signed for {signed_ref}, but the payment belongs to {object_id}")]
+    RefMismatch {
+        signed_ref: String,
+        object_id: String,
+    },
+
+    #[error("could not verify payment: {0}")]
+    Lookup(QPayError),
+}
+
+impl CallbackRejection {
+    /// Whether verification failed for a reason that may pass on a later
+    /// attempt, so the callback should be answered with an error and retried
+    /// by QPay rather than discarded.
+    pub fn is_retryable(&self) -> bool {
+        matches!(self, Self::Lookup(e) if e.is_transient())
+    }
+}
+
+/// Signs and verifies per-invoice callback URLs.
+///
+/// A signed URL is the configured callback URL with an invoice reference,
+/// an optional expiry and an HMAC-SHA256 over both added to the query, so
+/// callbacks for other invoices or with altered parameters can be told apart
+/// from QPay's.
+#[derive(Clone)]
+pub struct CallbackSigner {
+    base_url: String,
+    secret: Vec<u8>,
+    ttl: Option<Duration>,
+}
+
+impl std::fmt::Debug for CallbackSigner {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("CallbackSigner")
+            .field("base_url", &self.base_url)
+            .field("ttl", &self.ttl)
+            .finish_non_exhaustive()
+    }
+}
+
+impl CallbackSigner {
+    /// Create a signer for URLs based on `base_url` that do not expire.
+    pub fn new(base_url: impl Into<String>, secret: impl AsRef<[u8]>) -> Self {
+        Self {
+            base_url: base_url.into(),
+            secret: secret.as_ref().to_vec(),
+            ttl: None,
+        }
+    }
+
+    /// Let signed URLs expire `ttl` after signing.
+    pub fn with_ttl(mut self, ttl: Duration) -> Self {
+        self.ttl = Some(ttl);
+        self
+    }
+
+    /// A signer for `config.callback_url`, if `config.callback_secret` is set.
+    pub fn from_config(config: &QPayConfig) -> Option<Self> {
+        config.callback_secret.as_ref().map(|secret| Self {
+            ttl: config.callback_url_ttl,
+            ..Self::new(&config.callback_url, secret)
+        })
+    }
+
+    /// Signed callback URL for `invoice_ref`, valid for the signer's TTL if
+    /// it has one.
+    pub fn sign(&self, invoice_ref: &str) -> Result<String, QPayError> {
+        self.sign_url(&self.base_url, invoice_ref)
+    }
+
+    /// Sign `url` instead of the signer's base URL for `invoice_ref`. Its
+    /// existing query is kept and the signature parameters are appended.
+    pub fn sign_url(&self, url: &str, invoice_ref: &str) -> Result<String, QPayError> {
+        let expires_at = self.ttl.map(|ttl| unix_now() + ttl.as_secs());
+        self.signed_url(url, invoice_ref, expires_at)
+    }
+
+    /// Signed callback URL for `invoice_ref`, valid until `expires_at`
+    /// (Unix seconds).
+    pub fn sign_until(&self, invoice_ref: &str, expires_at: u64) -> Result<String, QPayError> {
+        self.signed_url(&self.base_url, invoice_ref, Some(expires_at))
+    }
+
+    fn signed_url(
+        &self,
+        base_url: &str,
+        invoice_ref: &str,
+        expires_at: Option<u64>,
+    ) -> Result<String, QPayError> {
+        let mut url = url::Url::parse(base_url).map_err(|e| {
+            QPayError::Config(format!("invalid callback_url {:?}: {}", base_url, e))
+        })?;
+        let expires_at = expires_at.map(|at| at.to_string());
+        let mac = self.mac(invoice_ref, expires_at.as_deref().unwrap_or(""));
+        let signature = hex::encode(mac.finalize().into_bytes());
+
+        {
+            let mut query = url.query_pairs_mut();
+            query.append_pair(SIGNED_REF_PARAM, invoice_ref);
+            if let Some(expires_at) = &expires_at {
+                query.append_pair(SIGNED_EXPIRES_PARAM, expires_at);
+            }
+            query.append_pair(SIGNED_SIGNATURE_PARAM, &signature);
+        }
+        Ok(url.into())
+    }
+
+    /// Check the signature and expiry of a callback, returning its invoice
+    /// reference. The signature is compared in constant time.
+    pub fn verify(&self, event: &CallbackEvent) -> Result<String, CallbackRejection> {
+        let (Some(invoice_ref), Some(signature)) = (
+            event.param(SIGNED_REF_PARAM),
+            event.param(SIGNED_SIGNATURE_PARAM),
+        ) else {
+            return Err(CallbackRejection::InvalidSignature);
+        };
+        let expires_at = event.param(SIGNED_EXPIRES_PARAM);
+        let signature = hex::decode(signature).map_err(|_| CallbackRejection::InvalidSignature)?;
+        self.mac(invoice_ref, expires_at.unwrap_or(""))
+            .verify_slice(&signature)
+            .map_err(|_| CallbackRejection::InvalidSignature)?;
+
+        if let Some(expires_at) = expires_at {
+            let expires_at: u64 = expires_at
+                .parse()
+                .map_err(|_| CallbackRejection::InvalidSignature)?;
+            if unix_now() > expires_at {
+                return Err(CallbackRejection::Expired(expires_at));
+            }
+        }
+        Ok(invoice_ref.to_string())
+    }
+
+    fn mac(&self, invoice_ref: &str, expires_at: &str) -> Hmac<Sha256> {
+        let mut mac =
+            Hmac::<Sha256>::new_from_slice(&self.secret).expect("HMAC accepts any key length");
+        mac.update(invoice_ref.as_bytes());
+        mac.update(b"\n");
+        mac.update(expires_at.as_bytes());
+        mac
+    }
+}
+
+/// Invoice requests whose `callback_url` can be filled with a signed URL.
+pub(crate) trait SignableInvoice: Clone {
+    fn sender_invoice_no(&self) -> &str;
+    fn callback_url(&self) -> &str;
+    fn callback_url_mut(&mut self) -> &mut String;
+}
+
+macro_rules! impl_signable_invoice {
+    ($($ty:ty),*) => {$(
+        impl SignableInvoice for $ty {
+            fn sender_invoice_no(&self) -> &str {
+                &self.sender_invoice_no
+            }
+
+            fn callback_url(&self) -> &str {
+                &self.callback_url
+            }
+
+            fn callback_url_mut(&mut self) -> &mut String {
+                &mut self.callback_url
+            }
+        }
+    )*};
+}
+
+impl_signable_invoice!(
+    CreateInvoiceRequest,
+    CreateSimpleInvoiceRequest,
+    CreateEbarimtInvoiceRequest
+);
+
+/// Whether `url` already has a signature parameter.
+fn is_signed(url: &str) -> bool {
+    url::Url::parse(url).is_ok_and(|url| {
+        url.query_pairs()
+            .any(|(name, _)| name == SIGNED_SIGNATURE_PARAM)
+    })
+}
+
+impl QPayClient {
+    /// The signer for this client's callback URLs, if a callback secret is
+    /// configured.
+    pub fn callback_signer(&self) -> Option<CallbackSigner> {
+        CallbackSigner::from_config(&self.config)
+    }
+
+    /// Signed callback URL for `invoice_ref`.
+    ///
+    /// Fails with [`QPayError::Config`] if no callback secret is configured.
+    pub fn signed_callback_url(&self, invoice_ref: &str) -> Result<String, QPayError> {
+        self.callback_signer()
+            .ok_or_else(|| QPayError::Config("callback_secret is not set".to_string()))?
+            .sign(invoice_ref)
+    }
+
+    /// Sign `callback_url` for `sender_invoice_no` when a callback secret is
+    /// configured, since the processor then rejects unsigned callbacks.
+    ///
+    /// An empty URL becomes the configured callback URL; any other URL keeps
+    /// its query and gets the signature appended. URLs that already carry a
+    /// signature, e.g. from [`QPayClient::signed_callback_url`], are sent
+    /// as they are.
+    pub(crate) fn with_signed_callback_url<'a, R: SignableInvoice>(
+        &self,
+        req: &'a R,
+    ) -> Result<Cow<'a, R>, QPayError> {
+        let Some(signer) = self.callback_signer() else {
+            return Ok(Cow::Borrowed(req));
+        };
+        if is_signed(req.callback_url()) {
+            return Ok(Cow::Borrowed(req));
+        }
+
+        let url = match req.callback_url() {
+            "" => signer.sign(req.sender_invoice_no())?,
+            url => signer.sign_url(url, req.sender_invoice_no())?,
+        };
+        let mut signed = req.clone();
+        *signed.callback_url_mut() = url;
+        Ok(Cow::Owned(signed))
+    }
+
+    /// Check that the reference a callback URL was signed for belongs to the
+    /// paid object: either its id, or the `sender_invoice_no` of the invoice.
+    async fn verify_signed_ref(
+        &self,
+        signed_ref: &str,
+        object_id: &str,
+    ) -> Result<(), CallbackRejection> {
+        if signed_ref == object_id {
+            return Ok(());
+        }
+        let invoice = self
+            .get_invoice(object_id)
+            .await
+            .map_err(CallbackRejection::Lookup)?;
+        if invoice.sender_invoice_no == signed_ref {
+            Ok(())
+        } else {
+            Err(CallbackRejection::RefMismatch {
+                signed_ref: signed_ref.to_string(),
+                object_id: object_id.to_string(),
+            })
+        }
+    }
+
+    /// Confirm a callback against the API.
+    ///
+    /// With a payment id the payment is fetched with `get_payment`; without
+    /// one, `expected.object_id` is looked up with `check_payment`. The
+    /// payment must be `PAID` and match `expected`.
+    pub async fn verify_callback(
+        &self,
+        event: &CallbackEvent,
+        expected: &ExpectedPayment,
+    ) -> Result<VerifiedCallback, CallbackRejection> {
+        let verified = match (&event.payment_id, &expected.object_id) {
+            (Some(payment_id), _) => self.verify_payment_id(event, payment_id).await?,
+            (None, Some(object_id)) => self.verify_invoice(event, object_id).await?,
+            (None, None) => return Err(CallbackRejection::MissingPaymentId),
+        };
+
+        if let Some(object_id) = &expected.object_id {
+            if *object_id != verified.object_id {
+                return Err(CallbackRejection::ObjectMismatch {
+                    expected: object_id.clone(),
+                    actual: verified.object_id,
+                });
+            }
+        }
+        if let Some(amount) = expected.amount {
+            if (amount - verified.amount).abs() > AMOUNT_TOLERANCE {
+                return Err(CallbackRejection::AmountMismatch {
+                    expected: amount,
+                    actual: verified.amount,
+                });
+            }
+        }
+        Ok(verified)
+    }
+
+    async fn verify_payment_id(
+        &self,
+        event: &CallbackEvent,
+        payment_id: &str,
+    ) -> Result<VerifiedCallback, CallbackRejection> {
+        let payment = match self.get_payment(payment_id).await {
+            Ok(payment) => payment,
+            Err(QPayError::Api { code, .. }) if code == ERR_PAYMENT_NOT_FOUND => {
+                return Err(CallbackRejection::UnknownPayment(payment_id.to_string()));
+            }
+            Err(e) => return Err(CallbackRejection::Lookup(e)),
+        };
+        if payment.payment_status != PAYMENT_STATUS_PAID {
+            return Err(CallbackRejection::NotPaid {
+                payment_id: payment.payment_id,
+                status: payment.payment_status,
+            });
+        }
+        Ok(VerifiedCallback {
+            event: event.clone(),
+            amount: parse_amount(&payment.payment_amount),
+            payment_id: payment.payment_id,
+            object_id: payment.object_id,
+            currency: payment.payment_currency,
+            signed_ref: None,
+        })
+    }
+
+    async fn verify_invoice(
+        &self,
+        event: &CallbackEvent,
+        invoice_id: &str,
+    ) -> Result<VerifiedCallback, CallbackRejection> {
+        let req = PaymentCheckRequest {
+            object_type: "INVOICE".to_string(),
+            object_id: invoice_id.to_string(),
+            offset: None,
+        };
+        let resp = self
+            .check_payment(&req)
+            .await
+            .map_err(CallbackRejection::Lookup)?;
+        let row = resp
+            .rows
+            .iter()
+            .find(|row| row.payment_status == PAYMENT_STATUS_PAID)
+            .ok_or_else(|| CallbackRejection::NoPayment(invoice_id.to_string()))?;
+        Ok(VerifiedCallback {
+            event: event.clone(),
+            payment_id: row.payment_id.clone(),
+            object_id: invoice_id.to_string(),
+            amount: resp.total_paid(),
+            currency: row.payment_currency.clone(),
+            signed_ref: None,
+        })
+    }
+}
+
+fn parse_amount(amount: &str) -> f64 {
+    amount.trim().parse().unwrap_or(0.0)
+}
+
+/// Error returned by a [`CallbackHandler`]. The callback is answered with an
+/// error so QPay delivers it again.
+pub type HandlerError = Box<dyn std::error::Error + Send + Sync>;
+
+/// Application logic for verified callbacks, used by [`CallbackProcessor`]
+/// and the framework integrations.
+pub trait CallbackHandler: Send + Sync + 'static {
+    /// What the callback must confirm, typically looked up from an order
+    /// reference in `event.params`. Defaults to only requiring a paid payment.
+    fn expected_payment(
+        &self,
+        event: &CallbackEvent,
+    ) -> impl Future<Output = Result<ExpectedPayment, HandlerError>> + Send {
+        let _ = event;
+        async { Ok(ExpectedPayment::default()) }
+    }
+
+    /// Called once per verified payment.
+    fn on_payment(
+        &self,
+        payment: VerifiedCallback,
+    ) -> impl Future<Output = Result<(), HandlerError>> + Send;
+}
+
+/// Result of processing one callback request.
+#[derive(Debug)]
+pub enum CallbackOutcome {
+    /// Verified and passed to the handler.
+    Processed(VerifiedCallback),
+    /// The payment was already processed; the handler was not called again.
+    Duplicate(String),
+    /// The callback failed verification.
+    Rejected(CallbackRejection),
+    /// The handler failed; QPay should retry.
+    HandlerFailed(HandlerError),
+}
+
+impl CallbackOutcome {
+    /// HTTP status to answer QPay with. Only 200 stops QPay from retrying.
+    pub fn status_code(&self) -> u16 {
+        match self {
+            Self::Processed(_) | Self::Duplicate(_) => 200,
+            Self::Rejected(rejection) if rejection.is_retryable() => 503,
+            Self::Rejected(_) => 400,
+            Self::HandlerFailed(_) => 500,
+        }
+    }
+
+    /// Response body to answer QPay with.
+    pub fn body(&self) -> String {
+        match self {
+            Self::Processed(_) | Self::Duplicate(_) => CALLBACK_SUCCESS_BODY.to_string(),
+            Self::Rejected(rejection) => rejection.to_string(),
+            Self::HandlerFailed(_) => "callback handler failed".to_string(),
+        }
+    }
+}
+
+/// Framework-agnostic callback processing: parse, check the signature (if a
+/// callback secret is configured), verify against the API, de-duplicate and
+/// call the [`CallbackHandler`].
+pub struct CallbackProcessor<H> {
+    client: Arc<QPayClient>,
+    handler: H,
+    signer: Option<CallbackSigner>,
+    dedup: Arc<dyn CallbackDedupStore>,
+}
+
+impl<H: CallbackHandler> CallbackProcessor<H> {
+    /// Create a processor. Signed callback URLs are required if the client
+    /// has a callback secret.
+    pub fn new(client: Arc<QPayClient>, handler: H) -> Self {
+        Self {
+            signer: client.callback_signer(),
+            client,
+            handler,
+            dedup: Arc::new(MemoryDedupStore::default()),
+        }
+    }
+
+    /// Use `store` to remember processed payments instead of the default
+    /// in-memory store, e.g. a [`FileDedupStore`](crate::dedup::FileDedupStore)
+    /// to keep de-duplicating across restarts.
+    pub fn with_dedup_store(mut self, store: impl CallbackDedupStore + 'static) -> Self {
+        self.dedup = Arc::new(store);
+        self
+    }
+
+    /// The application handler.
+    pub fn handler(&self) -> &H {
+        &self.handler
+    }
+
+    /// Process a callback request from its raw query string and body.
+    pub async fn process(&self, query: Option<&str>, body: &[u8]) -> CallbackOutcome {
+        let outcome = self.process_event(query, body).await;
+        match &outcome {
+            CallbackOutcome::Rejected(rejection) => {
+                log::warn!("qpay: rejected callback: {}", rejection)
+            }
+            CallbackOutcome::HandlerFailed(e) => {
+                log::error!("qpay: callback handler failed: {}", e)
+            }
+            _ => {}
+        }
+        outcome
+    }
+
+    async fn process_event(&self, query: Option<&str>, body: &[u8]) -> CallbackOutcome {
+        let event = CallbackEvent::from_request(query, body);
+
+        // Check the signature before anything else, so unsigned requests
+        // learn nothing about processed payments.
+        let signed_ref = match &self.signer {
+            Some(signer) => match signer.verify(&event) {
+                Ok(signed_ref) => Some(signed_ref),
+                Err(rejection) => return CallbackOutcome::Rejected(rejection),
+            },
+            None => None,
+        };
+        if let Some(payment_id) = &event.payment_id {
+            match self.dedup(payment_id, |store, id| store.contains(id)).await {
+                Ok(true) => return CallbackOutcome::Duplicate(payment_id.clone()),
+                Ok(false) => {}
+                Err(e) => return CallbackOutcome::HandlerFailed(Box::new(e)),
+            }
+        }
+
+        let expected = match self.handler.expected_payment(&event).await {
+            Ok(expected) => expected,
+            Err(e) => return CallbackOutcome::HandlerFailed(e),
+        };
+        let mut verified = match self.client.verify_callback(&event, &expected).await {
+            Ok(verified) => verified,
+            Err(rejection) => return CallbackOutcome::Rejected(rejection),
+        };
+        if let Some(signed_ref) = signed_ref {
+            if let Err(rejection) = self
+                .client
+                .verify_signed_ref(&signed_ref, &verified.object_id)
+                .await
+            {
+                return CallbackOutcome::Rejected(rejection);
+            }
+            verified.signed_ref = Some(signed_ref);
+        }
+
+        // Claim the payment before calling the handler so concurrent
+        // deliveries of the same callback run it only once.
+        let payment_id = verified.payment_id.clone();
+        match self.dedup(&payment_id, |store, id| store.claim(id)).await {
+            Ok(true) => {}
+            Ok(false) => return CallbackOutcome::Duplicate(payment_id),
+            Err(e) => return CallbackOutcome::HandlerFailed(Box::new(e)),
+        }
+        match self.handler.on_payment(verified.clone()).await {
+            Ok(()) => CallbackOutcome::Processed(verified),
+            Err(e) => {
+                if let Err(release_error) =
+                    self.dedup(&payment_id, |store, id| store.release(id)).await
+                {
+                    log::error!(
+                        "qpay: could not release callback claim for {}: {}",
+                        payment_id,
+                        release_error
+                    );
+                }
+                CallbackOutcome::HandlerFailed(e)
+            }
+        }
+    }
+
+    /// Call the dedup store on the blocking thread pool, as stores may do
+    /// file or network I/O.
+    async fn dedup<T: Send + 'static>(
+        &self,
+        payment_id: &str,
+        op: fn(&dyn CallbackDedupStore, &str) -> Result<T, QPayError>,
+    ) -> Result<T, QPayError> {
+        let store = Arc::clone(&self.dedup);
+        let payment_id = payment_id.to_string();
+        tokio::task::spawn_blocking(move || op(&*store, &payment_id))
+            .await
+            .map_err(|e| QPayError::Storage(e.to_string()))?
+    }
+}
@@ -1,11 +1,13 @@
-use std::time::Duration;
+use std::time::{Duration, Instant};
 
 use reqwest::header::{HeaderMap, HeaderValue, AUTHORIZATION, CONTENT_TYPE};
 use tokio::sync::Mutex;
 
-use crate::config::QPayConfig;
+use crate::config::{QPayConfig, Strictness};
 use crate::error::{ApiErrorBody, QPayError};
-use crate::models::TokenResponse;
+use crate::models::{ResponseModel, TokenResponse};
+use crate::reference::ReferenceCache;
+use crate::response::ApiResponse;
 
 const TOKEN_BUFFER_SECONDS: i64 = 30;
 
@@ -22,6 +24,7 @@ pub struct QPayClient {
     pub(crate) config: QPayConfig,
     pub(crate) http: reqwest::Client,
     pub(crate) token_state: Mutex<TokenState>,
+    pub(crate) reference_cache: Mutex<ReferenceCache>,
 }
 
 impl QPayClient {
@@ -36,6 +39,7 @@ impl QPayClient {
             config,
             http,
             token_state: Mutex::new(TokenState::default()),
+            reference_cache: Mutex::new(ReferenceCache::default()),
         }
     }
 
@@ -45,11 +49,20 @@ impl QPayClient {
             config,
             http,
             token_state: Mutex::new(TokenState::default()),
+            reference_cache: Mutex::new(ReferenceCache::default()),
         }
     }
 
     /// Ensure a valid access token is available, refreshing or re-authenticating as needed.
     pub(crate) async fn ensure_token(&self) -> Result<(), QPayError> {
+        self.try_ensure_token()
+            .await
+            .map_err(|e| QPayError::Token(e.to_string()))
+    }
+
+    /// Like [`ensure_token`](Self::ensure_token), but return the error of the
+    /// token request itself so callers can tell rejections from outages.
+    pub(crate) async fn try_ensure_token(&self) -> Result<(), QPayError> {
         let now = chrono_now();
 
         let (needs_refresh, can_refresh, refresh_tok) = {
@@ -74,10 +87,7 @@ impl QPayClient {
         }
 
         // Get a new token via basic auth
-        let token = self
-            .get_token_request()
-            .await
-            .map_err(|e| QPayError::Token(e.to_string()))?;
+        let token = self.get_token_request().await?;
 
         let mut state = self.token_state.lock().await;
         store_token(&mut state, &token);
@@ -111,8 +121,7 @@ impl QPayClient {
             });
         }
 
-        let token: TokenResponse = serde_json::from_str(&body)?;
-        Ok(token)
+        self.decode_response(&body)
     }
 
     /// Get a new token using basic auth credentials.
@@ -152,8 +161,7 @@ impl QPayClient {
             });
         }
 
-        let token: TokenResponse = serde_json::from_str(&body)?;
-        Ok(token)
+        self.decode_response(&body)
     }
 
     /// Store the token response in the client state.
@@ -162,64 +170,98 @@ impl QPayClient {
         store_token(&mut state, token);
     }
 
-    /// Make an authenticated JSON request to the QPay API.
-    pub(crate) async fn do_request<B: serde::Serialize, R: serde::de::DeserializeOwned>(
+    /// Call any QPay endpoint, including ones this SDK does not wrap yet.
+    ///
+    /// Uses the same token handling and error mapping as the built-in
+    /// endpoint methods. An empty response body deserializes as JSON `null`,
+    /// so `R = ()` works for endpoints that return nothing.
+    pub async fn request<B: serde::Serialize, R: serde::de::DeserializeOwned>(
         &self,
         method: reqwest::Method,
         path: &str,
         body: Option<&B>,
     ) -> Result<R, QPayError> {
-        self.ensure_token().await?;
-
-        let url = format!("{}{}", self.config.base_url, path);
-
-        let access_token = {
-            let state = self.token_state.lock().await;
-            state.access_token.clone()
-        };
+        self.with_raw()
+            .request(method, path, body)
+            .await
+            .map(ApiResponse::into_inner)
+    }
 
-        let mut headers = HeaderMap::new();
-        headers.insert(CONTENT_TYPE, HeaderValue::from_static("application/json"));
-        headers.insert(
-            AUTHORIZATION,
-            HeaderValue::from_str(&format!("Bearer {}", access_token))
-                .map_err(|e| QPayError::Config(e.to_string()))?,
-        );
+    /// Call any QPay endpoint and return the response as untyped JSON.
+    pub async fn request_raw<B: serde::Serialize>(
+        &self,
+        method: reqwest::Method,
+        path: &str,
+        body: Option<&B>,
+    ) -> Result<serde_json::Value, QPayError> {
+        self.request(method, path, body).await
+    }
 
-        let mut request = self.http.request(method, &url).headers(headers);
+    /// Make an authenticated JSON request to the QPay API.
+    pub(crate) async fn do_request<B: serde::Serialize, R: ResponseModel>(
+        &self,
+        method: reqwest::Method,
+        path: &str,
+        body: Option<&B>,
+    ) -> Result<R, QPayError> {
+        self.do_request_raw(method, path, body)
+            .await
+            .map(ApiResponse::into_inner)
+    }
 
-        if let Some(b) = body {
-            request = request.json(b);
-        }
+    /// Make an authenticated JSON request and keep the raw HTTP response details.
+    pub(crate) async fn do_request_raw<B: serde::Serialize, R: ResponseModel>(
+        &self,
+        method: reqwest::Method,
+        path: &str,
+        body: Option<&B>,
+    ) -> Result<ApiResponse<R>, QPayError> {
+        let raw = self.send_request(method, path, body).await?;
+        let value = self.decode_response(&raw.body)?;
+        Ok(raw.with_value(value))
+    }
 
-        let resp = request.send().await?;
-        let status = resp.status();
-        let resp_body = resp.text().await?;
+    /// Deserialize a successful response body according to the configured strictness.
+    pub(crate) fn decode_response<R: ResponseModel>(&self, body: &str) -> Result<R, QPayError> {
+        let result: R = match (serde_json::from_str::<R>(body), self.config.strictness) {
+            (Ok(result), _) => result,
+            (Err(err), Strictness::Strict) => return Err(err.into()),
+            (Err(err), Strictness::Lenient) => {
+                if !err.is_data() {
+                    return Err(err.into());
+                }
+                let mut value: serde_json::Value = serde_json::from_str(body)?;
+                let mut filled = Vec::new();
+                // Identifier and credential fields are left missing, so the
+                // decode below still fails for them
+                R::fill_defaults(&mut value, "", &mut filled);
+                if filled.is_empty() {
+                    return Err(err.into());
+                }
+                let result = serde_json::from_value(value)?;
+                log::warn!(
+                    "qpay: response is missing fields, using defaults: {}",
+                    filled.join(", ")
+                );
+                result
+            }
+        };
 
-        if !status.is_success() {
-            let api_err = serde_json::from_str::<ApiErrorBody>(&resp_body).unwrap_or_default();
-            let code = if api_err.code.is_empty() {
-                status
-                    .canonical_reason()
-                    .unwrap_or("Unknown")
-                    .to_string()
-            } else {
-                api_err.code
-            };
-            let message = if api_err.message.is_empty() {
-                resp_body.clone()
-            } else {
-                api_err.message
-            };
-            return Err(QPayError::Api {
-                status_code: status.as_u16(),
-                code,
-                message,
-                raw_body: resp_body,
-            });
+        let unknown = result.unknown_fields();
+        if !unknown.is_empty() {
+            match self.config.strictness {
+                Strictness::Strict => {
+                    return Err(QPayError::Json(serde::de::Error::custom(format!(
+                        "unknown fields in response: {}",
+                        unknown.join(", ")
+                    ))));
+                }
+                Strictness::Lenient => {
+                    log::warn!("qpay: response has unknown fields: {}", unknown.join(", "));
+                }
+            }
         }
 
-        let result: R = serde_json::from_str(&resp_body)?;
         Ok(result)
     }
 
@@ -230,6 +272,17 @@ impl QPayClient {
         path: &str,
         body: Option<&B>,
     ) -> Result<(), QPayError> {
+        self.send_request(method, path, body).await?;
+        Ok(())
+    }
+
+    /// Send an authenticated request, mapping non-success statuses to `QPayError::Api`.
+    pub(crate) async fn send_request<B: serde::Serialize>(
+        &self,
+        method: reqwest::Method,
+        path: &str,
+        body: Option<&B>,
+    ) -> Result<ApiResponse<()>, QPayError> {
         self.ensure_token().await?;
 
         let url = format!("{}{}", self.config.base_url, path);
@@ -253,9 +306,12 @@ impl QPayClient {
             request = request.json(b);
         }
 
+        let started = Instant::now();
         let resp = request.send().await?;
         let status = resp.status();
+        let resp_headers = resp.headers().clone();
         let resp_body = resp.text().await?;
+        let elapsed = started.elapsed();
 
         if !status.is_success() {
             let api_err = serde_json::from_str::<ApiErrorBody>(&resp_body).unwrap_or_default();
@@ -280,7 +336,13 @@ impl QPayClient {
             });
         }
 
-        Ok(())
+        Ok(ApiResponse {
+            value: (),
+            status,
+            headers: resp_headers,
+            body: resp_body,
+            elapsed,
+        })
     }
 }
 
@@ -1,13 +1,72 @@
+use std::time::Duration;
+
 use crate::error::QPayError;
 
 /// QPay client configuration.
-#[derive(Debug, Clone)]
+///
+/// Build it with [`QPayConfig::new`] or [`QPayConfig::from_env`] and the
+/// `with_*` methods; fields may be added in minor releases.
+#[derive(Clone)]
+#[non_exhaustive]
 pub struct QPayConfig {
     pub base_url: String,
     pub username: String,
     pub password: String,
     pub invoice_code: String,
     pub callback_url: String,
+    pub strictness: Strictness,
+    pub reference_data_ttl: Duration,
+    pub callback_secret: Option<String>,
+    /// Validity of signed callback URLs; `None` (the default) for no expiry.
+    pub callback_url_ttl: Option<Duration>,
+}
+
+impl std::fmt::Debug for QPayConfig {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("QPayConfig")
+            .field("base_url", &self.base_url)
+            .field("username", &self.username)
+            .field("password", &"<redacted>")
+            .field("invoice_code", &self.invoice_code)
+            .field("callback_url", &self.callback_url)
+            .field("strictness", &self.strictness)
+            .field("reference_data_ttl", &self.reference_data_ttl)
+            .field(
+                "callback_secret",
+                &self.callback_secret.as_ref().map(|_| "<redacted>"),
+            )
+            .field("callback_url_ttl", &self.callback_url_ttl)
+            .finish()
+    }
+}
+
+/// Default time reference data (banks, MCC codes, cities, districts) is cached for.
+pub const DEFAULT_REFERENCE_DATA_TTL: Duration = Duration::from_secs(24 * 60 * 60);
+
+/// How strictly API responses are checked against the SDK's models.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Strictness {
+    /// Fail on missing or unknown fields. Useful in CI to catch API drift.
+    Strict,
+    /// Fill missing fields with defaults, keep unknown fields in `extra`,
+    /// and log the difference.
+    #[default]
+    Lenient,
+}
+
+impl std::str::FromStr for Strictness {
+    type Err = QPayError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_ascii_lowercase().as_str() {
+            "strict" => Ok(Strictness::Strict),
+            "lenient" => Ok(Strictness::Lenient),
+            other => Err(QPayError::Config(format!(
+                "invalid strictness {:?}, expected \"strict\" or \"lenient\"",
+                other
+            ))),
+        }
+    }
 }
 
 impl QPayConfig {
@@ -25,9 +84,39 @@ impl QPayConfig {
             password: password.into(),
             invoice_code: invoice_code.into(),
             callback_url: callback_url.into(),
+            strictness: Strictness::default(),
+            reference_data_ttl: DEFAULT_REFERENCE_DATA_TTL,
+            callback_secret: None,
+            callback_url_ttl: None,
         }
     }
 
+    /// Set the response strictness mode.
+    pub fn with_strictness(mut self, strictness: Strictness) -> Self {
+        self.strictness = strictness;
+        self
+    }
+
+    /// Set how long reference data (banks, MCC codes, cities, districts) is cached.
+    pub fn with_reference_data_ttl(mut self, ttl: Duration) -> Self {
+        self.reference_data_ttl = ttl;
+        self
+    }
+
+    /// Set the secret used to sign per-invoice callback URLs.
+    pub fn with_callback_secret(mut self, secret: impl Into<String>) -> Self {
+        self.callback_secret = Some(secret.into());
+        self
+    }
+
+    /// Let signed callback URLs expire after `ttl`. Callbacks arriving later
+    /// are rejected and not retried by QPay, so `ttl` must outlast the
+    /// invoice. By default signed URLs do not expire.
+    pub fn with_callback_url_ttl(mut self, ttl: Duration) -> Self {
+        self.callback_url_ttl = Some(ttl);
+        self
+    }
+
     /// Load configuration from environment variables.
     ///
     /// Required variables:
@@ -36,12 +125,23 @@ impl QPayConfig {
     /// - `QPAY_PASSWORD`
     /// - `QPAY_INVOICE_CODE`
     /// - `QPAY_CALLBACK_URL`
+    ///
+    /// Optional variables:
+    /// - `QPAY_STRICTNESS` (`strict` or `lenient`, defaults to `lenient`)
+    /// - `QPAY_CALLBACK_SECRET` (enables signed callback URLs)
     pub fn from_env() -> Result<Self, QPayError> {
         let base_url = require_env("QPAY_BASE_URL")?;
         let username = require_env("QPAY_USERNAME")?;
         let password = require_env("QPAY_PASSWORD")?;
         let invoice_code = require_env("QPAY_INVOICE_CODE")?;
         let callback_url = require_env("QPAY_CALLBACK_URL")?;
+        let strictness = match std::env::var("QPAY_STRICTNESS") {
+            Ok(value) => value.parse()?,
+            Err(_) => Strictness::default(),
+        };
+        let callback_secret = std::env::var("QPAY_CALLBACK_SECRET")
+            .ok()
+            .filter(|secret| !secret.is_empty());
 
         Ok(Self {
             base_url,
@@ -49,15 +149,16 @@ impl QPayConfig {
             password,
             invoice_code,
             callback_url,
+            strictness,
+            reference_data_ttl: DEFAULT_REFERENCE_DATA_TTL,
+            callback_secret,
+            callback_url_ttl: None,
         })
     }
 }
 
 fn require_env(name: &str) -> Result<String, QPayError> {
     std::env::var(name).map_err(|_| {
-        QPayError::Config(format!(
-            "required environment variable {} is not set",
-            name
-        ))
+        QPayError::Config(format!("required environment variable {} is not set", name))
     })
 }
@@ -0,0 +1,194 @@
+//! Typed date/time support for QPay timestamps (requires the `chrono` feature).
+//!
+//! QPay returns dates as strings in several formats (RFC 3339, `YYYY-MM-DD HH:MM:SS`,
+//! date-only, ...). Values without an explicit offset are interpreted as
+//! Asia/Ulaanbaatar local time (UTC+8).
+
+use chrono::{DateTime, FixedOffset, NaiveDate, NaiveDateTime, TimeZone};
+
+use crate::models::{
+    CardTransaction, EbarimtHistory, EbarimtItem, EbarimtResponse, PaymentCheckRow, PaymentDetail,
+    PaymentListItem, PaymentListRequest,
+};
+
+/// Offset of Asia/Ulaanbaatar from UTC, in seconds.
+pub const ULAANBAATAR_OFFSET_SECONDS: i32 = 8 * 3600;
+
+/// Date-time format QPay expects in outgoing requests.
+pub const QPAY_DATETIME_FORMAT: &str = "%Y-%m-%d %H:%M:%S";
+
+const NAIVE_DATETIME_FORMATS: &[&str] = &[
+    "%Y-%m-%dT%H:%M:%S%.f",
+    "%Y-%m-%d %H:%M:%S%.f",
+    "%Y-%m-%dT%H:%M",
+    "%Y-%m-%d %H:%M",
+    "%Y/%m/%d %H:%M:%S",
+    "%Y.%m.%d %H:%M:%S",
+];
+
+const OFFSET_DATETIME_FORMATS: &[&str] = &[
+    "%Y-%m-%d %H:%M:%S%.f%:z",
+    "%Y-%m-%d %H:%M:%S%.f%z",
+    "%Y-%m-%dT%H:%M:%S%.f%z",
+];
+
+const DATE_FORMATS: &[&str] = &["%Y-%m-%d", "%Y/%m/%d", "%Y.%m.%d", "%Y%m%d"];
+
+/// Return the fixed UTC+8 offset used by QPay.
+pub fn ulaanbaatar() -> FixedOffset {
+    FixedOffset::east_opt(ULAANBAATAR_OFFSET_SECONDS).expect("valid offset")
+}
+
+/// Leniently parse a QPay timestamp.
+///
+/// Returns `None` for empty or unrecognized input. Timestamps without an
+/// offset are treated as Ulaanbaatar local time; date-only values resolve
+/// to local midnight.
+pub fn parse_datetime(value: &str) -> Option<DateTime<FixedOffset>> {
+    let value = value.trim();
+    if value.is_empty() {
+        return None;
+    }
+
+    if let Ok(dt) = DateTime::parse_from_rfc3339(value) {
+        return Some(dt);
+    }
+    for fmt in OFFSET_DATETIME_FORMATS {
+        if let Ok(dt) = DateTime::parse_from_str(value, fmt) {
+            return Some(dt);
+        }
+    }
+    for fmt in NAIVE_DATETIME_FORMATS {
+        if let Ok(naive) = NaiveDateTime::parse_from_str(value, fmt) {
+            return ulaanbaatar().from_local_datetime(&naive).single();
+        }
+    }
+    for fmt in DATE_FORMATS {
+        if let Ok(date) = NaiveDate::parse_from_str(value, fmt) {
+            let naive = date.and_hms_opt(0, 0, 0)?;
+            return ulaanbaatar().from_local_datetime(&naive).single();
+        }
+    }
+    None
+}
+
+/// Leniently parse a QPay date, returning the calendar date in Ulaanbaatar.
+pub fn parse_date(value: &str) -> Option<NaiveDate> {
+    parse_datetime(value).map(|dt| dt.with_timezone(&ulaanbaatar()).date_naive())
+}
+
+/// Format a timestamp the way QPay expects it in requests
+/// (`YYYY-MM-DD HH:MM:SS`, Ulaanbaatar local time).
+pub fn format_datetime<Tz: TimeZone>(dt: &DateTime<Tz>) -> String {
+    dt.with_timezone(&ulaanbaatar())
+        .format(QPAY_DATETIME_FORMAT)
+        .to_string()
+}
+
+fn parse_opt(value: Option<&String>) -> Option<DateTime<FixedOffset>> {
+    value.and_then(|v| parse_datetime(v))
+}
+
+impl PaymentCheckRow {
+    /// Parsed `payment_date`, if present.
+    pub fn payment_date_parsed(&self) -> Option<DateTime<FixedOffset>> {
+        parse_opt(self.payment_date.as_ref())
+    }
+
+    /// Next scheduled payment time for subscriptions, if any.
+    pub fn next_payment_parsed(&self) -> Option<DateTime<FixedOffset>> {
+        parse_opt(self.next_payment_datetime.as_ref())
+            .or_else(|| parse_opt(self.next_payment_date.as_ref()))
+    }
+}
+
+impl PaymentDetail {
+    /// Parsed `payment_date`.
+    pub fn payment_date_parsed(&self) -> Option<DateTime<FixedOffset>> {
+        parse_datetime(&self.payment_date)
+    }
+
+    /// Next scheduled payment time for subscriptions, if any.
+    pub fn next_payment_parsed(&self) -> Option<DateTime<FixedOffset>> {
+        parse_opt(self.next_payment_datetime.as_ref())
+            .or_else(|| parse_opt(self.next_payment_date.as_ref()))
+    }
+}
+
+impl CardTransaction {
+    /// Parsed `transaction_date`, falling back to `date`.
+    pub fn transaction_date_parsed(&self) -> Option<DateTime<FixedOffset>> {
+        parse_opt(self.transaction_date.as_ref()).or_else(|| parse_opt(self.date.as_ref()))
+    }
+
+    /// Parsed `settlement_status_date`.
+    pub fn settlement_status_date_parsed(&self) -> Option<DateTime<FixedOffset>> {
+        parse_datetime(&self.settlement_status_date)
+    }
+}
+
+impl PaymentListItem {
+    /// Parsed `payment_date`.
+    pub fn payment_date_parsed(&self) -> Option<DateTime<FixedOffset>> {
+        parse_datetime(&self.payment_date)
+    }
+}
+
+impl PaymentListRequest {
+    /// Set `start_date` and `end_date` from typed timestamps, formatted as QPay expects.
+    pub fn with_date_range<Tz: TimeZone>(
+        mut self,
+        start: &DateTime<Tz>,
+        end: &DateTime<Tz>,
+    ) -> Self {
+        self.start_date = format_datetime(start);
+        self.end_date = format_datetime(end);
+        self
+    }
+
+    /// Parsed `start_date`.
+    pub fn start_date_parsed(&self) -> Option<DateTime<FixedOffset>> {
+        parse_datetime(&self.start_date)
+    }
+
+    /// Parsed `end_date`.
+    pub fn end_date_parsed(&self) -> Option<DateTime<FixedOffset>> {
+        parse_datetime(&self.end_date)
+    }
+}
+
+impl EbarimtResponse {
+    /// Parsed `created_date`.
+    pub fn created_date_parsed(&self) -> Option<DateTime<FixedOffset>> {
+        parse_datetime(&self.created_date)
+    }
+
+    /// Parsed `updated_date`.
+    pub fn updated_date_parsed(&self) -> Option<DateTime<FixedOffset>> {
+        parse_datetime(&self.updated_date)
+    }
+
+    /// Parsed `barimt_status_date`.
+    pub fn barimt_status_date_parsed(&self) -> Option<DateTime<FixedOffset>> {
+        parse_datetime(&self.barimt_status_date)
+    }
+}
+
+impl EbarimtItem {
+    /// Parsed `created_date`.
+    pub fn created_date_parsed(&self) -> Option<DateTime<FixedOffset>> {
+        parse_datetime(&self.created_date)
+    }
+}
+
+impl EbarimtHistory {
+    /// Parsed `ebarimt_date`.
+    pub fn ebarimt_date_parsed(&self) -> Option<DateTime<FixedOffset>> {
+        parse_datetime(&self.ebarimt_date)
+    }
+
+    /// Parsed `created_date`.
+    pub fn created_date_parsed(&self) -> Option<DateTime<FixedOffset>> {
+        parse_datetime(&self.created_date)
+    }
+}
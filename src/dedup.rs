@@ -0,0 +1,175 @@
+//! Idempotency stores for callback processing.
+//!
+//! QPay may deliver the same callback several times. A
+//! [`CallbackDedupStore`] records which payments have reached the
+//! application handler so [`CallbackProcessor`](crate::callback::CallbackProcessor)
+//! passes each `payment_id` on at most once.
+
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use crate::error::QPayError;
+use crate::file_log::{self, unix_now};
+
+/// Default time a processed payment is remembered.
+pub const DEFAULT_DEDUP_TTL: Duration = Duration::from_secs(30 * 24 * 60 * 60);
+
+/// Records which payments have been handed to the application.
+pub trait CallbackDedupStore: Send + Sync {
+    /// Claim `payment_id`. Returns `Ok(false)` if it is already claimed.
+    fn claim(&self, payment_id: &str) -> Result<bool, QPayError>;
+
+    /// Drop a claim, e.g. after the handler failed, so a redelivery is
+    /// processed again.
+    fn release(&self, payment_id: &str) -> Result<(), QPayError>;
+
+    /// Whether `payment_id` is claimed.
+    fn contains(&self, payment_id: &str) -> Result<bool, QPayError>;
+}
+
+/// In-memory store; claims expire after a TTL and are lost on restart.
+#[derive(Debug)]
+pub struct MemoryDedupStore {
+    ttl: Duration,
+    entries: Mutex<HashMap<String, Instant>>,
+}
+
+impl MemoryDedupStore {
+    /// Create a store remembering payments for `ttl`.
+    pub fn new(ttl: Duration) -> Self {
+        Self {
+            ttl,
+            entries: Mutex::new(HashMap::new()),
+        }
+    }
+}
+
+impl Default for MemoryDedupStore {
+    fn default() -> Self {
+        Self::new(DEFAULT_DEDUP_TTL)
+    }
+}
+
+impl CallbackDedupStore for MemoryDedupStore {
+    fn claim(&self, payment_id: &str) -> Result<bool, QPayError> {
+        let mut entries = self.entries.lock().unwrap_or_else(|e| e.into_inner());
+        let now = Instant::now();
+        entries.retain(|_, claimed| now.duration_since(*claimed) < self.ttl);
+        if entries.contains_key(payment_id) {
+            return Ok(false);
+        }
+        entries.insert(payment_id.to_string(), now);
+        Ok(true)
+    }
+
+    fn release(&self, payment_id: &str) -> Result<(), QPayError> {
+        let mut entries = self.entries.lock().unwrap_or_else(|e| e.into_inner());
+        entries.remove(payment_id);
+        Ok(())
+    }
+
+    fn contains(&self, payment_id: &str) -> Result<bool, QPayError> {
+        let entries = self.entries.lock().unwrap_or_else(|e| e.into_inner());
+        Ok(entries
+            .get(payment_id)
+            .is_some_and(|claimed| claimed.elapsed() < self.ttl))
+    }
+}
+
+/// File-backed store that survives restarts.
+///
+/// Claims and releases are appended to a log file, one per line, and synced
+/// to disk. The file is compacted (expired and released entries dropped)
+/// when opened. [`CallbackProcessor`](crate::callback::CallbackProcessor)
+/// calls the store on tokio's blocking thread pool.
+#[derive(Debug)]
+pub struct FileDedupStore {
+    path: PathBuf,
+    ttl: Duration,
+    entries: Mutex<HashMap<String, u64>>,
+}
+
+impl FileDedupStore {
+    /// Open or create the store at `path`, remembering payments for `ttl`.
+    pub fn open(path: impl AsRef<Path>, ttl: Duration) -> Result<Self, QPayError> {
+        let path = path.as_ref().to_path_buf();
+        let mut entries = HashMap::new();
+
+        for line in file_log::read_lines(&path)? {
+            match line.split_once(' ') {
+                Some(("-", payment_id)) => {
+                    entries.remove(payment_id);
+                }
+                Some((claimed_at, payment_id)) => {
+                    if let Ok(claimed_at) = claimed_at.parse::<u64>() {
+                        entries.insert(payment_id.to_string(), claimed_at);
+                    }
+                }
+                None => {}
+            }
+        }
+
+        let now = unix_now();
+        entries.retain(|_, claimed_at| now.saturating_sub(*claimed_at) < ttl.as_secs());
+
+        let store = Self {
+            path,
+            ttl,
+            entries: Mutex::new(entries),
+        };
+        store.compact()?;
+        Ok(store)
+    }
+
+    fn compact(&self) -> Result<(), QPayError> {
+        let entries = self.entries.lock().unwrap_or_else(|e| e.into_inner());
+        let contents: String = entries
+            .iter()
+            .map(|(payment_id, claimed_at)| format!("{} {}\n", claimed_at, payment_id))
+            .collect();
+        file_log::compact(&self.path, &contents)
+    }
+
+    fn append(&self, line: &str) -> Result<(), QPayError> {
+        file_log::append(&self.path, line)
+    }
+
+    fn is_live(&self, claimed_at: u64) -> bool {
+        unix_now().saturating_sub(claimed_at) < self.ttl.as_secs()
+    }
+}
+
+impl CallbackDedupStore for FileDedupStore {
+    fn claim(&self, payment_id: &str) -> Result<bool, QPayError> {
+        let payment_id = payment_id.trim();
+        let mut entries = self.entries.lock().unwrap_or_else(|e| e.into_inner());
+        if entries
+            .get(payment_id)
+            .is_some_and(|claimed_at| self.is_live(*claimed_at))
+        {
+            return Ok(false);
+        }
+        let now = unix_now();
+        self.append(&format!("{} {}", now, payment_id))?;
+        entries.insert(payment_id.to_string(), now);
+        Ok(true)
+    }
+
+    fn release(&self, payment_id: &str) -> Result<(), QPayError> {
+        let payment_id = payment_id.trim();
+        let mut entries = self.entries.lock().unwrap_or_else(|e| e.into_inner());
+        if entries.remove(payment_id).is_some() {
+            self.append(&format!("- {}", payment_id))?;
+        }
+        Ok(())
+    }
+
+    fn contains(&self, payment_id: &str) -> Result<bool, QPayError> {
+        let entries = self.entries.lock().unwrap_or_else(|e| e.into_inner());
+        Ok(entries
+            .get(payment_id.trim())
+            .is_some_and(|claimed_at| self.is_live(*claimed_at)))
+    }
+}
@@ -1,6 +1,7 @@
 use crate::client::QPayClient;
 use crate::error::QPayError;
 use crate::models::{CreateEbarimtRequest, EbarimtResponse};
+use crate::response::{ApiResponse, RawClient};
 
 impl QPayClient {
     /// Create an ebarimt (electronic tax receipt) for a payment.
@@ -24,3 +25,28 @@ impl QPayClient {
             .await
     }
 }
+
+impl RawClient<'_> {
+    /// Create an ebarimt (electronic tax receipt) for a payment.
+    /// POST /v2/ebarimt_v3/create
+    pub async fn create_ebarimt(
+        &self,
+        req: &CreateEbarimtRequest,
+    ) -> Result<ApiResponse<EbarimtResponse>, QPayError> {
+        self.client
+            .do_request_raw(reqwest::Method::POST, "/v2/ebarimt_v3/create", Some(req))
+            .await
+    }
+
+    /// Cancel an ebarimt by payment ID.
+    /// DELETE /v2/ebarimt_v3/{id}
+    pub async fn cancel_ebarimt(
+        &self,
+        payment_id: &str,
+    ) -> Result<ApiResponse<EbarimtResponse>, QPayError> {
+        let path = format!("/v2/ebarimt_v3/{}", payment_id);
+        self.client
+            .do_request_raw::<(), EbarimtResponse>(reqwest::Method::DELETE, &path, None)
+            .await
+    }
+}
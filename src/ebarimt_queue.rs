@@ -0,0 +1,522 @@
+//! Durable retry queue for ebarimt submissions.
+//!
+//! A receipt that `create_ebarimt` could not issue is kept in an
+//! [`EbarimtQueueStore`] and retried with exponential backoff. Failures that
+//! retrying cannot fix, or that exhaust the attempts, are moved to a dead
+//! letter list to be inspected, retried or dropped by hand.
+//!
+//! Built [`with_issuer`](EbarimtQueue::with_issuer), the queue issues through
+//! an [`EbarimtIssuer`], and an
+//! [`EbarimtCallbackHandler`](crate::issuance::EbarimtCallbackHandler) built
+//! [`with_queue`](crate::issuance::EbarimtCallbackHandler::with_queue) queues
+//! the receipts it could not issue.
+
+use std::collections::{BTreeMap, HashSet};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use serde::{Deserialize, Serialize};
+
+use crate::client::QPayClient;
+use crate::error::{QPayError, ERR_EBARIMT_NOT_REGISTERED, ERR_PAYMENT_NOT_PAID};
+use crate::file_log::{self, unix_now};
+use crate::issuance::EbarimtIssuer;
+use crate::models::{CreateEbarimtRequest, EbarimtResponse};
+use crate::polling::CancellationToken;
+
+/// Attempts per receipt, including the first, before it is dead-lettered.
+pub const EBARIMT_QUEUE_MAX_ATTEMPTS: u32 = 10;
+
+/// Delay before the first retry; doubled for each further retry.
+pub const EBARIMT_QUEUE_BASE_DELAY: Duration = Duration::from_secs(60);
+
+/// Upper bound for the retry delay.
+pub const EBARIMT_QUEUE_MAX_DELAY: Duration = Duration::from_secs(6 * 60 * 60);
+
+/// Whether a failed `create_ebarimt` call is worth retrying.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EbarimtFailure {
+    Retryable,
+    Permanent,
+}
+
+impl EbarimtFailure {
+    /// Classify a `create_ebarimt` error.
+    ///
+    /// Transient errors (see [`QPayError::is_transient`]) are retryable, as
+    /// are [`ERR_EBARIMT_NOT_REGISTERED`] and [`ERR_PAYMENT_NOT_PAID`], which
+    /// clear once the merchant's ebarimt registration or the payment
+    /// completes. Other API errors (unknown payment, invalid receiver, ...)
+    /// and local errors need the request fixed and are permanent.
+    pub fn classify(err: &QPayError) -> Self {
+        match err {
+            e if e.is_transient() => Self::Retryable,
+            QPayError::Api { code, .. }
+                if code == ERR_EBARIMT_NOT_REGISTERED || code == ERR_PAYMENT_NOT_PAID =>
+            {
+                Self::Retryable
+            }
+            QPayError::Token(_) | QPayError::Timeout(_) => Self::Retryable,
+            _ => Self::Permanent,
+        }
+    }
+}
+
+/// A receipt waiting in the queue.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct QueuedEbarimt {
+    pub request: CreateEbarimtRequest,
+    /// Failed attempts so far.
+    pub attempts: u32,
+    /// Unix seconds of the next attempt.
+    pub next_attempt_at: u64,
+    pub last_error: Option<String>,
+    /// Set once the entry is no longer retried automatically.
+    pub dead_letter: bool,
+    /// Unix seconds when the entry was queued.
+    pub enqueued_at: u64,
+}
+
+impl QueuedEbarimt {
+    /// A new entry, due immediately.
+    pub fn new(request: CreateEbarimtRequest) -> Self {
+        let now = unix_now();
+        Self {
+            request,
+            attempts: 0,
+            next_attempt_at: now,
+            last_error: None,
+            dead_letter: false,
+            enqueued_at: now,
+        }
+    }
+
+    /// The payment the receipt is for; entries are keyed by it.
+    pub fn payment_id(&self) -> &str {
+        &self.request.payment_id
+    }
+}
+
+/// Storage for queued receipts, keyed by payment id.
+pub trait EbarimtQueueStore: Send + Sync {
+    /// Insert or replace an entry.
+    fn put(&self, entry: &QueuedEbarimt) -> Result<(), QPayError>;
+
+    /// An entry by payment id.
+    fn get(&self, payment_id: &str) -> Result<Option<QueuedEbarimt>, QPayError>;
+
+    /// Remove an entry, returning it.
+    fn remove(&self, payment_id: &str) -> Result<Option<QueuedEbarimt>, QPayError>;
+
+    /// All entries, oldest first.
+    fn list(&self) -> Result<Vec<QueuedEbarimt>, QPayError>;
+}
+
+/// In-memory store; the queue is lost on restart.
+#[derive(Debug, Default)]
+pub struct MemoryEbarimtQueueStore {
+    entries: Mutex<BTreeMap<String, QueuedEbarimt>>,
+}
+
+impl MemoryEbarimtQueueStore {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+impl EbarimtQueueStore for MemoryEbarimtQueueStore {
+    fn put(&self, entry: &QueuedEbarimt) -> Result<(), QPayError> {
+        let mut entries = self.entries.lock().unwrap_or_else(|e| e.into_inner());
+        entries.insert(entry.payment_id().to_string(), entry.clone());
+        Ok(())
+    }
+
+    fn get(&self, payment_id: &str) -> Result<Option<QueuedEbarimt>, QPayError> {
+        let entries = self.entries.lock().unwrap_or_else(|e| e.into_inner());
+        Ok(entries.get(payment_id).cloned())
+    }
+
+    fn remove(&self, payment_id: &str) -> Result<Option<QueuedEbarimt>, QPayError> {
+        let mut entries = self.entries.lock().unwrap_or_else(|e| e.into_inner());
+        Ok(entries.remove(payment_id))
+    }
+
+    fn list(&self) -> Result<Vec<QueuedEbarimt>, QPayError> {
+        let entries = self.entries.lock().unwrap_or_else(|e| e.into_inner());
+        Ok(oldest_first(entries.values()))
+    }
+}
+
+/// One line of the [`FileEbarimtQueueStore`] log.
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+enum LogRecord {
+    Put(QueuedEbarimt),
+    Remove(String),
+}
+
+/// File-backed store that survives restarts.
+///
+/// Changes are appended to a log file as JSON lines and synced before the
+/// call returns. The file is compacted when opened; a torn last line from a
+/// crash is skipped.
+#[derive(Debug)]
+pub struct FileEbarimtQueueStore {
+    path: PathBuf,
+    entries: Mutex<BTreeMap<String, QueuedEbarimt>>,
+}
+
+impl FileEbarimtQueueStore {
+    /// Open or create the store at `path`.
+    pub fn open(path: impl AsRef<Path>) -> Result<Self, QPayError> {
+        let path = path.as_ref().to_path_buf();
+        let mut entries = BTreeMap::new();
+
+        for line in file_log::read_lines(&path)? {
+            match serde_json::from_str(&line) {
+                Ok(LogRecord::Put(entry)) => {
+                    entries.insert(entry.payment_id().to_string(), entry);
+                }
+                Ok(LogRecord::Remove(payment_id)) => {
+                    entries.remove(&payment_id);
+                }
+                Err(_) => {}
+            }
+        }
+
+        let store = Self {
+            path,
+            entries: Mutex::new(entries),
+        };
+        store.compact()?;
+        Ok(store)
+    }
+
+    fn compact(&self) -> Result<(), QPayError> {
+        let entries = self.entries.lock().unwrap_or_else(|e| e.into_inner());
+        let mut contents = String::new();
+        for entry in entries.values() {
+            contents.push_str(&serde_json::to_string(&LogRecord::Put(entry.clone()))?);
+            contents.push('\n');
+        }
+        file_log::compact(&self.path, &contents)
+    }
+
+    fn append(&self, record: &LogRecord) -> Result<(), QPayError> {
+        file_log::append(&self.path, &serde_json::to_string(record)?)
+    }
+}
+
+impl EbarimtQueueStore for FileEbarimtQueueStore {
+    fn put(&self, entry: &QueuedEbarimt) -> Result<(), QPayError> {
+        let mut entries = self.entries.lock().unwrap_or_else(|e| e.into_inner());
+        self.append(&LogRecord::Put(entry.clone()))?;
+        entries.insert(entry.payment_id().to_string(), entry.clone());
+        Ok(())
+    }
+
+    fn get(&self, payment_id: &str) -> Result<Option<QueuedEbarimt>, QPayError> {
+        let entries = self.entries.lock().unwrap_or_else(|e| e.into_inner());
+        Ok(entries.get(payment_id).cloned())
+    }
+
+    fn remove(&self, payment_id: &str) -> Result<Option<QueuedEbarimt>, QPayError> {
+        let mut entries = self.entries.lock().unwrap_or_else(|e| e.into_inner());
+        if !entries.contains_key(payment_id) {
+            return Ok(None);
+        }
+        self.append(&LogRecord::Remove(payment_id.to_string()))?;
+        Ok(entries.remove(payment_id))
+    }
+
+    fn list(&self) -> Result<Vec<QueuedEbarimt>, QPayError> {
+        let entries = self.entries.lock().unwrap_or_else(|e| e.into_inner());
+        Ok(oldest_first(entries.values()))
+    }
+}
+
+/// Result of one attempt to issue a queued receipt.
+#[derive(Debug)]
+pub enum EbarimtAttempt {
+    /// The receipt was issued and the entry removed.
+    Issued {
+        payment_id: String,
+        receipt: Box<EbarimtResponse>,
+    },
+    /// The attempt failed and will be retried at `entry.next_attempt_at`.
+    Retrying(QueuedEbarimt),
+    /// The attempt failed permanently or was the last one allowed.
+    DeadLettered(QueuedEbarimt),
+    /// Another attempt for the payment was running; nothing was done.
+    InFlight(String),
+}
+
+/// Submits ebarimt requests, retrying failures from a durable store.
+pub struct EbarimtQueue {
+    client: Arc<QPayClient>,
+    store: Arc<dyn EbarimtQueueStore>,
+    issuer: Option<Arc<EbarimtIssuer>>,
+    max_attempts: u32,
+    base_delay: Duration,
+    max_delay: Duration,
+    in_flight: Mutex<HashSet<String>>,
+}
+
+impl EbarimtQueue {
+    /// Create a queue backed by a [`MemoryEbarimtQueueStore`].
+    pub fn new(client: Arc<QPayClient>) -> Self {
+        Self::with_store(client, MemoryEbarimtQueueStore::new())
+    }
+
+    /// Create a queue backed by `store`, e.g. a [`FileEbarimtQueueStore`].
+    pub fn with_store(client: Arc<QPayClient>, store: impl EbarimtQueueStore + 'static) -> Self {
+        Self {
+            client,
+            store: Arc::new(store),
+            issuer: None,
+            max_attempts: EBARIMT_QUEUE_MAX_ATTEMPTS,
+            base_delay: EBARIMT_QUEUE_BASE_DELAY,
+            max_delay: EBARIMT_QUEUE_MAX_DELAY,
+            in_flight: Mutex::new(HashSet::new()),
+        }
+    }
+
+    /// Issue through `issuer`, so receipts are recorded in its store and a
+    /// payment it has already issued is not sent again.
+    pub fn with_issuer(mut self, issuer: Arc<EbarimtIssuer>) -> Self {
+        self.issuer = Some(issuer);
+        self
+    }
+
+    /// Dead-letter an entry after `max_attempts` failed attempts.
+    pub fn with_max_attempts(mut self, max_attempts: u32) -> Self {
+        self.max_attempts = max_attempts.max(1);
+        self
+    }
+
+    /// Retry after `base`, doubling for each further failure up to `max`.
+    pub fn with_backoff(mut self, base: Duration, max: Duration) -> Self {
+        self.base_delay = base;
+        self.max_delay = max;
+        self
+    }
+
+    /// The underlying store.
+    pub fn store(&self) -> &dyn EbarimtQueueStore {
+        &*self.store
+    }
+
+    /// Queue a request for the next [`process_due`](Self::process_due)
+    /// without calling the API. A payment already in the queue keeps its
+    /// existing entry.
+    pub async fn enqueue(&self, req: CreateEbarimtRequest) -> Result<QueuedEbarimt, QPayError> {
+        self.call_store(move |store| {
+            if let Some(entry) = store.get(&req.payment_id)? {
+                return Ok(entry);
+            }
+            let entry = QueuedEbarimt::new(req);
+            store.put(&entry)?;
+            Ok(entry)
+        })
+        .await
+    }
+
+    /// Try to issue the receipt now, queueing it if that fails.
+    ///
+    /// The entry is stored before the API is called, so a crash during the
+    /// call leaves it in the queue. Returns [`EbarimtAttempt::InFlight`] if
+    /// the payment is being attempted already.
+    pub async fn submit(&self, req: CreateEbarimtRequest) -> Result<EbarimtAttempt, QPayError> {
+        let Some(_guard) = self.begin(&req.payment_id) else {
+            return Ok(EbarimtAttempt::InFlight(req.payment_id));
+        };
+        let entry = self.enqueue(req).await?;
+        self.attempt(entry).await
+    }
+
+    /// Attempt every entry that is due and not dead-lettered, oldest first.
+    /// Entries being attempted by another call are skipped.
+    pub async fn process_due(&self) -> Result<Vec<EbarimtAttempt>, QPayError> {
+        let now = unix_now();
+        let is_due = |entry: &QueuedEbarimt| !entry.dead_letter && entry.next_attempt_at <= now;
+        let mut results = Vec::new();
+        for entry in self.call_store(|store| store.list()).await? {
+            if !is_due(&entry) {
+                continue;
+            }
+            let Some(_guard) = self.begin(entry.payment_id()) else {
+                continue;
+            };
+            // Another attempt may have finished since the list was read
+            if let Some(entry) = self.get(entry.payment_id()).await?.filter(is_due) {
+                results.push(self.attempt(entry).await?);
+            }
+        }
+        Ok(results)
+    }
+
+    /// Call [`process_due`](Self::process_due) every `interval` until
+    /// `cancellation` is triggered. Store errors are logged and retried on
+    /// the next tick.
+    pub async fn run(&self, interval: Duration, cancellation: CancellationToken) {
+        loop {
+            if let Err(e) = self.process_due().await {
+                log::error!("qpay: ebarimt queue processing failed: {}", e);
+            }
+            tokio::select! {
+                _ = cancellation.cancelled() => return,
+                _ = tokio::time::sleep(interval) => {}
+            }
+        }
+    }
+
+    /// Entries waiting for a retry.
+    pub async fn pending(&self) -> Result<Vec<QueuedEbarimt>, QPayError> {
+        let entries = self.call_store(|store| store.list()).await?;
+        Ok(entries.into_iter().filter(|e| !e.dead_letter).collect())
+    }
+
+    /// Entries that are no longer retried automatically.
+    pub async fn dead_letters(&self) -> Result<Vec<QueuedEbarimt>, QPayError> {
+        let entries = self.call_store(|store| store.list()).await?;
+        Ok(entries.into_iter().filter(|e| e.dead_letter).collect())
+    }
+
+    /// Attempt a dead-lettered entry again now, with a fresh attempt count.
+    /// Returns `None` if there is no such dead letter.
+    pub async fn retry_dead_letter(
+        &self,
+        payment_id: &str,
+    ) -> Result<Option<EbarimtAttempt>, QPayError> {
+        let Some(_guard) = self.begin(payment_id) else {
+            return Ok(Some(EbarimtAttempt::InFlight(payment_id.to_string())));
+        };
+        let Some(mut entry) = self.get(payment_id).await?.filter(|e| e.dead_letter) else {
+            return Ok(None);
+        };
+        entry.dead_letter = false;
+        entry.attempts = 0;
+        entry.next_attempt_at = unix_now();
+        let entry = self.put(entry).await?;
+        self.attempt(entry).await.map(Some)
+    }
+
+    /// Remove a dead-lettered entry, e.g. after issuing the receipt by
+    /// other means.
+    pub async fn drop_dead_letter(
+        &self,
+        payment_id: &str,
+    ) -> Result<Option<QueuedEbarimt>, QPayError> {
+        let payment_id = payment_id.to_string();
+        self.call_store(move |store| match store.get(&payment_id)? {
+            Some(entry) if entry.dead_letter => store.remove(&payment_id),
+            _ => Ok(None),
+        })
+        .await
+    }
+
+    /// Call the store on the blocking thread pool, as stores may do file or
+    /// network I/O.
+    async fn call_store<T: Send + 'static>(
+        &self,
+        op: impl FnOnce(&dyn EbarimtQueueStore) -> Result<T, QPayError> + Send + 'static,
+    ) -> Result<T, QPayError> {
+        let store = Arc::clone(&self.store);
+        tokio::task::spawn_blocking(move || op(&*store))
+            .await
+            .map_err(|e| QPayError::Storage(e.to_string()))?
+    }
+
+    async fn get(&self, payment_id: &str) -> Result<Option<QueuedEbarimt>, QPayError> {
+        let payment_id = payment_id.to_string();
+        self.call_store(move |store| store.get(&payment_id)).await
+    }
+
+    /// Store `entry` and hand it back.
+    async fn put(&self, entry: QueuedEbarimt) -> Result<QueuedEbarimt, QPayError> {
+        self.call_store(move |store| store.put(&entry).map(|()| entry))
+            .await
+    }
+
+    /// Mark `payment_id` as being attempted, unless it already is.
+    fn begin(&self, payment_id: &str) -> Option<InFlightGuard<'_>> {
+        let mut in_flight = self.in_flight.lock().unwrap_or_else(|e| e.into_inner());
+        in_flight
+            .insert(payment_id.to_string())
+            .then(|| InFlightGuard {
+                in_flight: &self.in_flight,
+                payment_id: payment_id.to_string(),
+            })
+    }
+
+    /// Attempt `entry`; the caller holds its [`InFlightGuard`].
+    async fn attempt(&self, mut entry: QueuedEbarimt) -> Result<EbarimtAttempt, QPayError> {
+        let result = match &self.issuer {
+            // The queue does its own backoff, so the issuer tries once
+            Some(issuer) => issuer.issue_request(&entry.request, 1).await,
+            None => self.client.create_ebarimt(&entry.request).await,
+        };
+        let err = match result {
+            Ok(receipt) => {
+                let payment_id = entry.request.payment_id.clone();
+                self.call_store(move |store| store.remove(&payment_id))
+                    .await?;
+                return Ok(EbarimtAttempt::Issued {
+                    payment_id: entry.request.payment_id,
+                    receipt: Box::new(receipt),
+                });
+            }
+            Err(e) => e,
+        };
+
+        entry.attempts += 1;
+        entry.last_error = Some(err.to_string());
+        let permanent = EbarimtFailure::classify(&err) == EbarimtFailure::Permanent;
+        if permanent || entry.attempts >= self.max_attempts {
+            log::error!(
+                "qpay: ebarimt for payment {} dead-lettered after {} attempt(s): {}",
+                entry.payment_id(),
+                entry.attempts,
+                err
+            );
+            entry.dead_letter = true;
+            let entry = self.put(entry).await?;
+            return Ok(EbarimtAttempt::DeadLettered(entry));
+        }
+
+        let delay = self
+            .base_delay
+            .saturating_mul(2u32.saturating_pow(entry.attempts - 1))
+            .min(self.max_delay);
+        entry.next_attempt_at = unix_now() + delay.as_secs();
+        log::warn!(
+            "qpay: ebarimt for payment {} failed (attempt {}), retrying in {:?}: {}",
+            entry.payment_id(),
+            entry.attempts,
+            delay,
+            err
+        );
+        let entry = self.put(entry).await?;
+        Ok(EbarimtAttempt::Retrying(entry))
+    }
+}
+
+/// Clears a payment's in-flight mark when dropped.
+struct InFlightGuard<'a> {
+    in_flight: &'a Mutex<HashSet<String>>,
+    payment_id: String,
+}
+
+impl Drop for InFlightGuard<'_> {
+    fn drop(&mut self) {
+        let mut in_flight = self.in_flight.lock().unwrap_or_else(|e| e.into_inner());
+        in_flight.remove(&self.payment_id);
+    }
+}
+
+fn oldest_first<'a>(entries: impl Iterator<Item = &'a QueuedEbarimt>) -> Vec<QueuedEbarimt> {
+    let mut entries: Vec<_> = entries.cloned().collect();
+    entries.sort_by(|a, b| (a.enqueued_at, a.payment_id()).cmp(&(b.enqueued_at, b.payment_id())));
+    entries
+}
@@ -1,7 +1,15 @@
 use serde::Deserialize;
 
+#[cfg(feature = "chrono")]
+use crate::export::DateRangeError;
+use crate::lifecycle::LifecycleError;
+use crate::validation::ValidationError;
+
 /// QPay API error.
+///
+/// New variants may be added in minor releases.
 #[derive(Debug, thiserror::Error)]
+#[non_exhaustive]
 pub enum QPayError {
     /// HTTP/network error from reqwest.
     #[error("http error: {0}")]
@@ -27,6 +35,50 @@ pub enum QPayError {
     /// Token acquisition failed.
     #[error("failed to get token: {0}")]
     Token(String),
+
+    /// Request data failed client-side validation.
+    #[error("validation error: {0}")]
+    Validation(#[from] ValidationError),
+
+    /// A wait or poll ran past its deadline.
+    #[error("timed out: {0}")]
+    Timeout(String),
+
+    /// A wait or poll was cancelled by the caller.
+    #[error("cancelled: {0}")]
+    Cancelled(String),
+
+    /// A local store (callback de-duplication, queues, ...) failed.
+    #[error("storage error: {0}")]
+    Storage(String),
+
+    /// An invoice lifecycle step is not allowed in the current state.
+    #[error("lifecycle error: {0}")]
+    Lifecycle(#[from] LifecycleError),
+
+    /// A payment listing date range could not be split.
+    #[cfg(feature = "chrono")]
+    #[error(transparent)]
+    DateRange(#[from] DateRangeError),
+}
+
+impl QPayError {
+    /// Whether retrying the same request may succeed: network errors,
+    /// timeouts, rate limiting (429) and server errors (5xx).
+    pub fn is_transient(&self) -> bool {
+        match self {
+            QPayError::Http(_) => true,
+            QPayError::Api { status_code, .. } => {
+                matches!(*status_code, 408 | 429) || *status_code >= 500
+            }
+            _ => false,
+        }
+    }
+
+    /// Whether this is an API error with error code `code`.
+    pub(crate) fn is_code(&self, code: &str) -> bool {
+        matches!(self, QPayError::Api { code: actual, .. } if actual == code)
+    }
 }
 
 /// Helper struct for deserializing QPay error JSON responses.
@@ -0,0 +1,149 @@
+//! Listing payments over long date ranges (requires the `chrono` feature).
+//!
+//! Wide `start_date`/`end_date` ranges are slow and can hit server limits,
+//! so the range is split into per-day or per-hour windows in Ulaanbaatar
+//! local time that are listed separately and merged back together.
+
+use std::collections::HashSet;
+use std::future::ready;
+
+use chrono::{DateTime, Duration, FixedOffset, NaiveTime, Timelike};
+use futures::stream::{self, Stream, StreamExt, TryStreamExt};
+
+use crate::client::QPayClient;
+use crate::datetime::{format_datetime, parse_date, parse_datetime, ulaanbaatar};
+use crate::error::QPayError;
+use crate::models::{Offset, PaymentListItem, PaymentListRequest};
+
+/// A `start_date`/`end_date` range that cannot be parsed or ends before it
+/// starts.
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+#[error("invalid date range: {start_date:?} to {end_date:?}")]
+pub struct DateRangeError {
+    pub start_date: String,
+    pub end_date: String,
+}
+
+/// Window size used to split a date range.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DateWindow {
+    /// One window per calendar day in Ulaanbaatar.
+    Day,
+    /// One window per hour in Ulaanbaatar.
+    Hour,
+}
+
+impl DateWindow {
+    /// Start of the window following the one containing `dt`.
+    fn next_boundary(self, dt: DateTime<FixedOffset>) -> DateTime<FixedOffset> {
+        let local = dt.with_timezone(&ulaanbaatar());
+        match self {
+            Self::Day => {
+                let midnight = local.with_time(NaiveTime::MIN).unwrap();
+                midnight + Duration::days(1)
+            }
+            Self::Hour => {
+                let hour = NaiveTime::from_hms_opt(local.hour(), 0, 0).unwrap();
+                local.with_time(hour).unwrap() + Duration::hours(1)
+            }
+        }
+    }
+}
+
+impl PaymentListRequest {
+    /// Split the request's date range into consecutive, non-overlapping
+    /// windows, each starting on the first page.
+    ///
+    /// Both ends are inclusive; a date-only `end_date` covers that whole day.
+    pub fn split_date_range(
+        &self,
+        window: DateWindow,
+    ) -> Result<Vec<PaymentListRequest>, DateRangeError> {
+        let invalid = || DateRangeError {
+            start_date: self.start_date.clone(),
+            end_date: self.end_date.clone(),
+        };
+        let start = parse_datetime(&self.start_date).ok_or_else(invalid)?;
+        let end = if self.end_date.contains(':') {
+            parse_datetime(&self.end_date).ok_or_else(invalid)?
+        } else {
+            let date = parse_date(&self.end_date).ok_or_else(invalid)?;
+            let next_day = date.succ_opt().ok_or_else(invalid)?;
+            let midnight = next_day.and_time(NaiveTime::MIN);
+            midnight.and_local_timezone(ulaanbaatar()).unwrap() - Duration::seconds(1)
+        };
+        if start > end {
+            return Err(invalid());
+        }
+
+        let mut windows = Vec::new();
+        let mut window_start = start;
+        while window_start <= end {
+            let next = window.next_boundary(window_start);
+            let window_end = (next - Duration::seconds(1)).min(end);
+            windows.push(PaymentListRequest {
+                start_date: format_datetime(&window_start),
+                end_date: format_datetime(&window_end),
+                offset: Offset {
+                    page_number: 1,
+                    page_limit: self.offset.page_limit,
+                },
+                ..self.clone()
+            });
+            window_start = next;
+        }
+        Ok(windows)
+    }
+}
+
+impl QPayClient {
+    /// List payments over a long date range by splitting it into `window`
+    /// sized requests, running up to `concurrency` of them at once.
+    ///
+    /// Payments are yielded in `payment_date` order and de-duplicated by
+    /// `payment_id`. The stream ends after the first error.
+    pub fn list_payments_chunked(
+        &self,
+        req: &PaymentListRequest,
+        window: DateWindow,
+        concurrency: usize,
+    ) -> impl Stream<Item = Result<PaymentListItem, QPayError>> + '_ {
+        let windows = match req.split_date_range(window) {
+            Ok(windows) => windows,
+            Err(e) => return stream::iter(vec![Err(e.into())]).left_stream(),
+        };
+
+        stream::iter(windows)
+            .map(move |req| async move {
+                let mut rows: Vec<PaymentListItem> =
+                    self.list_payments_stream(&req, 0).try_collect().await?;
+                // Rows without a parseable date go last.
+                rows.sort_by_key(|row| {
+                    let date = row.payment_date_parsed();
+                    (date.is_none(), date)
+                });
+                Ok::<_, QPayError>(rows)
+            })
+            .buffered(concurrency.max(1))
+            .flat_map(|window| match window {
+                Ok(rows) => stream::iter(rows.into_iter().map(Ok).collect::<Vec<_>>()),
+                Err(e) => stream::iter(vec![Err(e)]),
+            })
+            .scan((HashSet::new(), false), |(seen, failed), item| {
+                if *failed {
+                    return ready(None);
+                }
+                let item = match item {
+                    Ok(row) if !seen.insert(row.payment_id.clone()) => None,
+                    Ok(row) => Some(Ok(row)),
+                    Err(e) => {
+                        *failed = true;
+                        Some(Err(e))
+                    }
+                };
+                ready(Some(item))
+            })
+            .filter_map(ready)
+            .right_stream()
+    }
+}
@@ -0,0 +1,89 @@
+//! Append-only log files shared by the file-backed stores.
+//!
+//! A store keeps its state in memory and appends each change to a log file,
+//! one line per change, synced before the call returns. When the store is
+//! opened the log is replayed and rewritten with only the live state.
+
+use std::fs::{self, File, OpenOptions};
+use std::io::{BufRead, BufReader, Write};
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::error::QPayError;
+
+/// The lines of the log at `path`; empty if it does not exist yet.
+pub(crate) fn read_lines(path: &Path) -> Result<Vec<String>, QPayError> {
+    match File::open(path) {
+        Ok(file) => BufReader::new(file)
+            .lines()
+            .collect::<Result<_, _>>()
+            .map_err(storage_error),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(Vec::new()),
+        Err(e) => Err(storage_error(e)),
+    }
+}
+
+/// Append one line to the log and sync it to disk.
+pub(crate) fn append(path: &Path, line: &str) -> Result<(), QPayError> {
+    let mut file = OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(path)
+        .map_err(storage_error)?;
+    writeln!(file, "{}", line).map_err(storage_error)?;
+    file.sync_data().map_err(storage_error)
+}
+
+/// Replace the log with `contents`.
+///
+/// The contents are written and synced to `<file name>.tmp` next to the log,
+/// then renamed over it. The directory is synced before the rename, so the
+/// temporary file exists on disk, and after it, so the rename does; a crash
+/// leaves either the old or the new log.
+pub(crate) fn compact(path: &Path, contents: &str) -> Result<(), QPayError> {
+    let tmp = tmp_path(path);
+    let mut file = File::create(&tmp).map_err(storage_error)?;
+    file.write_all(contents.as_bytes()).map_err(storage_error)?;
+    file.sync_all().map_err(storage_error)?;
+    drop(file);
+
+    sync_dir(path)?;
+    fs::rename(&tmp, path).map_err(storage_error)?;
+    sync_dir(path)
+}
+
+fn tmp_path(path: &Path) -> PathBuf {
+    let mut tmp = path.as_os_str().to_os_string();
+    tmp.push(".tmp");
+    PathBuf::from(tmp)
+}
+
+/// Sync the directory holding `path`, so entries created or renamed in it
+/// survive a crash.
+#[cfg(unix)]
+fn sync_dir(path: &Path) -> Result<(), QPayError> {
+    let dir = match path.parent() {
+        Some(dir) if !dir.as_os_str().is_empty() => dir,
+        _ => Path::new("."),
+    };
+    File::open(dir)
+        .and_then(|dir| dir.sync_all())
+        .map_err(storage_error)
+}
+
+/// Directories cannot be opened for syncing on this platform.
+#[cfg(not(unix))]
+fn sync_dir(_path: &Path) -> Result<(), QPayError> {
+    Ok(())
+}
+
+pub(crate) fn unix_now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0)
+}
+
+pub(crate) fn storage_error(e: std::io::Error) -> QPayError {
+    QPayError::Storage(e.to_string())
+}
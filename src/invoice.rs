@@ -1,38 +1,58 @@
 use crate::client::QPayClient;
 use crate::error::QPayError;
 use crate::models::{
-    CreateEbarimtInvoiceRequest, CreateInvoiceRequest, CreateSimpleInvoiceRequest,
+    CreateEbarimtInvoiceRequest, CreateInvoiceRequest, CreateSimpleInvoiceRequest, InvoiceDetail,
     InvoiceResponse,
 };
+use crate::response::{ApiResponse, RawClient};
 
 impl QPayClient {
     /// Create a detailed invoice with full options.
+    ///
+    /// If a callback secret is configured, `callback_url` is signed for
+    /// `sender_invoice_no`; an empty one becomes the configured callback
+    /// URL first.
     /// POST /v2/invoice
     pub async fn create_invoice(
         &self,
         req: &CreateInvoiceRequest,
     ) -> Result<InvoiceResponse, QPayError> {
-        self.do_request(reqwest::Method::POST, "/v2/invoice", Some(req))
+        let req = self.with_signed_callback_url(req)?;
+        self.do_request(reqwest::Method::POST, "/v2/invoice", Some(&*req))
             .await
     }
 
     /// Create a simple invoice with minimal fields.
+    ///
+    /// The callback URL is signed as in [`create_invoice`](Self::create_invoice).
     /// POST /v2/invoice
     pub async fn create_simple_invoice(
         &self,
         req: &CreateSimpleInvoiceRequest,
     ) -> Result<InvoiceResponse, QPayError> {
-        self.do_request(reqwest::Method::POST, "/v2/invoice", Some(req))
+        let req = self.with_signed_callback_url(req)?;
+        self.do_request(reqwest::Method::POST, "/v2/invoice", Some(&*req))
             .await
     }
 
     /// Create an invoice with ebarimt (tax) information.
+    ///
+    /// The callback URL is signed as in [`create_invoice`](Self::create_invoice).
     /// POST /v2/invoice
     pub async fn create_ebarimt_invoice(
         &self,
         req: &CreateEbarimtInvoiceRequest,
     ) -> Result<InvoiceResponse, QPayError> {
-        self.do_request(reqwest::Method::POST, "/v2/invoice", Some(req))
+        let req = self.with_signed_callback_url(req)?;
+        self.do_request(reqwest::Method::POST, "/v2/invoice", Some(&*req))
+            .await
+    }
+
+    /// Retrieve an invoice's current state, lines and payments by ID.
+    /// GET /v2/invoice/{id}
+    pub async fn get_invoice(&self, invoice_id: &str) -> Result<InvoiceDetail, QPayError> {
+        let path = format!("/v2/invoice/{}", invoice_id);
+        self.do_request::<(), InvoiceDetail>(reqwest::Method::GET, &path, None)
             .await
     }
 
@@ -44,3 +64,62 @@ impl QPayClient {
             .await
     }
 }
+
+impl RawClient<'_> {
+    /// Create a detailed invoice with full options.
+    /// POST /v2/invoice
+    pub async fn create_invoice(
+        &self,
+        req: &CreateInvoiceRequest,
+    ) -> Result<ApiResponse<InvoiceResponse>, QPayError> {
+        let req = self.client.with_signed_callback_url(req)?;
+        self.client
+            .do_request_raw(reqwest::Method::POST, "/v2/invoice", Some(&*req))
+            .await
+    }
+
+    /// Create a simple invoice with minimal fields.
+    /// POST /v2/invoice
+    pub async fn create_simple_invoice(
+        &self,
+        req: &CreateSimpleInvoiceRequest,
+    ) -> Result<ApiResponse<InvoiceResponse>, QPayError> {
+        let req = self.client.with_signed_callback_url(req)?;
+        self.client
+            .do_request_raw(reqwest::Method::POST, "/v2/invoice", Some(&*req))
+            .await
+    }
+
+    /// Create an invoice with ebarimt (tax) information.
+    /// POST /v2/invoice
+    pub async fn create_ebarimt_invoice(
+        &self,
+        req: &CreateEbarimtInvoiceRequest,
+    ) -> Result<ApiResponse<InvoiceResponse>, QPayError> {
+        let req = self.client.with_signed_callback_url(req)?;
+        self.client
+            .do_request_raw(reqwest::Method::POST, "/v2/invoice", Some(&*req))
+            .await
+    }
+
+    /// Retrieve an invoice's current state, lines and payments by ID.
+    /// GET /v2/invoice/{id}
+    pub async fn get_invoice(
+        &self,
+        invoice_id: &str,
+    ) -> Result<ApiResponse<InvoiceDetail>, QPayError> {
+        let path = format!("/v2/invoice/{}", invoice_id);
+        self.client
+            .do_request_raw::<(), InvoiceDetail>(reqwest::Method::GET, &path, None)
+            .await
+    }
+
+    /// Cancel an existing invoice by ID.
+    /// DELETE /v2/invoice/{id}
+    pub async fn cancel_invoice(&self, invoice_id: &str) -> Result<ApiResponse<()>, QPayError> {
+        let path = format!("/v2/invoice/{}", invoice_id);
+        self.client
+            .send_request::<()>(reqwest::Method::DELETE, &path, None)
+            .await
+    }
+}
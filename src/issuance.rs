@@ -0,0 +1,502 @@
+//! Automatic ebarimt issuance for confirmed payments.
+//!
+//! Every paid invoice needs an ebarimt (electronic tax receipt). An
+//! [`EbarimtIssuer`] calls `create_ebarimt` once per payment, using the
+//! receiver registered for the invoice, and records the receipt. It is fed
+//! from callbacks through [`EbarimtCallbackHandler`] or from polling with
+//! [`EbarimtIssuer::wait_and_issue`].
+
+use std::collections::{BTreeMap, HashMap};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use serde::{Deserialize, Serialize};
+
+use crate::callback::{
+    CallbackEvent, CallbackHandler, ExpectedPayment, HandlerError, VerifiedCallback,
+};
+use crate::client::QPayClient;
+use crate::ebarimt_queue::EbarimtQueue;
+use crate::error::QPayError;
+use crate::models::{CreateEbarimtRequest, EbarimtResponse, PaymentCheckResponse};
+use crate::polling::{WaitOptions, PAYMENT_STATUS_PAID};
+use crate::validation::RegisterNumber;
+
+/// Attempts per receipt, including the first.
+pub const EBARIMT_MAX_ATTEMPTS: u32 = 3;
+
+/// Delay before the first retry; doubled for each further retry.
+pub const EBARIMT_RETRY_DELAY: Duration = Duration::from_millis(500);
+
+/// Who an ebarimt is issued to.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct EbarimtReceiver {
+    /// `CITIZEN` or `COMPANY`.
+    pub receiver_type: String,
+    /// Citizen register number, company register number or TIN.
+    pub receiver: Option<String>,
+    pub district_code: Option<String>,
+    pub classification_code: Option<String>,
+}
+
+impl EbarimtReceiver {
+    /// An individual who did not give a register number.
+    pub fn citizen() -> Self {
+        Self {
+            receiver_type: "CITIZEN".to_string(),
+            receiver: None,
+            district_code: None,
+            classification_code: None,
+        }
+    }
+
+    /// A citizen or company identified by a register number or TIN; the
+    /// receiver type follows the kind of number.
+    pub fn register(register: RegisterNumber) -> Self {
+        let receiver_type = if register.is_citizen() {
+            "CITIZEN"
+        } else {
+            "COMPANY"
+        };
+        Self {
+            receiver_type: receiver_type.to_string(),
+            receiver: Some(register.into()),
+            district_code: None,
+            classification_code: None,
+        }
+    }
+
+    /// Set the district code sent with the receipt.
+    pub fn with_district_code(mut self, code: impl Into<String>) -> Self {
+        self.district_code = Some(code.into());
+        self
+    }
+
+    /// Set the product classification code sent with the receipt.
+    pub fn with_classification_code(mut self, code: impl Into<String>) -> Self {
+        self.classification_code = Some(code.into());
+        self
+    }
+
+    /// The `create_ebarimt` request for `payment_id`.
+    pub fn request(&self, payment_id: &str) -> CreateEbarimtRequest {
+        CreateEbarimtRequest {
+            payment_id: payment_id.to_string(),
+            ebarimt_receiver_type: self.receiver_type.clone(),
+            ebarimt_receiver: self.receiver.clone(),
+            district_code: self.district_code.clone(),
+            classification_code: self.classification_code.clone(),
+        }
+    }
+}
+
+impl Default for EbarimtReceiver {
+    fn default() -> Self {
+        Self::citizen()
+    }
+}
+
+/// A receipt that could not be issued for a confirmed payment.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct FailedEbarimt {
+    pub invoice_id: String,
+    pub payment_id: String,
+    /// The last error, as text.
+    pub error: String,
+}
+
+/// Storage for per-invoice receivers, issued receipts and failed issues.
+pub trait EbarimtStore: Send + Sync {
+    /// The receiver registered for an invoice.
+    fn receiver(&self, invoice_id: &str) -> Result<Option<EbarimtReceiver>, QPayError>;
+
+    /// Register the receiver for an invoice.
+    fn set_receiver(&self, invoice_id: &str, receiver: &EbarimtReceiver) -> Result<(), QPayError>;
+
+    /// The receipt issued for a payment, if any.
+    fn receipt(&self, payment_id: &str) -> Result<Option<EbarimtResponse>, QPayError>;
+
+    /// Record the receipt issued for a payment.
+    fn record_receipt(&self, payment_id: &str, receipt: &EbarimtResponse) -> Result<(), QPayError>;
+
+    /// Record a failed issue, replacing an earlier one for the payment.
+    fn record_failure(&self, failure: &FailedEbarimt) -> Result<(), QPayError>;
+
+    /// Failed issues waiting for a retry, ordered by payment id.
+    fn failures(&self) -> Result<Vec<FailedEbarimt>, QPayError>;
+
+    /// Forget the failed issue of a payment.
+    fn clear_failure(&self, payment_id: &str) -> Result<(), QPayError>;
+}
+
+/// In-memory store; receivers and receipts are lost on restart.
+#[derive(Debug, Default)]
+pub struct MemoryEbarimtStore {
+    receivers: Mutex<HashMap<String, EbarimtReceiver>>,
+    receipts: Mutex<HashMap<String, EbarimtResponse>>,
+    failures: Mutex<BTreeMap<String, FailedEbarimt>>,
+}
+
+impl MemoryEbarimtStore {
+    /// Create an empty store.
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+impl EbarimtStore for MemoryEbarimtStore {
+    fn receiver(&self, invoice_id: &str) -> Result<Option<EbarimtReceiver>, QPayError> {
+        let receivers = self.receivers.lock().unwrap_or_else(|e| e.into_inner());
+        Ok(receivers.get(invoice_id).cloned())
+    }
+
+    fn set_receiver(&self, invoice_id: &str, receiver: &EbarimtReceiver) -> Result<(), QPayError> {
+        let mut receivers = self.receivers.lock().unwrap_or_else(|e| e.into_inner());
+        receivers.insert(invoice_id.to_string(), receiver.clone());
+        Ok(())
+    }
+
+    fn receipt(&self, payment_id: &str) -> Result<Option<EbarimtResponse>, QPayError> {
+        let receipts = self.receipts.lock().unwrap_or_else(|e| e.into_inner());
+        Ok(receipts.get(payment_id).cloned())
+    }
+
+    fn record_receipt(&self, payment_id: &str, receipt: &EbarimtResponse) -> Result<(), QPayError> {
+        let mut receipts = self.receipts.lock().unwrap_or_else(|e| e.into_inner());
+        receipts.insert(payment_id.to_string(), receipt.clone());
+        Ok(())
+    }
+
+    fn record_failure(&self, failure: &FailedEbarimt) -> Result<(), QPayError> {
+        let mut failures = self.failures.lock().unwrap_or_else(|e| e.into_inner());
+        failures.insert(failure.payment_id.clone(), failure.clone());
+        Ok(())
+    }
+
+    fn failures(&self) -> Result<Vec<FailedEbarimt>, QPayError> {
+        let failures = self.failures.lock().unwrap_or_else(|e| e.into_inner());
+        Ok(failures.values().cloned().collect())
+    }
+
+    fn clear_failure(&self, payment_id: &str) -> Result<(), QPayError> {
+        let mut failures = self.failures.lock().unwrap_or_else(|e| e.into_inner());
+        failures.remove(payment_id);
+        Ok(())
+    }
+}
+
+/// Issues one ebarimt per confirmed payment.
+pub struct EbarimtIssuer {
+    client: Arc<QPayClient>,
+    store: Arc<dyn EbarimtStore>,
+    default_receiver: EbarimtReceiver,
+    max_attempts: u32,
+    retry_delay: Duration,
+    in_flight: Mutex<HashMap<String, Arc<tokio::sync::Mutex<()>>>>,
+}
+
+impl EbarimtIssuer {
+    /// Create an issuer backed by a [`MemoryEbarimtStore`].
+    pub fn new(client: Arc<QPayClient>) -> Self {
+        Self::with_store(client, MemoryEbarimtStore::new())
+    }
+
+    /// Create an issuer backed by `store`.
+    pub fn with_store(client: Arc<QPayClient>, store: impl EbarimtStore + 'static) -> Self {
+        Self {
+            client,
+            store: Arc::new(store),
+            default_receiver: EbarimtReceiver::citizen(),
+            max_attempts: EBARIMT_MAX_ATTEMPTS,
+            retry_delay: EBARIMT_RETRY_DELAY,
+            in_flight: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Receiver for invoices without a registered one. Defaults to
+    /// [`EbarimtReceiver::citizen`].
+    pub fn with_default_receiver(mut self, receiver: EbarimtReceiver) -> Self {
+        self.default_receiver = receiver;
+        self
+    }
+
+    /// Retry transient failures up to `max_attempts` attempts in total,
+    /// starting with `delay` and doubling it after each retry.
+    pub fn with_retry(mut self, max_attempts: u32, delay: Duration) -> Self {
+        self.max_attempts = max_attempts.max(1);
+        self.retry_delay = delay;
+        self
+    }
+
+    /// The underlying store.
+    pub fn store(&self) -> &dyn EbarimtStore {
+        &*self.store
+    }
+
+    /// Register who the receipts for `invoice_id` are issued to, typically
+    /// when the invoice is created.
+    pub async fn set_receiver(
+        &self,
+        invoice_id: &str,
+        receiver: &EbarimtReceiver,
+    ) -> Result<(), QPayError> {
+        let invoice_id = invoice_id.to_string();
+        let receiver = receiver.clone();
+        self.call_store(move |store| store.set_receiver(&invoice_id, &receiver))
+            .await
+    }
+
+    /// Issue the receipt for a payment of `invoice_id`.
+    ///
+    /// A receipt already recorded for the payment is returned without
+    /// calling the API, so callbacks and polling can both report the same
+    /// payment. Transient failures are retried; the last error is returned.
+    pub async fn issue(
+        &self,
+        invoice_id: &str,
+        payment_id: &str,
+    ) -> Result<EbarimtResponse, QPayError> {
+        let req = self.request_for(invoice_id, payment_id).await?;
+        self.issue_request(&req, self.max_attempts).await
+    }
+
+    /// The `create_ebarimt` request for a payment of `invoice_id`, using
+    /// its registered receiver.
+    pub(crate) async fn request_for(
+        &self,
+        invoice_id: &str,
+        payment_id: &str,
+    ) -> Result<CreateEbarimtRequest, QPayError> {
+        let invoice_id = invoice_id.to_string();
+        let receiver = self
+            .call_store(move |store| store.receiver(&invoice_id))
+            .await?
+            .unwrap_or_else(|| self.default_receiver.clone());
+        Ok(receiver.request(payment_id))
+    }
+
+    /// Issue `req` with up to `max_attempts` attempts, one call at a time
+    /// per payment.
+    pub(crate) async fn issue_request(
+        &self,
+        req: &CreateEbarimtRequest,
+        max_attempts: u32,
+    ) -> Result<EbarimtResponse, QPayError> {
+        let payment_id = req.payment_id.as_str();
+        let lock = {
+            let mut in_flight = self.in_flight.lock().unwrap_or_else(|e| e.into_inner());
+            in_flight.entry(payment_id.to_string()).or_default().clone()
+        };
+        let result = {
+            let _guard = lock.lock().await;
+            self.issue_once(req, max_attempts).await
+        };
+
+        let mut in_flight = self.in_flight.lock().unwrap_or_else(|e| e.into_inner());
+        if Arc::strong_count(&lock) == 2 {
+            in_flight.remove(payment_id);
+        }
+        result
+    }
+
+    async fn issue_once(
+        &self,
+        req: &CreateEbarimtRequest,
+        max_attempts: u32,
+    ) -> Result<EbarimtResponse, QPayError> {
+        let payment_id = req.payment_id.as_str();
+        let id = payment_id.to_string();
+        if let Some(receipt) = self.call_store(move |store| store.receipt(&id)).await? {
+            return Ok(receipt);
+        }
+        req.validate()?;
+
+        let mut attempts = 0;
+        let mut delay = self.retry_delay;
+        let receipt = loop {
+            attempts += 1;
+            match self.client.create_ebarimt(req).await {
+                Err(e) if e.is_transient() && attempts < max_attempts => {
+                    log::warn!(
+                        "qpay: retrying ebarimt for payment {} after attempt {}: {}",
+                        payment_id,
+                        attempts,
+                        e
+                    );
+                    tokio::time::sleep(delay).await;
+                    delay *= 2;
+                }
+                result => break result?,
+            }
+        };
+        let id = payment_id.to_string();
+        let recorded = receipt.clone();
+        self.call_store(move |store| {
+            store.record_receipt(&id, &recorded)?;
+            store.clear_failure(&id)
+        })
+        .await?;
+        Ok(receipt)
+    }
+
+    /// Failed issues recorded by [`EbarimtCallbackHandler`].
+    pub async fn failures(&self) -> Result<Vec<FailedEbarimt>, QPayError> {
+        self.call_store(|store| store.failures()).await
+    }
+
+    /// Record a failed issue in the store.
+    async fn record_failure(&self, failure: FailedEbarimt) -> Result<(), QPayError> {
+        self.call_store(move |store| store.record_failure(&failure))
+            .await
+    }
+
+    /// Call the store on the blocking thread pool, as stores may do file or
+    /// network I/O.
+    async fn call_store<T: Send + 'static>(
+        &self,
+        op: impl FnOnce(&dyn EbarimtStore) -> Result<T, QPayError> + Send + 'static,
+    ) -> Result<T, QPayError> {
+        let store = Arc::clone(&self.store);
+        tokio::task::spawn_blocking(move || op(&*store))
+            .await
+            .map_err(|e| QPayError::Storage(e.to_string()))?
+    }
+
+    /// Issue every recorded failure again, e.g. after fixing the receiver
+    /// or the merchant's ebarimt registration. Failures that fail again
+    /// stay recorded with the new error; the issued receipts are returned.
+    pub async fn retry_failures(&self) -> Result<Vec<EbarimtResponse>, QPayError> {
+        let mut receipts = Vec::new();
+        for mut failure in self.failures().await? {
+            match self.issue(&failure.invoice_id, &failure.payment_id).await {
+                Ok(receipt) => receipts.push(receipt),
+                Err(e) => {
+                    failure.error = e.to_string();
+                    self.record_failure(failure).await?;
+                }
+            }
+        }
+        Ok(receipts)
+    }
+
+    /// Issue receipts for every paid row of a `check_payment` response.
+    /// Stops at the first failure.
+    pub async fn issue_paid(
+        &self,
+        invoice_id: &str,
+        resp: &PaymentCheckResponse,
+    ) -> Result<Vec<EbarimtResponse>, QPayError> {
+        let mut receipts = Vec::new();
+        for row in resp
+            .rows
+            .iter()
+            .filter(|row| row.payment_status == PAYMENT_STATUS_PAID)
+        {
+            receipts.push(self.issue(invoice_id, &row.payment_id).await?);
+        }
+        Ok(receipts)
+    }
+
+    /// Wait for the invoice to be paid (see [`QPayClient::wait_for_payment`])
+    /// and issue receipts for its payments.
+    pub async fn wait_and_issue(
+        &self,
+        invoice_id: &str,
+        options: &WaitOptions,
+    ) -> Result<Vec<EbarimtResponse>, QPayError> {
+        let resp = self.client.wait_for_payment(invoice_id, options).await?;
+        self.issue_paid(invoice_id, &resp).await
+    }
+}
+
+/// A [`CallbackHandler`] that issues the ebarimt for each verified payment
+/// before passing it on to `inner`.
+///
+/// If issuance fails with a transient error the callback is answered with
+/// an error so QPay delivers it again. Other failures are recorded in the
+/// issuer's store (see [`EbarimtIssuer::failures`]) and the payment is
+/// still passed on, since it is confirmed.
+///
+/// With a queue ([`with_queue`](Self::with_queue)) every failed receipt is
+/// queued for retry instead, and the payment is passed on.
+pub struct EbarimtCallbackHandler<H> {
+    inner: H,
+    issuer: Arc<EbarimtIssuer>,
+    queue: Option<Arc<EbarimtQueue>>,
+}
+
+impl<H: CallbackHandler> EbarimtCallbackHandler<H> {
+    /// Wrap `inner`, issuing receipts with `issuer`.
+    pub fn new(inner: H, issuer: Arc<EbarimtIssuer>) -> Self {
+        Self {
+            inner,
+            issuer,
+            queue: None,
+        }
+    }
+
+    /// Queue receipts that could not be issued in `queue`, which should
+    /// issue through the same issuer ([`EbarimtQueue::with_issuer`]).
+    pub fn with_queue(mut self, queue: Arc<EbarimtQueue>) -> Self {
+        self.queue = Some(queue);
+        self
+    }
+
+    /// The wrapped handler.
+    pub fn inner(&self) -> &H {
+        &self.inner
+    }
+
+    /// The issuer used for receipts.
+    pub fn issuer(&self) -> &EbarimtIssuer {
+        &self.issuer
+    }
+}
+
+impl<H: CallbackHandler> CallbackHandler for EbarimtCallbackHandler<H> {
+    async fn expected_payment(
+        &self,
+        event: &CallbackEvent,
+    ) -> Result<ExpectedPayment, HandlerError> {
+        self.inner.expected_payment(event).await
+    }
+
+    async fn on_payment(&self, payment: VerifiedCallback) -> Result<(), HandlerError> {
+        let result = self
+            .issuer
+            .issue(&payment.object_id, &payment.payment_id)
+            .await;
+        match (result, &self.queue) {
+            (Ok(_), _) => {}
+            (Err(e), Some(queue)) => {
+                log::warn!(
+                    "qpay: ebarimt for payment {} queued for retry: {}",
+                    payment.payment_id,
+                    e
+                );
+                let req = self
+                    .issuer
+                    .request_for(&payment.object_id, &payment.payment_id)
+                    .await?;
+                queue.enqueue(req).await?;
+            }
+            (Err(e), None) if e.is_transient() => return Err(Box::new(e)),
+            (Err(e), None) => {
+                log::error!(
+                    "qpay: ebarimt for payment {} was not issued: {}",
+                    payment.payment_id,
+                    e
+                );
+                let failure = FailedEbarimt {
+                    invoice_id: payment.object_id.clone(),
+                    payment_id: payment.payment_id.clone(),
+                    error: e.to_string(),
+                };
+                // Without a record the receipt would be lost; have QPay
+                // deliver the callback again instead
+                self.issuer.record_failure(failure).await?;
+            }
+        }
+        self.inner.on_payment(payment).await
+    }
+}
@@ -31,15 +31,41 @@
 //! }
 //! ```
 
+#[cfg(feature = "actix")]
+pub mod actix;
 pub mod auth;
+#[cfg(feature = "axum")]
+pub mod axum;
+pub mod bulk;
+pub mod callback;
 pub mod client;
 pub mod config;
+#[cfg(feature = "chrono")]
+pub mod datetime;
+pub mod dedup;
 pub mod ebarimt;
+pub mod ebarimt_queue;
 pub mod error;
+#[cfg(feature = "chrono")]
+pub mod export;
+mod file_log;
 pub mod invoice;
+pub mod issuance;
+pub mod lifecycle;
+pub mod merchant;
 pub mod models;
+pub mod pagination;
 pub mod payment;
+pub mod polling;
+pub mod reference;
+pub mod registry;
+pub mod response;
+pub mod subscription;
+pub mod tax;
+pub mod validation;
 
 pub use client::QPayClient;
-pub use config::QPayConfig;
+pub use config::{QPayConfig, Strictness};
 pub use error::{is_qpay_error, QPayError};
+pub use reqwest::Method;
+pub use response::{ApiResponse, RawClient};
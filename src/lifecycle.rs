@@ -0,0 +1,486 @@
+//! Invoice lifecycle tracking.
+//!
+//! An invoice is created, collects one or more payments and ends up paid,
+//! cancelled or expired; paid invoices may later be refunded. An
+//! [`InvoiceLifecycle`] drives those steps through the API, checks each
+//! step against the legal transitions and stores the result in an
+//! [`InvoiceRepository`].
+
+use std::collections::HashMap;
+use std::fmt;
+use std::sync::{Arc, Mutex};
+
+use futures::TryStreamExt;
+use serde::{Deserialize, Serialize};
+
+use crate::callback::AMOUNT_TOLERANCE;
+use crate::client::QPayClient;
+use crate::error::{QPayError, ERR_PAYMENT_ALREADY_CANCELED};
+use crate::file_log::unix_now;
+use crate::models::{
+    CreateInvoiceRequest, PaymentCancelRequest, PaymentCheckRow, PaymentRefundRequest,
+};
+use crate::polling::{invoice_check_request, PAYMENT_STATUS_PAID};
+
+/// Payment status QPay reports for a refunded payment.
+pub const PAYMENT_STATUS_REFUNDED: &str = "REFUNDED";
+
+/// State of an invoice.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum InvoiceState {
+    Created,
+    PartiallyPaid,
+    Paid,
+    Cancelled,
+    Expired,
+    /// Every payment was refunded or cancelled.
+    Refunded,
+}
+
+impl InvoiceState {
+    /// Whether an invoice may move from this state to `next`.
+    ///
+    /// ```text
+    /// Created -> PartiallyPaid -> Paid -> Refunded
+    ///    |             |                     ^
+    ///    |             +---------------------+
+    ///    +-> Cancelled, Expired, Paid
+    /// ```
+    pub fn can_transition_to(self, next: InvoiceState) -> bool {
+        use InvoiceState::*;
+        matches!(
+            (self, next),
+            (Created, PartiallyPaid | Paid | Cancelled | Expired)
+                | (PartiallyPaid, Paid | Refunded)
+                | (Paid, Refunded)
+        )
+    }
+
+    /// Whether no further transitions are possible.
+    pub fn is_terminal(self) -> bool {
+        matches!(self, Self::Cancelled | Self::Expired | Self::Refunded)
+    }
+
+    /// Whether the invoice holds money that can be refunded or cancelled.
+    pub fn has_payments(self) -> bool {
+        matches!(self, Self::PartiallyPaid | Self::Paid)
+    }
+}
+
+impl fmt::Display for InvoiceState {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Self::Created => "CREATED",
+            Self::PartiallyPaid => "PARTIALLY_PAID",
+            Self::Paid => "PAID",
+            Self::Cancelled => "CANCELLED",
+            Self::Expired => "EXPIRED",
+            Self::Refunded => "REFUNDED",
+        })
+    }
+}
+
+/// State of a single payment against an invoice.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum PaymentState {
+    Paid,
+    Refunded,
+    Cancelled,
+}
+
+impl PaymentState {
+    /// Whether a payment may move from this state to `next`. Only a paid
+    /// payment can be refunded or cancelled.
+    pub fn can_transition_to(self, next: PaymentState) -> bool {
+        self == Self::Paid && next != Self::Paid
+    }
+}
+
+impl fmt::Display for PaymentState {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Self::Paid => "PAID",
+            Self::Refunded => "REFUNDED",
+            Self::Cancelled => "CANCELLED",
+        })
+    }
+}
+
+/// A payment recorded against an invoice.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PaymentRecord {
+    pub payment_id: String,
+    pub amount: f64,
+    pub state: PaymentState,
+}
+
+/// The stored state of an invoice.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct InvoiceRecord {
+    pub invoice_id: String,
+    pub sender_invoice_no: String,
+    pub amount: f64,
+    pub state: InvoiceState,
+    #[serde(default)]
+    pub payments: Vec<PaymentRecord>,
+    /// Unix seconds of the last change.
+    pub updated_at: u64,
+}
+
+impl InvoiceRecord {
+    /// Sum of payments that are still paid.
+    pub fn paid_amount(&self) -> f64 {
+        self.payments
+            .iter()
+            .filter(|p| p.state == PaymentState::Paid)
+            .map(|p| p.amount)
+            .sum()
+    }
+
+    /// A recorded payment by id.
+    pub fn payment(&self, payment_id: &str) -> Option<&PaymentRecord> {
+        self.payments.iter().find(|p| p.payment_id == payment_id)
+    }
+
+    /// Move to `next`, failing if the transition is not allowed.
+    pub fn transition(&mut self, next: InvoiceState) -> Result<(), LifecycleError> {
+        if !self.state.can_transition_to(next) {
+            return Err(LifecycleError::InvalidTransition {
+                invoice_id: self.invoice_id.clone(),
+                from: self.state,
+                to: next,
+            });
+        }
+        self.state = next;
+        self.updated_at = unix_now();
+        Ok(())
+    }
+
+    /// Move a payment to `next`. Once every payment is refunded or
+    /// cancelled the invoice becomes [`InvoiceState::Refunded`].
+    pub fn transition_payment(
+        &mut self,
+        payment_id: &str,
+        next: PaymentState,
+    ) -> Result<(), LifecycleError> {
+        if !self.state.has_payments() {
+            return Err(LifecycleError::InvalidTransition {
+                invoice_id: self.invoice_id.clone(),
+                from: self.state,
+                to: InvoiceState::Refunded,
+            });
+        }
+        let payment = self
+            .payments
+            .iter_mut()
+            .find(|p| p.payment_id == payment_id)
+            .ok_or_else(|| LifecycleError::UnknownPayment {
+                invoice_id: self.invoice_id.clone(),
+                payment_id: payment_id.to_string(),
+            })?;
+        if !payment.state.can_transition_to(next) {
+            return Err(LifecycleError::InvalidPaymentTransition {
+                payment_id: payment_id.to_string(),
+                from: payment.state,
+                to: next,
+            });
+        }
+        payment.state = next;
+        self.updated_at = unix_now();
+
+        if self.payments.iter().all(|p| p.state != PaymentState::Paid) {
+            self.transition(InvoiceState::Refunded)?;
+        }
+        Ok(())
+    }
+
+    /// Record the rows returned by `check_payment`: add new paid payments,
+    /// mark refunded ones and move the invoice to partially paid or paid.
+    /// Invoices in a terminal state are left unchanged. Returns whether
+    /// anything changed.
+    pub fn apply_rows(&mut self, rows: &[PaymentCheckRow]) -> Result<bool, LifecycleError> {
+        if self.state.is_terminal() {
+            return Ok(false);
+        }
+        let mut changed = false;
+        for row in rows {
+            let known = self.payment(&row.payment_id).map(|p| p.state);
+            match (row.payment_status.as_str(), known) {
+                (PAYMENT_STATUS_PAID, None) => {
+                    self.payments.push(PaymentRecord {
+                        payment_id: row.payment_id.clone(),
+                        amount: row.payment_amount.parse().unwrap_or(0.0),
+                        state: PaymentState::Paid,
+                    });
+                    changed = true;
+                }
+                (PAYMENT_STATUS_REFUNDED, Some(PaymentState::Paid)) => {
+                    self.transition_payment(&row.payment_id, PaymentState::Refunded)?;
+                    changed = true;
+                }
+                _ => {}
+            }
+        }
+
+        // Payments only move the invoice forward; partial refunds leave it
+        // paid.
+        let paid = self.paid_amount();
+        let next = match self.state {
+            InvoiceState::Created | InvoiceState::PartiallyPaid
+                if paid > 0.0 && paid + AMOUNT_TOLERANCE >= self.amount =>
+            {
+                Some(InvoiceState::Paid)
+            }
+            InvoiceState::Created if paid > 0.0 => Some(InvoiceState::PartiallyPaid),
+            _ => None,
+        };
+        if let Some(next) = next {
+            self.transition(next)?;
+            changed = true;
+        }
+        if changed {
+            self.updated_at = unix_now();
+        }
+        Ok(changed)
+    }
+}
+
+/// A lifecycle step that is not allowed.
+#[derive(Debug, Clone, PartialEq, thiserror::Error)]
+pub enum LifecycleError {
+    #[error("unknown invoice {0}")]
+    UnknownInvoice(String),
+
+    #[error("invoice {invoice_id} has no payment {payment_id}")]
+    UnknownPayment {
+        invoice_id: String,
+        payment_id: String,
+    },
+
+    #[error("invoice {invoice_id} cannot move from {from} to {to}")]
+    InvalidTransition {
+        invoice_id: String,
+        from: InvoiceState,
+        to: InvoiceState,
+    },
+
+    #[error("payment {payment_id} cannot move from {from} to {to}")]
+    InvalidPaymentTransition {
+        payment_id: String,
+        from: PaymentState,
+        to: PaymentState,
+    },
+}
+
+/// Persistence for [`InvoiceRecord`]s.
+pub trait InvoiceRepository: Send + Sync {
+    /// Load an invoice by id.
+    fn load(&self, invoice_id: &str) -> Result<Option<InvoiceRecord>, QPayError>;
+
+    /// Insert or replace an invoice.
+    fn save(&self, record: &InvoiceRecord) -> Result<(), QPayError>;
+
+    /// All invoices in `state`.
+    fn list(&self, state: InvoiceState) -> Result<Vec<InvoiceRecord>, QPayError>;
+}
+
+/// In-memory repository; records are lost on restart.
+#[derive(Debug, Default)]
+pub struct MemoryInvoiceRepository {
+    records: Mutex<HashMap<String, InvoiceRecord>>,
+}
+
+impl MemoryInvoiceRepository {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+impl InvoiceRepository for MemoryInvoiceRepository {
+    fn load(&self, invoice_id: &str) -> Result<Option<InvoiceRecord>, QPayError> {
+        let records = self.records.lock().unwrap_or_else(|e| e.into_inner());
+        Ok(records.get(invoice_id).cloned())
+    }
+
+    fn save(&self, record: &InvoiceRecord) -> Result<(), QPayError> {
+        let mut records = self.records.lock().unwrap_or_else(|e| e.into_inner());
+        records.insert(record.invoice_id.clone(), record.clone());
+        Ok(())
+    }
+
+    fn list(&self, state: InvoiceState) -> Result<Vec<InvoiceRecord>, QPayError> {
+        let records = self.records.lock().unwrap_or_else(|e| e.into_inner());
+        let mut matching: Vec<_> = records
+            .values()
+            .filter(|r| r.state == state)
+            .cloned()
+            .collect();
+        matching.sort_by(|a, b| a.invoice_id.cmp(&b.invoice_id));
+        Ok(matching)
+    }
+}
+
+/// Drives invoices through their lifecycle using the QPay API.
+///
+/// Each step loads the record, checks the transition before calling the
+/// API, and saves the new state once the API call succeeded. Steps for the
+/// same invoice should not run concurrently.
+pub struct InvoiceLifecycle {
+    client: Arc<QPayClient>,
+    repository: Box<dyn InvoiceRepository>,
+}
+
+impl InvoiceLifecycle {
+    /// Create a lifecycle backed by a [`MemoryInvoiceRepository`].
+    pub fn new(client: Arc<QPayClient>) -> Self {
+        Self::with_repository(client, MemoryInvoiceRepository::new())
+    }
+
+    /// Create a lifecycle backed by `repository`.
+    pub fn with_repository(
+        client: Arc<QPayClient>,
+        repository: impl InvoiceRepository + 'static,
+    ) -> Self {
+        Self {
+            client,
+            repository: Box::new(repository),
+        }
+    }
+
+    /// The underlying repository.
+    pub fn repository(&self) -> &dyn InvoiceRepository {
+        &*self.repository
+    }
+
+    /// Load an invoice, failing if it is unknown.
+    pub fn get(&self, invoice_id: &str) -> Result<InvoiceRecord, QPayError> {
+        self.repository
+            .load(invoice_id)?
+            .ok_or_else(|| LifecycleError::UnknownInvoice(invoice_id.to_string()).into())
+    }
+
+    /// Create an invoice with `create_invoice` and store it as
+    /// [`InvoiceState::Created`].
+    pub async fn create(&self, req: &CreateInvoiceRequest) -> Result<InvoiceRecord, QPayError> {
+        let invoice = self.client.create_invoice(req).await?;
+        let record = InvoiceRecord {
+            invoice_id: invoice.invoice_id,
+            sender_invoice_no: req.sender_invoice_no.clone(),
+            amount: req.amount,
+            state: InvoiceState::Created,
+            payments: Vec::new(),
+            updated_at: unix_now(),
+        };
+        self.repository.save(&record)?;
+        Ok(record)
+    }
+
+    /// Record the invoice's payments from `check_payment`. An unpaid
+    /// invoice whose `invoice_status` from `get_invoice` is cancelled moves
+    /// to [`InvoiceState::Cancelled`].
+    pub async fn sync(&self, invoice_id: &str) -> Result<InvoiceRecord, QPayError> {
+        let mut record = self.get(invoice_id)?;
+        if record.state.is_terminal() {
+            return Ok(record);
+        }
+
+        let rows: Vec<PaymentCheckRow> = self
+            .client
+            .check_payment_stream(&invoice_check_request(invoice_id), 0)
+            .try_collect()
+            .await?;
+        let mut changed = record.apply_rows(&rows)?;
+        if record.state == InvoiceState::Created
+            && self.client.get_invoice(invoice_id).await?.is_cancelled()
+        {
+            record.transition(InvoiceState::Cancelled)?;
+            changed = true;
+        }
+        if changed {
+            self.repository.save(&record)?;
+        }
+        Ok(record)
+    }
+
+    /// Cancel an unpaid invoice with `cancel_invoice`. If the call fails
+    /// but `get_invoice` shows the invoice as cancelled, it is treated as
+    /// cancelled.
+    pub async fn cancel(&self, invoice_id: &str) -> Result<InvoiceRecord, QPayError> {
+        let mut record = self.get(invoice_id)?;
+        check_transition(&record, InvoiceState::Cancelled)?;
+
+        match self.client.cancel_invoice(invoice_id).await {
+            Ok(()) => {}
+            Err(e @ QPayError::Api { .. }) => {
+                if !self.client.get_invoice(invoice_id).await?.is_cancelled() {
+                    return Err(e);
+                }
+            }
+            Err(e) => return Err(e),
+        }
+        record.transition(InvoiceState::Cancelled)?;
+        self.repository.save(&record)?;
+        Ok(record)
+    }
+
+    /// Mark an unpaid invoice as expired, e.g. once its `expiry_date` has
+    /// passed. No API call is made.
+    pub fn expire(&self, invoice_id: &str) -> Result<InvoiceRecord, QPayError> {
+        let mut record = self.get(invoice_id)?;
+        record.transition(InvoiceState::Expired)?;
+        self.repository.save(&record)?;
+        Ok(record)
+    }
+
+    /// Refund one payment of the invoice with `refund_payment`.
+    pub async fn refund_payment(
+        &self,
+        invoice_id: &str,
+        payment_id: &str,
+        req: &PaymentRefundRequest,
+    ) -> Result<InvoiceRecord, QPayError> {
+        let mut record = self.get(invoice_id)?;
+        check_payment_transition(&record, payment_id, PaymentState::Refunded)?;
+
+        self.client.refund_payment(payment_id, req).await?;
+        record.transition_payment(payment_id, PaymentState::Refunded)?;
+        self.repository.save(&record)?;
+        Ok(record)
+    }
+
+    /// Cancel one payment of the invoice with `cancel_payment`. A payment
+    /// QPay already cancelled is treated as cancelled.
+    pub async fn cancel_payment(
+        &self,
+        invoice_id: &str,
+        payment_id: &str,
+        req: &PaymentCancelRequest,
+    ) -> Result<InvoiceRecord, QPayError> {
+        let mut record = self.get(invoice_id)?;
+        check_payment_transition(&record, payment_id, PaymentState::Cancelled)?;
+
+        match self.client.cancel_payment(payment_id, req).await {
+            Ok(()) => {}
+            Err(e) if e.is_code(ERR_PAYMENT_ALREADY_CANCELED) => {}
+            Err(e) => return Err(e),
+        }
+        record.transition_payment(payment_id, PaymentState::Cancelled)?;
+        self.repository.save(&record)?;
+        Ok(record)
+    }
+}
+
+/// Fail before calling the API if `next` is not reachable.
+fn check_transition(record: &InvoiceRecord, next: InvoiceState) -> Result<(), LifecycleError> {
+    record.clone().transition(next)
+}
+
+fn check_payment_transition(
+    record: &InvoiceRecord,
+    payment_id: &str,
+    next: PaymentState,
+) -> Result<(), LifecycleError> {
+    record.clone().transition_payment(payment_id, next)
+}
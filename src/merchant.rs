@@ -0,0 +1,186 @@
+use crate::client::QPayClient;
+use crate::error::QPayError;
+use crate::models::{
+    CompanyMerchantRequest, MerchantBankAccount, MerchantBankAccountRequest, MerchantListRequest,
+    MerchantListResponse, MerchantResponse, PersonMerchantRequest,
+};
+use crate::response::{ApiResponse, RawClient};
+
+impl QPayClient {
+    /// Register a company sub-merchant.
+    /// POST /v2/merchant/company
+    pub async fn create_company_merchant(
+        &self,
+        req: &CompanyMerchantRequest,
+    ) -> Result<MerchantResponse, QPayError> {
+        self.do_request(reqwest::Method::POST, "/v2/merchant/company", Some(req))
+            .await
+    }
+
+    /// Register a person (individual) sub-merchant.
+    /// POST /v2/merchant/person
+    pub async fn create_person_merchant(
+        &self,
+        req: &PersonMerchantRequest,
+    ) -> Result<MerchantResponse, QPayError> {
+        self.do_request(reqwest::Method::POST, "/v2/merchant/person", Some(req))
+            .await
+    }
+
+    /// Retrieve a merchant by ID.
+    /// GET /v2/merchant/{id}
+    pub async fn get_merchant(&self, merchant_id: &str) -> Result<MerchantResponse, QPayError> {
+        let path = format!("/v2/merchant/{}", merchant_id);
+        self.do_request::<(), MerchantResponse>(reqwest::Method::GET, &path, None)
+            .await
+    }
+
+    /// Update a company merchant.
+    /// PUT /v2/merchant/company/{id}
+    pub async fn update_company_merchant(
+        &self,
+        merchant_id: &str,
+        req: &CompanyMerchantRequest,
+    ) -> Result<MerchantResponse, QPayError> {
+        let path = format!("/v2/merchant/company/{}", merchant_id);
+        self.do_request(reqwest::Method::PUT, &path, Some(req))
+            .await
+    }
+
+    /// Update a person merchant.
+    /// PUT /v2/merchant/person/{id}
+    pub async fn update_person_merchant(
+        &self,
+        merchant_id: &str,
+        req: &PersonMerchantRequest,
+    ) -> Result<MerchantResponse, QPayError> {
+        let path = format!("/v2/merchant/person/{}", merchant_id);
+        self.do_request(reqwest::Method::PUT, &path, Some(req))
+            .await
+    }
+
+    /// Return a page of registered merchants.
+    /// POST /v2/merchant/list
+    pub async fn list_merchants(
+        &self,
+        req: &MerchantListRequest,
+    ) -> Result<MerchantListResponse, QPayError> {
+        self.do_request(reqwest::Method::POST, "/v2/merchant/list", Some(req))
+            .await
+    }
+
+    /// Delete a merchant by ID.
+    /// DELETE /v2/merchant/{id}
+    pub async fn delete_merchant(&self, merchant_id: &str) -> Result<(), QPayError> {
+        let path = format!("/v2/merchant/{}", merchant_id);
+        self.do_request_no_response::<()>(reqwest::Method::DELETE, &path, None)
+            .await
+    }
+
+    /// Add a bank account to a merchant.
+    /// POST /v2/merchant/{id}/bank_account
+    pub async fn add_merchant_bank_account(
+        &self,
+        merchant_id: &str,
+        req: &MerchantBankAccountRequest,
+    ) -> Result<MerchantBankAccount, QPayError> {
+        let path = format!("/v2/merchant/{}/bank_account", merchant_id);
+        self.do_request(reqwest::Method::POST, &path, Some(req))
+            .await
+    }
+}
+
+impl RawClient<'_> {
+    /// Register a company sub-merchant.
+    /// POST /v2/merchant/company
+    pub async fn create_company_merchant(
+        &self,
+        req: &CompanyMerchantRequest,
+    ) -> Result<ApiResponse<MerchantResponse>, QPayError> {
+        self.client
+            .do_request_raw(reqwest::Method::POST, "/v2/merchant/company", Some(req))
+            .await
+    }
+
+    /// Register a person (individual) sub-merchant.
+    /// POST /v2/merchant/person
+    pub async fn create_person_merchant(
+        &self,
+        req: &PersonMerchantRequest,
+    ) -> Result<ApiResponse<MerchantResponse>, QPayError> {
+        self.client
+            .do_request_raw(reqwest::Method::POST, "/v2/merchant/person", Some(req))
+            .await
+    }
+
+    /// Retrieve a merchant by ID.
+    /// GET /v2/merchant/{id}
+    pub async fn get_merchant(
+        &self,
+        merchant_id: &str,
+    ) -> Result<ApiResponse<MerchantResponse>, QPayError> {
+        let path = format!("/v2/merchant/{}", merchant_id);
+        self.client
+            .do_request_raw::<(), MerchantResponse>(reqwest::Method::GET, &path, None)
+            .await
+    }
+
+    /// Update a company merchant.
+    /// PUT /v2/merchant/company/{id}
+    pub async fn update_company_merchant(
+        &self,
+        merchant_id: &str,
+        req: &CompanyMerchantRequest,
+    ) -> Result<ApiResponse<MerchantResponse>, QPayError> {
+        let path = format!("/v2/merchant/company/{}", merchant_id);
+        self.client
+            .do_request_raw(reqwest::Method::PUT, &path, Some(req))
+            .await
+    }
+
+    /// Update a person merchant.
+    /// PUT /v2/merchant/person/{id}
+    pub async fn update_person_merchant(
+        &self,
+        merchant_id: &str,
+        req: &PersonMerchantRequest,
+    ) -> Result<ApiResponse<MerchantResponse>, QPayError> {
+        let path = format!("/v2/merchant/person/{}", merchant_id);
+        self.client
+            .do_request_raw(reqwest::Method::PUT, &path, Some(req))
+            .await
+    }
+
+    /// Return a page of registered merchants.
+    /// POST /v2/merchant/list
+    pub async fn list_merchants(
+        &self,
+        req: &MerchantListRequest,
+    ) -> Result<ApiResponse<MerchantListResponse>, QPayError> {
+        self.client
+            .do_request_raw(reqwest::Method::POST, "/v2/merchant/list", Some(req))
+            .await
+    }
+
+    /// Delete a merchant by ID.
+    /// DELETE /v2/merchant/{id}
+    pub async fn delete_merchant(&self, merchant_id: &str) -> Result<ApiResponse<()>, QPayError> {
+        let path = format!("/v2/merchant/{}", merchant_id);
+        self.client
+            .send_request::<()>(reqwest::Method::DELETE, &path, None)
+            .await
+    }
+
+    /// Add a bank account to a merchant.
+    /// POST /v2/merchant/{id}/bank_account
+    pub async fn add_merchant_bank_account(
+        &self,
+        merchant_id: &str,
+        req: &MerchantBankAccountRequest,
+    ) -> Result<ApiResponse<MerchantBankAccount>, QPayError> {
+        let path = format!("/v2/merchant/{}/bank_account", merchant_id);
+        self.client
+            .do_request_raw(reqwest::Method::POST, &path, Some(req))
+            .await
+    }
+}
@@ -1,8 +1,10 @@
+use serde::de::DeserializeOwned;
 use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
 
 // --- Auth ---
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, Default)]
 pub struct TokenResponse {
     pub token_type: String,
     pub refresh_expires_in: i64,
@@ -13,6 +15,8 @@ pub struct TokenResponse {
     #[serde(rename = "not-before-policy")]
     pub not_before_policy: String,
     pub session_state: String,
+    #[serde(flatten)]
+    pub extra: Map<String, Value>,
 }
 
 // --- Common nested types ---
@@ -141,12 +145,14 @@ pub struct TaxEntry {
     pub note: Option<String>,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, Default)]
 pub struct Deeplink {
     pub name: String,
     pub description: String,
     pub logo: String,
     pub link: String,
+    #[serde(flatten)]
+    pub extra: Map<String, Value>,
 }
 
 // --- Invoice ---
@@ -229,7 +235,7 @@ pub struct CreateEbarimtInvoiceRequest {
     pub lines: Vec<EbarimtInvoiceLine>,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, Default)]
 pub struct InvoiceResponse {
     pub invoice_id: String,
     pub qr_text: String,
@@ -237,6 +243,115 @@ pub struct InvoiceResponse {
     #[serde(rename = "qPay_shortUrl")]
     pub qpay_short_url: String,
     pub urls: Vec<Deeplink>,
+    #[serde(flatten)]
+    pub extra: Map<String, Value>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, Default)]
+pub struct InvoiceDetail {
+    pub invoice_id: String,
+    pub invoice_status: String,
+    pub sender_invoice_no: String,
+    pub sender_branch_code: Option<String>,
+    pub sender_branch_data: Option<InvoiceDetailSenderBranch>,
+    pub sender_staff_code: Option<String>,
+    pub sender_staff_data: Option<InvoiceDetailSenderStaff>,
+    pub sender_terminal_code: Option<String>,
+    pub sender_terminal_data: Option<serde_json::Value>,
+    pub invoice_receiver_code: Option<String>,
+    pub invoice_receiver_data: Option<InvoiceDetailReceiver>,
+    pub invoice_description: String,
+    pub invoice_due_date: Option<String>,
+    pub enable_expiry: Option<bool>,
+    pub expiry_date: Option<String>,
+    #[serde(default)]
+    pub allow_partial: bool,
+    pub minimum_amount: Option<f64>,
+    #[serde(default)]
+    pub allow_exceed: bool,
+    pub maximum_amount: Option<f64>,
+    pub total_amount: f64,
+    pub gross_amount: Option<f64>,
+    pub tax_amount: Option<f64>,
+    pub surcharge_amount: Option<f64>,
+    pub discount_amount: Option<f64>,
+    pub callback_url: String,
+    pub note: Option<String>,
+    #[serde(default)]
+    pub lines: Vec<InvoiceDetailLine>,
+    #[serde(default)]
+    pub transactions: Vec<InvoiceDetailTransaction>,
+    #[serde(default)]
+    pub inputs: Vec<serde_json::Value>,
+    #[serde(default)]
+    pub payments: Vec<InvoicePayment>,
+    #[serde(flatten)]
+    pub extra: Map<String, Value>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, Default)]
+pub struct InvoiceDetailSenderBranch {
+    pub register: Option<String>,
+    pub name: Option<String>,
+    pub email: Option<String>,
+    pub phone: Option<String>,
+    pub address: Option<Address>,
+    #[serde(flatten)]
+    pub extra: Map<String, Value>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, Default)]
+pub struct InvoiceDetailSenderStaff {
+    pub name: Option<String>,
+    pub email: Option<String>,
+    pub phone: Option<String>,
+    #[serde(flatten)]
+    pub extra: Map<String, Value>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, Default)]
+pub struct InvoiceDetailReceiver {
+    pub register: Option<String>,
+    pub name: Option<String>,
+    pub email: Option<String>,
+    pub phone: Option<String>,
+    pub address: Option<Address>,
+    #[serde(flatten)]
+    pub extra: Map<String, Value>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, Default)]
+pub struct InvoiceDetailTransaction {
+    pub description: String,
+    pub amount: String,
+    pub accounts: Option<Vec<Account>>,
+    #[serde(flatten)]
+    pub extra: Map<String, Value>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, Default)]
+pub struct InvoiceDetailLine {
+    pub tax_product_code: Option<String>,
+    pub line_description: String,
+    pub line_quantity: String,
+    pub line_unit_price: String,
+    pub note: Option<String>,
+    #[serde(default)]
+    pub discounts: Vec<TaxEntry>,
+    #[serde(default)]
+    pub surcharges: Vec<TaxEntry>,
+    #[serde(default)]
+    pub taxes: Vec<TaxEntry>,
+    #[serde(flatten)]
+    pub extra: Map<String, Value>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, Default)]
+pub struct InvoicePayment {
+    pub payment_id: String,
+    pub transaction_type: String,
+    #[serde(flatten)]
+    pub extra: Map<String, Value>,
 }
 
 // --- Payment ---
@@ -255,15 +370,17 @@ pub struct PaymentCheckRequest {
     pub offset: Option<Offset>,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, Default)]
 pub struct PaymentCheckResponse {
     pub count: i32,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub paid_amount: Option<f64>,
     pub rows: Vec<PaymentCheckRow>,
+    #[serde(flatten)]
+    pub extra: Map<String, Value>,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, Default)]
 pub struct PaymentCheckRow {
     pub payment_id: String,
     pub payment_status: String,
@@ -272,15 +389,18 @@ pub struct PaymentCheckRow {
     pub payment_currency: String,
     pub payment_wallet: String,
     pub payment_type: String,
+    pub payment_date: Option<String>,
     pub next_payment_date: Option<String>,
     pub next_payment_datetime: Option<String>,
     #[serde(default)]
     pub card_transactions: Vec<CardTransaction>,
     #[serde(default)]
     pub p2p_transactions: Vec<P2PTransaction>,
+    #[serde(flatten)]
+    pub extra: Map<String, Value>,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, Default)]
 pub struct PaymentDetail {
     pub payment_id: String,
     pub payment_status: String,
@@ -298,9 +418,11 @@ pub struct PaymentDetail {
     pub card_transactions: Vec<CardTransaction>,
     #[serde(default)]
     pub p2p_transactions: Vec<P2PTransaction>,
+    #[serde(flatten)]
+    pub extra: Map<String, Value>,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, Default)]
 pub struct CardTransaction {
     #[serde(skip_serializing_if = "Option::is_none")]
     pub card_merchant_code: Option<String>,
@@ -328,9 +450,11 @@ pub struct CardTransaction {
     pub transaction_status: Option<String>,
     pub settlement_status: String,
     pub settlement_status_date: String,
+    #[serde(flatten)]
+    pub extra: Map<String, Value>,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, Default)]
 pub struct P2PTransaction {
     pub transaction_bank_code: String,
     pub account_bank_code: String,
@@ -340,6 +464,8 @@ pub struct P2PTransaction {
     pub amount: String,
     pub currency: String,
     pub settlement_status: String,
+    #[serde(flatten)]
+    pub extra: Map<String, Value>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -351,13 +477,15 @@ pub struct PaymentListRequest {
     pub offset: Offset,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, Default)]
 pub struct PaymentListResponse {
     pub count: i32,
     pub rows: Vec<PaymentListItem>,
+    #[serde(flatten)]
+    pub extra: Map<String, Value>,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, Default)]
 pub struct PaymentListItem {
     pub payment_id: String,
     pub payment_date: String,
@@ -372,6 +500,8 @@ pub struct PaymentListItem {
     pub paid_by: String,
     pub object_type: String,
     pub object_id: String,
+    #[serde(flatten)]
+    pub extra: Map<String, Value>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, Default)]
@@ -404,7 +534,7 @@ pub struct CreateEbarimtRequest {
     pub classification_code: Option<String>,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, Default)]
 pub struct EbarimtResponse {
     pub id: String,
     pub ebarimt_by: String,
@@ -449,9 +579,11 @@ pub struct EbarimtResponse {
     pub barimt_transactions: Vec<serde_json::Value>,
     #[serde(default)]
     pub barimt_histories: Vec<EbarimtHistory>,
+    #[serde(flatten)]
+    pub extra: Map<String, Value>,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, Default)]
 pub struct EbarimtItem {
     pub id: String,
     pub barimt_id: String,
@@ -470,9 +602,11 @@ pub struct EbarimtItem {
     pub updated_by: String,
     pub updated_date: String,
     pub status: bool,
+    #[serde(flatten)]
+    pub extra: Map<String, Value>,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, Default)]
 pub struct EbarimtHistory {
     pub id: String,
     pub barimt_id: String,
@@ -502,4 +636,336 @@ pub struct EbarimtHistory {
     pub updated_by: String,
     pub updated_date: String,
     pub status: bool,
+    #[serde(flatten)]
+    pub extra: Map<String, Value>,
+}
+
+// --- Merchant ---
+
+#[derive(Debug, Clone, Serialize, Deserialize, Default)]
+pub struct CompanyMerchantRequest {
+    pub owner_register_no: String,
+    pub owner_first_name: String,
+    pub owner_last_name: String,
+    pub register_number: String,
+    pub name: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub name_eng: Option<String>,
+    pub mcc_code: String,
+    pub city: String,
+    pub district: String,
+    pub address: String,
+    pub phone: String,
+    pub email: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub location_lat: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub location_lng: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, Default)]
+pub struct PersonMerchantRequest {
+    pub register_number: String,
+    pub first_name: String,
+    pub last_name: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub business_name: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub business_name_eng: Option<String>,
+    pub mcc_code: String,
+    pub city: String,
+    pub district: String,
+    pub address: String,
+    pub phone: String,
+    pub email: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub location_lat: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub location_lng: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, Default)]
+pub struct MerchantResponse {
+    pub id: String,
+    #[serde(rename = "type")]
+    pub merchant_type: String,
+    pub register_number: String,
+    pub name: Option<String>,
+    pub name_eng: Option<String>,
+    pub owner_register_no: Option<String>,
+    pub owner_first_name: Option<String>,
+    pub owner_last_name: Option<String>,
+    pub first_name: Option<String>,
+    pub last_name: Option<String>,
+    pub business_name: Option<String>,
+    pub business_name_eng: Option<String>,
+    pub mcc_code: String,
+    pub city: String,
+    pub district: String,
+    pub address: Option<String>,
+    pub phone: Option<String>,
+    pub email: Option<String>,
+    pub location_lat: Option<String>,
+    pub location_lng: Option<String>,
+    pub created_date: Option<String>,
+    #[serde(flatten)]
+    pub extra: Map<String, Value>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MerchantListRequest {
+    pub offset: Offset,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, Default)]
+pub struct MerchantListResponse {
+    pub count: i32,
+    pub rows: Vec<MerchantResponse>,
+    #[serde(flatten)]
+    pub extra: Map<String, Value>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, Default)]
+pub struct MerchantBankAccountRequest {
+    pub account_bank_code: String,
+    pub account_number: String,
+    pub account_name: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub iban_number: Option<String>,
+    pub is_default: bool,
 }
+
+#[derive(Debug, Clone, Serialize, Deserialize, Default)]
+pub struct MerchantBankAccount {
+    pub id: String,
+    pub account_bank_code: String,
+    pub account_number: String,
+    pub account_name: String,
+    pub iban_number: Option<String>,
+    pub is_default: bool,
+    #[serde(flatten)]
+    pub extra: Map<String, Value>,
+}
+
+// --- Reference data ---
+
+#[derive(Debug, Clone, Serialize, Deserialize, Default)]
+pub struct Bank {
+    pub bank_code: String,
+    pub name: String,
+    pub name_eng: Option<String>,
+    #[serde(flatten)]
+    pub extra: Map<String, Value>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, Default)]
+pub struct MccCode {
+    pub code: String,
+    pub name: String,
+    pub name_eng: Option<String>,
+    #[serde(flatten)]
+    pub extra: Map<String, Value>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, Default)]
+pub struct City {
+    pub code: String,
+    pub name: String,
+    #[serde(flatten)]
+    pub extra: Map<String, Value>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, Default)]
+pub struct District {
+    pub code: String,
+    pub name: String,
+    pub city_code: Option<String>,
+    #[serde(flatten)]
+    pub extra: Map<String, Value>,
+}
+
+// --- Forward compatibility ---
+
+/// A response model that keeps fields unknown to this SDK version in its
+/// flattened `extra` map.
+pub trait ResponseModel: DeserializeOwned + Serialize + Default {
+    /// Unknown fields captured directly on this value.
+    fn extra(&self) -> &Map<String, Value>;
+
+    /// Dotted paths of all unknown fields, including those on nested models.
+    fn unknown_fields(&self) -> Vec<String> {
+        let mut out = Vec::new();
+        self.collect_unknown_fields("", &mut out);
+        out
+    }
+
+    #[doc(hidden)]
+    fn collect_unknown_fields(&self, path: &str, out: &mut Vec<String>);
+
+    /// Fill absent or null fields of a raw JSON value with this model's
+    /// defaults, recording the paths of the fields that were filled.
+    #[doc(hidden)]
+    fn fill_defaults(value: &mut Value, path: &str, filled: &mut Vec<String>);
+}
+
+impl<T: ResponseModel> ResponseModel for Vec<T> {
+    fn extra(&self) -> &Map<String, Value> {
+        static EMPTY: std::sync::OnceLock<Map<String, Value>> = std::sync::OnceLock::new();
+        EMPTY.get_or_init(Map::new)
+    }
+
+    fn collect_unknown_fields(&self, path: &str, out: &mut Vec<String>) {
+        for (i, item) in self.iter().enumerate() {
+            item.collect_unknown_fields(&format!("{}[{}]", path, i), out);
+        }
+    }
+
+    fn fill_defaults(value: &mut Value, path: &str, filled: &mut Vec<String>) {
+        if let Value::Array(items) = value {
+            for (i, item) in items.iter_mut().enumerate() {
+                T::fill_defaults(item, &format!("{}[{}]", path, i), filled);
+            }
+        }
+    }
+}
+
+impl<T: ResponseModel> ResponseModel for Option<T> {
+    fn extra(&self) -> &Map<String, Value> {
+        match self {
+            Some(inner) => inner.extra(),
+            None => {
+                static EMPTY: std::sync::OnceLock<Map<String, Value>> = std::sync::OnceLock::new();
+                EMPTY.get_or_init(Map::new)
+            }
+        }
+    }
+
+    fn collect_unknown_fields(&self, path: &str, out: &mut Vec<String>) {
+        if let Some(inner) = self {
+            inner.collect_unknown_fields(path, out);
+        }
+    }
+
+    fn fill_defaults(value: &mut Value, path: &str, filled: &mut Vec<String>) {
+        if !value.is_null() {
+            T::fill_defaults(value, path, filled);
+        }
+    }
+}
+
+fn join_path(path: &str, key: &str) -> String {
+    if path.is_empty() {
+        key.to_string()
+    } else {
+        format!("{}.{}", path, key)
+    }
+}
+
+/// Identifier and credential fields, which are never filled with defaults:
+/// an empty id or token would be passed on to later requests.
+const NEVER_DEFAULTED: &[&str] = &[
+    "access_token",
+    "refresh_token",
+    "id",
+    "invoice_id",
+    "payment_id",
+    "object_id",
+];
+
+fn fill_missing<T: Serialize + Default>(value: &mut Value, path: &str, filled: &mut Vec<String>) {
+    let (Value::Object(obj), Ok(Value::Object(defaults))) =
+        (value, serde_json::to_value(T::default()))
+    else {
+        return;
+    };
+    for (key, default) in defaults {
+        let absent = obj.get(&key).is_none_or(Value::is_null);
+        if absent && !default.is_null() && !NEVER_DEFAULTED.contains(&key.as_str()) {
+            filled.push(join_path(path, &key));
+            obj.insert(key, default);
+        }
+    }
+}
+
+macro_rules! response_model {
+    ($ty:ty { $($field:ident: $nested:ty),* $(,)? }) => {
+        impl ResponseModel for $ty {
+            fn extra(&self) -> &Map<String, Value> {
+                &self.extra
+            }
+
+            fn collect_unknown_fields(&self, path: &str, out: &mut Vec<String>) {
+                for key in self.extra.keys() {
+                    out.push(join_path(path, key));
+                }
+                $(
+                    self.$field
+                        .collect_unknown_fields(&join_path(path, stringify!($field)), out);
+                )*
+            }
+
+            fn fill_defaults(value: &mut Value, path: &str, filled: &mut Vec<String>) {
+                fill_missing::<Self>(value, path, filled);
+                $(
+                    if let Some(nested) = value.get_mut(stringify!($field)) {
+                        <$nested>::fill_defaults(
+                            nested,
+                            &join_path(path, stringify!($field)),
+                            filled,
+                        );
+                    }
+                )*
+            }
+        }
+    };
+}
+
+response_model!(TokenResponse {});
+response_model!(Deeplink {});
+response_model!(InvoiceResponse { urls: Vec<Deeplink> });
+response_model!(InvoiceDetail {
+    sender_branch_data: Option<InvoiceDetailSenderBranch>,
+    sender_staff_data: Option<InvoiceDetailSenderStaff>,
+    invoice_receiver_data: Option<InvoiceDetailReceiver>,
+    lines: Vec<InvoiceDetailLine>,
+    transactions: Vec<InvoiceDetailTransaction>,
+    payments: Vec<InvoicePayment>,
+});
+response_model!(InvoiceDetailSenderBranch {});
+response_model!(InvoiceDetailSenderStaff {});
+response_model!(InvoiceDetailReceiver {});
+response_model!(InvoiceDetailTransaction {});
+response_model!(InvoiceDetailLine {});
+response_model!(InvoicePayment {});
+response_model!(PaymentCheckResponse {
+    rows: Vec<PaymentCheckRow>
+});
+response_model!(PaymentCheckRow {
+    card_transactions: Vec<CardTransaction>,
+    p2p_transactions: Vec<P2PTransaction>,
+});
+response_model!(PaymentDetail {
+    card_transactions: Vec<CardTransaction>,
+    p2p_transactions: Vec<P2PTransaction>,
+});
+response_model!(CardTransaction {});
+response_model!(P2PTransaction {});
+response_model!(PaymentListResponse {
+    rows: Vec<PaymentListItem>
+});
+response_model!(PaymentListItem {});
+response_model!(EbarimtResponse {
+    barimt_items: Vec<EbarimtItem>,
+    barimt_histories: Vec<EbarimtHistory>,
+});
+response_model!(EbarimtItem {});
+response_model!(EbarimtHistory {});
+response_model!(MerchantResponse {});
+response_model!(MerchantListResponse {
+    rows: Vec<MerchantResponse>
+});
+response_model!(MerchantBankAccount {});
+response_model!(Bank {});
+response_model!(MccCode {});
+response_model!(City {});
+response_model!(District {});
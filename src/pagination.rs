@@ -0,0 +1,126 @@
+//! Auto-paginating streams over paged endpoints.
+
+use std::future::{ready, Future};
+
+use futures::stream::{self, Stream, StreamExt};
+
+use crate::client::QPayClient;
+use crate::error::QPayError;
+use crate::models::{
+    Offset, PaymentCheckRequest, PaymentCheckRow, PaymentListItem, PaymentListRequest,
+};
+
+/// Page size used when a request has no `offset`.
+pub const DEFAULT_PAGE_LIMIT: i32 = 100;
+
+impl QPayClient {
+    /// Stream every payment matching `req`, starting at `req.offset`.
+    ///
+    /// Pages are fetched as the stream is consumed, with up to `prefetch`
+    /// further pages requested ahead of the consumer. The stream stops once
+    /// `count` rows have been returned, on an empty page, or after the first
+    /// error.
+    pub fn list_payments_stream(
+        &self,
+        req: &PaymentListRequest,
+        prefetch: usize,
+    ) -> impl Stream<Item = Result<PaymentListItem, QPayError>> + '_ {
+        let req = req.clone();
+        paginate(req.offset.clone(), prefetch, move |offset| {
+            let req = PaymentListRequest {
+                offset,
+                ..req.clone()
+            };
+            async move {
+                let resp = self.list_payments(&req).await?;
+                Ok((resp.count, resp.rows))
+            }
+        })
+    }
+
+    /// Stream every payment row for the object in `req`, starting at
+    /// `req.offset` (or the first page of [`DEFAULT_PAGE_LIMIT`] rows).
+    ///
+    /// See [`QPayClient::list_payments_stream`] for paging behaviour.
+    pub fn check_payment_stream(
+        &self,
+        req: &PaymentCheckRequest,
+        prefetch: usize,
+    ) -> impl Stream<Item = Result<PaymentCheckRow, QPayError>> + '_ {
+        let req = req.clone();
+        let start = req.offset.clone().unwrap_or(Offset {
+            page_number: 1,
+            page_limit: DEFAULT_PAGE_LIMIT,
+        });
+        paginate(start, prefetch, move |offset| {
+            let req = PaymentCheckRequest {
+                offset: Some(offset),
+                ..req.clone()
+            };
+            async move {
+                let resp = self.check_payment(&req).await?;
+                Ok((resp.count, resp.rows))
+            }
+        })
+    }
+}
+
+/// Turn a page fetcher returning `(count, rows)` into a stream of rows.
+pub(crate) fn paginate<'a, T, F, Fut>(
+    start: Offset,
+    prefetch: usize,
+    fetch: F,
+) -> impl Stream<Item = Result<T, QPayError>> + 'a
+where
+    T: 'a,
+    F: Fn(Offset) -> Fut + Clone + 'a,
+    Fut: Future<Output = Result<(i32, Vec<T>), QPayError>> + 'a,
+{
+    let page_limit = start.page_limit.max(1);
+    let first_page = start.page_number.max(1);
+    let offset = move |page_number| Offset {
+        page_number,
+        page_limit,
+    };
+
+    stream::once(fetch.clone()(offset(first_page)))
+        .map(move |first| {
+            let (count, rows) = match first {
+                Ok(page) => page,
+                Err(e) => return stream::iter(vec![Err(e)]).left_stream(),
+            };
+
+            // Rows still to come from the first requested page onwards.
+            let skipped = i64::from(first_page - 1) * i64::from(page_limit);
+            let remaining = (i64::from(count) - skipped).max(0);
+            let pages = (remaining + i64::from(page_limit) - 1) / i64::from(page_limit);
+            let last_page = if rows.is_empty() {
+                first_page
+            } else {
+                first_page + pages as i32 - 1
+            };
+
+            let fetch = fetch.clone();
+            let rest = stream::iter(first_page + 1..=last_page)
+                .map(move |page_number| fetch(offset(page_number)))
+                .buffered(prefetch + 1)
+                .take_while(|page| ready(!matches!(page, Ok((_, rows)) if rows.is_empty())))
+                .flat_map(|page| match page {
+                    Ok((_, rows)) => stream::iter(rows.into_iter().map(Ok).collect::<Vec<_>>()),
+                    Err(e) => stream::iter(vec![Err(e)]),
+                });
+
+            stream::iter(rows.into_iter().map(Ok).collect::<Vec<_>>())
+                .chain(rest)
+                .take(remaining as usize)
+                .right_stream()
+        })
+        .flatten()
+        .scan(false, |failed, item| {
+            if *failed {
+                return ready(None);
+            }
+            *failed = item.is_err();
+            ready(Some(item))
+        })
+}
@@ -4,6 +4,7 @@ use crate::models::{
     PaymentCancelRequest, PaymentCheckRequest, PaymentCheckResponse, PaymentDetail,
     PaymentListRequest, PaymentListResponse, PaymentRefundRequest,
 };
+use crate::response::{ApiResponse, RawClient};
 
 impl QPayClient {
     /// Retrieve payment details by payment ID.
@@ -58,3 +59,65 @@ impl QPayClient {
             .await
     }
 }
+
+impl RawClient<'_> {
+    /// Retrieve payment details by payment ID.
+    /// GET /v2/payment/{id}
+    pub async fn get_payment(
+        &self,
+        payment_id: &str,
+    ) -> Result<ApiResponse<PaymentDetail>, QPayError> {
+        let path = format!("/v2/payment/{}", payment_id);
+        self.client
+            .do_request_raw::<(), PaymentDetail>(reqwest::Method::GET, &path, None)
+            .await
+    }
+
+    /// Check if a payment has been made for an invoice.
+    /// POST /v2/payment/check
+    pub async fn check_payment(
+        &self,
+        req: &PaymentCheckRequest,
+    ) -> Result<ApiResponse<PaymentCheckResponse>, QPayError> {
+        self.client
+            .do_request_raw(reqwest::Method::POST, "/v2/payment/check", Some(req))
+            .await
+    }
+
+    /// Return a list of payments matching the given criteria.
+    /// POST /v2/payment/list
+    pub async fn list_payments(
+        &self,
+        req: &PaymentListRequest,
+    ) -> Result<ApiResponse<PaymentListResponse>, QPayError> {
+        self.client
+            .do_request_raw(reqwest::Method::POST, "/v2/payment/list", Some(req))
+            .await
+    }
+
+    /// Cancel a payment (card transactions only).
+    /// DELETE /v2/payment/cancel/{id}
+    pub async fn cancel_payment(
+        &self,
+        payment_id: &str,
+        req: &PaymentCancelRequest,
+    ) -> Result<ApiResponse<()>, QPayError> {
+        let path = format!("/v2/payment/cancel/{}", payment_id);
+        self.client
+            .send_request(reqwest::Method::DELETE, &path, Some(req))
+            .await
+    }
+
+    /// Refund a payment (card transactions only).
+    /// DELETE /v2/payment/refund/{id}
+    pub async fn refund_payment(
+        &self,
+        payment_id: &str,
+        req: &PaymentRefundRequest,
+    ) -> Result<ApiResponse<()>, QPayError> {
+        let path = format!("/v2/payment/refund/{}", payment_id);
+        self.client
+            .send_request(reqwest::Method::DELETE, &path, Some(req))
+            .await
+    }
+}
@@ -0,0 +1,375 @@
+//! Polling helpers for clients without a callback URL (kiosks, POS terminals).
+
+use std::collections::{HashMap, VecDeque};
+use std::future::Future;
+use std::time::Duration;
+
+use futures::Stream;
+use tokio::time::Instant;
+
+use crate::client::QPayClient;
+use crate::error::QPayError;
+use crate::models::{InvoiceDetail, PaymentCheckRequest, PaymentCheckResponse, PaymentCheckRow};
+
+pub use tokio_util::sync::CancellationToken;
+
+/// Default delay before the first re-check.
+pub const DEFAULT_POLL_INTERVAL: Duration = Duration::from_secs(2);
+
+/// Default upper bound for the delay between checks.
+pub const DEFAULT_MAX_POLL_INTERVAL: Duration = Duration::from_secs(30);
+
+/// Payment status reported by `check_payment` for a settled payment.
+pub const PAYMENT_STATUS_PAID: &str = "PAID";
+
+/// `invoice_status` reported by `get_invoice` for a cancelled invoice.
+pub const INVOICE_STATUS_CANCELLED: &str = "CANCELLED";
+
+/// Options for [`QPayClient::wait_for_payment`].
+#[derive(Debug, Clone)]
+pub struct WaitOptions {
+    /// Delay before the first re-check.
+    pub interval: Duration,
+    /// Factor the delay is multiplied by after each check (1.0 = fixed interval).
+    pub backoff: f64,
+    /// Upper bound for the delay between checks.
+    pub max_interval: Duration,
+    /// Give up after this long. `None` waits until paid or cancelled.
+    pub timeout: Option<Duration>,
+    /// For invoices created with `allow_partial`: wait until `paid_amount`
+    /// reaches this amount instead of stopping at the first paid row.
+    pub amount: Option<f64>,
+    /// Stop waiting when this token is cancelled.
+    pub cancellation: Option<CancellationToken>,
+}
+
+impl Default for WaitOptions {
+    fn default() -> Self {
+        Self {
+            interval: DEFAULT_POLL_INTERVAL,
+            backoff: 1.0,
+            max_interval: DEFAULT_MAX_POLL_INTERVAL,
+            timeout: None,
+            amount: None,
+            cancellation: None,
+        }
+    }
+}
+
+impl WaitOptions {
+    /// Poll at a fixed interval.
+    pub fn with_interval(mut self, interval: Duration) -> Self {
+        self.interval = interval;
+        self
+    }
+
+    /// Multiply the delay by `factor` after each check, up to `max_interval`.
+    pub fn with_backoff(mut self, factor: f64, max_interval: Duration) -> Self {
+        self.backoff = factor;
+        self.max_interval = max_interval;
+        self
+    }
+
+    /// Give up with [`QPayError::Timeout`] after `timeout`.
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = Some(timeout);
+        self
+    }
+
+    /// Wait for the full `amount` of a partially payable invoice.
+    pub fn with_partial_payments(mut self, amount: f64) -> Self {
+        self.amount = Some(amount);
+        self
+    }
+
+    /// Give up with [`QPayError::Cancelled`] when `token` is cancelled.
+    pub fn with_cancellation(mut self, token: CancellationToken) -> Self {
+        self.cancellation = Some(token);
+        self
+    }
+
+    /// Whether `resp` settles the invoice under these options.
+    pub fn is_complete(&self, resp: &PaymentCheckResponse) -> bool {
+        match self.amount {
+            Some(amount) => resp.total_paid() >= amount,
+            None => resp.is_paid(),
+        }
+    }
+
+    fn next_interval(&self, interval: Duration) -> Duration {
+        interval
+            .mul_f64(self.backoff.max(1.0))
+            .min(self.max_interval)
+    }
+}
+
+impl PaymentCheckResponse {
+    /// Whether any row has status `PAID`.
+    pub fn is_paid(&self) -> bool {
+        self.rows
+            .iter()
+            .any(|row| row.payment_status == PAYMENT_STATUS_PAID)
+    }
+
+    /// `paid_amount`, or the sum of the paid rows' amounts if QPay omitted it.
+    pub fn total_paid(&self) -> f64 {
+        self.paid_amount.unwrap_or_else(|| {
+            self.rows
+                .iter()
+                .filter(|row| row.payment_status == PAYMENT_STATUS_PAID)
+                .filter_map(|row| row.payment_amount.parse::<f64>().ok())
+                .sum()
+        })
+    }
+}
+
+impl InvoiceDetail {
+    /// Whether `invoice_status` reports the invoice as cancelled. Both
+    /// spellings are accepted, in any case.
+    pub fn is_cancelled(&self) -> bool {
+        let status = self.invoice_status.trim();
+        status.eq_ignore_ascii_case(INVOICE_STATUS_CANCELLED)
+            || status.eq_ignore_ascii_case("CANCELED")
+    }
+}
+
+impl QPayClient {
+    /// Poll `check_payment` for an invoice until it is paid.
+    ///
+    /// Returns the response that settled the invoice, or
+    /// [`QPayError::Timeout`] / [`QPayError::Cancelled`]. Errors from
+    /// `check_payment` are returned as-is.
+    pub async fn wait_for_payment(
+        &self,
+        invoice_id: &str,
+        options: &WaitOptions,
+    ) -> Result<PaymentCheckResponse, QPayError> {
+        let req = invoice_check_request(invoice_id);
+        let deadline = options.timeout.map(|timeout| Instant::now() + timeout);
+        let cancellation = options.cancellation.clone().unwrap_or_default();
+        let mut interval = options.interval;
+
+        loop {
+            let resp = tokio::select! {
+                biased;
+                _ = cancellation.cancelled() => return Err(cancelled(invoice_id)),
+                _ = sleep_until(deadline) => return Err(timed_out(invoice_id)),
+                resp = self.check_payment(&req) => resp?,
+            };
+            if options.is_complete(&resp) {
+                return Ok(resp);
+            }
+
+            tokio::select! {
+                biased;
+                _ = cancellation.cancelled() => return Err(cancelled(invoice_id)),
+                _ = sleep_until(deadline) => return Err(timed_out(invoice_id)),
+                _ = tokio::time::sleep(interval) => {}
+            }
+            interval = options.next_interval(interval);
+        }
+    }
+
+    /// Poll `check_payment` for an invoice and emit one [`PaymentEvent`] per
+    /// new `payment_id` and per status change.
+    ///
+    /// `check_payment` does not report cancellation, so a check that shows
+    /// no new payment or status change is followed by `get_invoice`. The
+    /// stream ends once the invoice is paid (see
+    /// [`WaitOptions::is_complete`]), after
+    /// [`PaymentEvent::InvoiceCancelled`], or when the cancellation token
+    /// fires. Passing the timeout yields a final [`QPayError::Timeout`].
+    /// Transient errors (see [`QPayError::is_transient`]) are yielded and
+    /// polling continues with the usual backoff; other errors end the stream
+    /// after being yielded.
+    pub fn payment_events(
+        &self,
+        invoice_id: &str,
+        options: WaitOptions,
+    ) -> impl Stream<Item = Result<PaymentEvent, QPayError>> + '_ {
+        let state = EventState {
+            client: self,
+            req: invoice_check_request(invoice_id),
+            deadline: options.timeout.map(|timeout| Instant::now() + timeout),
+            cancellation: options.cancellation.clone().unwrap_or_default(),
+            interval: None,
+            options,
+            seen: HashMap::new(),
+            queue: VecDeque::new(),
+            done: false,
+        };
+        futures::stream::unfold(state, |mut state| async move {
+            let item = state.next_event().await?;
+            Some((item, state))
+        })
+    }
+}
+
+/// A change in the payments of an invoice, emitted by
+/// [`QPayClient::payment_events`].
+#[derive(Debug, Clone)]
+pub enum PaymentEvent {
+    /// A `payment_id` seen for the first time.
+    NewPayment(PaymentCheckRow),
+    /// A known payment changed its `payment_status`.
+    StatusChanged {
+        previous_status: String,
+        row: PaymentCheckRow,
+    },
+    /// `get_invoice` reported the invoice as cancelled (see
+    /// [`InvoiceDetail::is_cancelled`]). This is the last event.
+    InvoiceCancelled,
+}
+
+struct EventState<'a> {
+    client: &'a QPayClient,
+    req: PaymentCheckRequest,
+    options: WaitOptions,
+    deadline: Option<Instant>,
+    cancellation: CancellationToken,
+    interval: Option<Duration>,
+    seen: HashMap<String, String>,
+    queue: VecDeque<PaymentEvent>,
+    done: bool,
+}
+
+impl EventState<'_> {
+    async fn next_event(&mut self) -> Option<Result<PaymentEvent, QPayError>> {
+        loop {
+            if let Some(event) = self.queue.pop_front() {
+                return Some(Ok(event));
+            }
+            if self.done {
+                return None;
+            }
+
+            // No delay before the first check.
+            let delay = match self.interval {
+                Some(interval) => {
+                    self.interval = Some(self.options.next_interval(interval));
+                    interval
+                }
+                None => {
+                    self.interval = Some(self.options.interval);
+                    Duration::ZERO
+                }
+            };
+            let client = self.client;
+            let checked = until_stopped(
+                &self.cancellation,
+                self.deadline,
+                &self.req.object_id,
+                async {
+                    tokio::time::sleep(delay).await;
+                    client.check_payment(&self.req).await
+                },
+            )
+            .await;
+            let resp = match checked {
+                Some(Ok(resp)) => resp,
+                Some(Err(e)) => return Some(Err(self.failed(e))),
+                None => {
+                    self.done = true;
+                    return None;
+                }
+            };
+            if self.options.is_complete(&resp) {
+                self.done = true;
+            }
+            if self.record(resp.rows) || self.done {
+                continue;
+            }
+
+            // check_payment does not report cancellation; ask only when it
+            // showed no progress
+            let invoice_id = &self.req.object_id;
+            let invoice = until_stopped(
+                &self.cancellation,
+                self.deadline,
+                invoice_id,
+                client.get_invoice(invoice_id),
+            )
+            .await;
+            match invoice {
+                Some(Ok(invoice)) if invoice.is_cancelled() => {
+                    self.done = true;
+                    return Some(Ok(PaymentEvent::InvoiceCancelled));
+                }
+                Some(Ok(_)) => {}
+                Some(Err(e)) => return Some(Err(self.failed(e))),
+                None => {
+                    self.done = true;
+                    return None;
+                }
+            }
+        }
+    }
+
+    /// End the stream unless `err` is transient, in which case polling goes
+    /// on after it is yielded.
+    fn failed(&mut self, err: QPayError) -> QPayError {
+        self.done = !err.is_transient();
+        err
+    }
+
+    /// Queue events for `rows`, returning whether there were any.
+    fn record(&mut self, rows: Vec<PaymentCheckRow>) -> bool {
+        let queued = self.queue.len();
+        for row in rows {
+            let status = row.payment_status.clone();
+            match self.seen.insert(row.payment_id.clone(), status.clone()) {
+                None => self.queue.push_back(PaymentEvent::NewPayment(row)),
+                Some(previous_status) if previous_status != status => {
+                    self.queue.push_back(PaymentEvent::StatusChanged {
+                        previous_status,
+                        row,
+                    });
+                }
+                Some(_) => {}
+            }
+        }
+        self.queue.len() > queued
+    }
+}
+
+pub(crate) fn invoice_check_request(invoice_id: &str) -> PaymentCheckRequest {
+    PaymentCheckRequest {
+        object_type: "INVOICE".to_string(),
+        object_id: invoice_id.to_string(),
+        offset: None,
+    }
+}
+
+/// Run `request` unless `cancellation` fires first (`None`) or `deadline`
+/// passes (a [`QPayError::Timeout`]).
+async fn until_stopped<T>(
+    cancellation: &CancellationToken,
+    deadline: Option<Instant>,
+    invoice_id: &str,
+    request: impl Future<Output = Result<T, QPayError>>,
+) -> Option<Result<T, QPayError>> {
+    tokio::select! {
+        biased;
+        _ = cancellation.cancelled() => None,
+        _ = sleep_until(deadline) => Some(Err(timed_out(invoice_id))),
+        result = request => Some(result),
+    }
+}
+
+async fn sleep_until(deadline: Option<Instant>) {
+    match deadline {
+        Some(deadline) => tokio::time::sleep_until(deadline).await,
+        None => std::future::pending().await,
+    }
+}
+
+fn timed_out(invoice_id: &str) -> QPayError {
+    QPayError::Timeout(format!("invoice {} was not paid in time", invoice_id))
+}
+
+fn cancelled(invoice_id: &str) -> QPayError {
+    QPayError::Cancelled(format!(
+        "stopped waiting for payment of invoice {}",
+        invoice_id
+    ))
+}
@@ -0,0 +1,128 @@
+use std::any::Any;
+use std::collections::HashMap;
+use std::time::Instant;
+
+use crate::client::QPayClient;
+use crate::error::QPayError;
+use crate::models::{Bank, City, District, MccCode, ResponseModel};
+use crate::response::{ApiResponse, RawClient};
+
+/// In-memory cache of decoded reference data, keyed by request path.
+#[derive(Default)]
+pub(crate) struct ReferenceCache {
+    entries: HashMap<String, (Instant, Box<dyn Any + Send + Sync>)>,
+}
+
+/// Response types that can be kept in the [`ReferenceCache`].
+trait Cacheable: ResponseModel + Clone + Send + Sync + 'static {}
+
+impl<R: ResponseModel + Clone + Send + Sync + 'static> Cacheable for R {}
+
+impl QPayClient {
+    /// Return the list of banks supported by QPay.
+    /// GET /v2/banks
+    ///
+    /// Results are cached for `QPayConfig::reference_data_ttl`.
+    pub async fn get_banks(&self) -> Result<Vec<Bank>, QPayError> {
+        self.cached_get("/v2/banks").await
+    }
+
+    /// Return the list of merchant category codes.
+    /// GET /v2/mcc
+    ///
+    /// Results are cached for `QPayConfig::reference_data_ttl`.
+    pub async fn get_mcc_codes(&self) -> Result<Vec<MccCode>, QPayError> {
+        self.cached_get("/v2/mcc").await
+    }
+
+    /// Return the list of cities and aimags.
+    /// GET /v2/aimag_city
+    ///
+    /// Results are cached for `QPayConfig::reference_data_ttl`.
+    pub async fn get_cities(&self) -> Result<Vec<City>, QPayError> {
+        self.cached_get("/v2/aimag_city").await
+    }
+
+    /// Return the districts of a city or aimag.
+    /// GET /v2/aimag_city/{code}
+    ///
+    /// Results are cached for `QPayConfig::reference_data_ttl`.
+    pub async fn get_districts(&self, city_code: &str) -> Result<Vec<District>, QPayError> {
+        let path = format!("/v2/aimag_city/{}", city_code);
+        self.cached_get(&path).await
+    }
+
+    /// Drop all cached reference data so the next call fetches fresh values.
+    pub async fn clear_reference_cache(&self) {
+        self.reference_cache.lock().await.entries.clear();
+    }
+
+    async fn cached_get<R: Cacheable>(&self, path: &str) -> Result<R, QPayError> {
+        {
+            let cache = self.reference_cache.lock().await;
+            if let Some((fetched_at, value)) = cache.entries.get(path) {
+                if fetched_at.elapsed() < self.config.reference_data_ttl {
+                    if let Some(value) = value.downcast_ref::<R>() {
+                        return Ok(value.clone());
+                    }
+                }
+            }
+        }
+
+        self.fetch_reference(path)
+            .await
+            .map(ApiResponse::into_inner)
+    }
+
+    /// Fetch reference data and store the decoded value in the cache.
+    async fn fetch_reference<R: Cacheable>(&self, path: &str) -> Result<ApiResponse<R>, QPayError> {
+        let resp: ApiResponse<R> = self
+            .do_request_raw::<(), R>(reqwest::Method::GET, path, None)
+            .await?;
+
+        let mut cache = self.reference_cache.lock().await;
+        cache.entries.insert(
+            path.to_string(),
+            (Instant::now(), Box::new(resp.value.clone())),
+        );
+        Ok(resp)
+    }
+}
+
+impl RawClient<'_> {
+    /// Return the list of banks supported by QPay.
+    /// GET /v2/banks
+    ///
+    /// Always fetched; the response replaces the cached value.
+    pub async fn get_banks(&self) -> Result<ApiResponse<Vec<Bank>>, QPayError> {
+        self.client.fetch_reference("/v2/banks").await
+    }
+
+    /// Return the list of merchant category codes.
+    /// GET /v2/mcc
+    ///
+    /// Always fetched; the response replaces the cached value.
+    pub async fn get_mcc_codes(&self) -> Result<ApiResponse<Vec<MccCode>>, QPayError> {
+        self.client.fetch_reference("/v2/mcc").await
+    }
+
+    /// Return the list of cities and aimags.
+    /// GET /v2/aimag_city
+    ///
+    /// Always fetched; the response replaces the cached value.
+    pub async fn get_cities(&self) -> Result<ApiResponse<Vec<City>>, QPayError> {
+        self.client.fetch_reference("/v2/aimag_city").await
+    }
+
+    /// Return the districts of a city or aimag.
+    /// GET /v2/aimag_city/{code}
+    ///
+    /// Always fetched; the response replaces the cached value.
+    pub async fn get_districts(
+        &self,
+        city_code: &str,
+    ) -> Result<ApiResponse<Vec<District>>, QPayError> {
+        let path = format!("/v2/aimag_city/{}", city_code);
+        self.client.fetch_reference(&path).await
+    }
+}
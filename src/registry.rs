@@ -0,0 +1,258 @@
+//! Offline registry of Mongolian bank codes and ebarimt district codes.
+//!
+//! The bundled tables are a snapshot and can be replaced at runtime, e.g.
+//! with data fetched through [`QPayClient::get_banks`].
+
+use std::collections::BTreeMap;
+use std::sync::{OnceLock, RwLock};
+
+use crate::client::QPayClient;
+use crate::error::QPayError;
+use crate::models::{
+    Account, Bank, CreateEbarimtInvoiceRequest, CreateEbarimtRequest, District, P2PTransaction,
+};
+
+/// A code with its Mongolian and English names.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RegistryEntry {
+    pub code: String,
+    pub name_mn: String,
+    pub name_en: String,
+}
+
+/// Registry lookup failure.
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+pub enum RegistryError {
+    #[error("unknown bank code: {0}")]
+    UnknownBankCode(String),
+
+    #[error("unknown district code: {0}")]
+    UnknownDistrictCode(String),
+}
+
+const BUNDLED_BANKS: &[(&str, &str, &str)] = &[
+    ("010000", "Монголбанк", "Bank of Mongolia"),
+    ("020000", "Капитрон банк", "Capitron Bank"),
+    (
+        "040000",
+        "Худалдаа хөгжлийн банк",
+        "Trade and Development Bank",
+    ),
+    ("050000", "Хаан банк", "Khan Bank"),
+    ("150000", "Голомт банк", "Golomt Bank"),
+    (
+        "190000",
+        "Тээвэр хөгжлийн банк",
+        "Transport and Development Bank",
+    ),
+    ("210000", "Ариг банк", "Arig Bank"),
+    (
+        "290000",
+        "Үндэсний хөрөнгө оруулалтын банк",
+        "National Investment Bank",
+    ),
+    ("320000", "Хас банк", "XacBank"),
+    ("330000", "Чингис хаан банк", "Chinggis Khaan Bank"),
+    ("340000", "Төрийн банк", "State Bank"),
+    ("360000", "Хөгжлийн банк", "Development Bank of Mongolia"),
+    ("380000", "Богд банк", "Bogd Bank"),
+    ("390000", "Төрийн сан", "State Treasury"),
+];
+
+/// Ebarimt district codes, as sent in `district_code`: the aimags and the
+/// districts (дүүрэг) of Ulaanbaatar.
+const BUNDLED_DISTRICTS: &[(&str, &str, &str)] = &[
+    ("01", "Архангай", "Arkhangai"),
+    ("02", "Баян-Өлгий", "Bayan-Ulgii"),
+    ("03", "Баянхонгор", "Bayankhongor"),
+    ("04", "Булган", "Bulgan"),
+    ("05", "Говь-Алтай", "Govi-Altai"),
+    ("06", "Дорноговь", "Dornogovi"),
+    ("07", "Дорнод", "Dornod"),
+    ("08", "Дундговь", "Dundgovi"),
+    ("09", "Завхан", "Zavkhan"),
+    ("10", "Өвөрхангай", "Uvurkhangai"),
+    ("11", "Өмнөговь", "Umnugovi"),
+    ("12", "Сүхбаатар", "Sukhbaatar"),
+    ("13", "Сэлэнгэ", "Selenge"),
+    ("14", "Төв", "Tuv"),
+    ("15", "Увс", "Uvs"),
+    ("16", "Ховд", "Khovd"),
+    ("17", "Хөвсгөл", "Khuvsgul"),
+    ("18", "Хэнтий", "Khentii"),
+    ("19", "Дархан-Уул", "Darkhan-Uul"),
+    ("20", "Орхон", "Orkhon"),
+    ("23", "Хан-Уул дүүрэг", "Khan-Uul District"),
+    ("24", "Баянзүрх дүүрэг", "Bayanzurkh District"),
+    ("25", "Сүхбаатар дүүрэг", "Sukhbaatar District"),
+    ("26", "Баянгол дүүрэг", "Bayangol District"),
+    ("27", "Багануур дүүрэг", "Baganuur District"),
+    ("28", "Багахангай дүүрэг", "Bagakhangai District"),
+    ("29", "Налайх дүүрэг", "Nalaikh District"),
+    ("32", "Говьсүмбэр", "Govisumber"),
+    ("34", "Сонгинохайрхан дүүрэг", "Songinokhairkhan District"),
+    ("35", "Чингэлтэй дүүрэг", "Chingeltei District"),
+];
+
+type Table = BTreeMap<String, RegistryEntry>;
+
+struct Registry {
+    banks: Table,
+    districts: Table,
+}
+
+fn table(rows: &[(&str, &str, &str)]) -> Table {
+    rows.iter()
+        .map(|(code, mn, en)| {
+            (
+                code.to_string(),
+                RegistryEntry {
+                    code: code.to_string(),
+                    name_mn: mn.to_string(),
+                    name_en: en.to_string(),
+                },
+            )
+        })
+        .collect()
+}
+
+fn registry() -> &'static RwLock<Registry> {
+    static REGISTRY: OnceLock<RwLock<Registry>> = OnceLock::new();
+    REGISTRY.get_or_init(|| {
+        RwLock::new(Registry {
+            banks: table(BUNDLED_BANKS),
+            districts: table(BUNDLED_DISTRICTS),
+        })
+    })
+}
+
+fn read<T>(f: impl FnOnce(&Registry) -> T) -> T {
+    let guard = registry().read().unwrap_or_else(|e| e.into_inner());
+    f(&guard)
+}
+
+fn write(f: impl FnOnce(&mut Registry)) {
+    let mut guard = registry().write().unwrap_or_else(|e| e.into_inner());
+    f(&mut guard)
+}
+
+fn collect(entries: impl IntoIterator<Item = RegistryEntry>) -> Table {
+    entries
+        .into_iter()
+        .map(|entry| (entry.code.clone(), entry))
+        .collect()
+}
+
+/// Look up a bank by its code (e.g. `"050000"`).
+pub fn bank(code: &str) -> Option<RegistryEntry> {
+    read(|r| r.banks.get(code.trim()).cloned())
+}
+
+/// Look up a district by its ebarimt code (e.g. `"34"`).
+pub fn district(code: &str) -> Option<RegistryEntry> {
+    read(|r| r.districts.get(code.trim()).cloned())
+}
+
+/// All known banks, ordered by code.
+pub fn banks() -> Vec<RegistryEntry> {
+    read(|r| r.banks.values().cloned().collect())
+}
+
+/// All known districts, ordered by code.
+pub fn districts() -> Vec<RegistryEntry> {
+    read(|r| r.districts.values().cloned().collect())
+}
+
+/// Fail with [`RegistryError::UnknownBankCode`] if the code is not registered.
+pub fn validate_bank_code(code: &str) -> Result<(), RegistryError> {
+    bank(code)
+        .map(|_| ())
+        .ok_or_else(|| RegistryError::UnknownBankCode(code.to_string()))
+}
+
+/// Fail with [`RegistryError::UnknownDistrictCode`] if the code is not registered.
+pub fn validate_district_code(code: &str) -> Result<(), RegistryError> {
+    district(code)
+        .map(|_| ())
+        .ok_or_else(|| RegistryError::UnknownDistrictCode(code.to_string()))
+}
+
+/// Replace the bank table.
+pub fn refresh_banks(entries: impl IntoIterator<Item = RegistryEntry>) {
+    let banks = collect(entries);
+    write(|r| r.banks = banks);
+}
+
+/// Replace the district table.
+pub fn refresh_districts(entries: impl IntoIterator<Item = RegistryEntry>) {
+    let districts = collect(entries);
+    write(|r| r.districts = districts);
+}
+
+/// Restore the bundled tables.
+pub fn reset() {
+    write(|r| {
+        r.banks = table(BUNDLED_BANKS);
+        r.districts = table(BUNDLED_DISTRICTS);
+    });
+}
+
+/// Replace the bank table with the list returned by [`QPayClient::get_banks`].
+pub async fn refresh_banks_from(client: &QPayClient) -> Result<(), QPayError> {
+    let banks = client.get_banks().await?;
+    refresh_banks(banks.iter().map(RegistryEntry::from));
+    Ok(())
+}
+
+impl From<&Bank> for RegistryEntry {
+    fn from(bank: &Bank) -> Self {
+        Self {
+            code: bank.bank_code.clone(),
+            name_mn: bank.name.clone(),
+            name_en: bank.name_eng.clone().unwrap_or_else(|| bank.name.clone()),
+        }
+    }
+}
+
+impl From<&District> for RegistryEntry {
+    fn from(district: &District) -> Self {
+        Self {
+            code: district.code.clone(),
+            name_mn: district.name.clone(),
+            // QPay only returns the Mongolian name
+            name_en: String::new(),
+        }
+    }
+}
+
+impl Account {
+    /// Check `account_bank_code` against the registry.
+    pub fn validate_codes(&self) -> Result<(), RegistryError> {
+        validate_bank_code(&self.account_bank_code)
+    }
+}
+
+impl P2PTransaction {
+    /// Check `transaction_bank_code` and `account_bank_code` against the registry.
+    pub fn validate_codes(&self) -> Result<(), RegistryError> {
+        validate_bank_code(&self.transaction_bank_code)?;
+        validate_bank_code(&self.account_bank_code)
+    }
+}
+
+impl CreateEbarimtInvoiceRequest {
+    /// Check `district_code` against the registry.
+    pub fn validate_codes(&self) -> Result<(), RegistryError> {
+        validate_district_code(&self.district_code)
+    }
+}
+
+impl CreateEbarimtRequest {
+    /// Check `district_code`, if set, against the registry.
+    pub fn validate_codes(&self) -> Result<(), RegistryError> {
+        match &self.district_code {
+            Some(code) => validate_district_code(code),
+            None => Ok(()),
+        }
+    }
+}
@@ -0,0 +1,86 @@
+use std::time::Duration;
+
+use reqwest::header::HeaderMap;
+use reqwest::StatusCode;
+
+use crate::client::QPayClient;
+use crate::error::QPayError;
+
+/// A parsed API result together with the raw HTTP response it came from.
+#[derive(Debug, Clone)]
+pub struct ApiResponse<T> {
+    /// The deserialized response value.
+    pub value: T,
+    /// HTTP status code.
+    pub status: StatusCode,
+    /// Response headers (request IDs, rate-limit headers, ...).
+    pub headers: HeaderMap,
+    /// Raw response body.
+    pub body: String,
+    /// Time from sending the request until the body was fully read.
+    pub elapsed: Duration,
+}
+
+impl<T> ApiResponse<T> {
+    /// Discard the raw response details and return the parsed value.
+    pub fn into_inner(self) -> T {
+        self.value
+    }
+
+    /// Return a header value as a string, if present and valid UTF-8.
+    pub fn header(&self, name: &str) -> Option<&str> {
+        self.headers.get(name).and_then(|v| v.to_str().ok())
+    }
+
+    /// Replace the parsed value, keeping the raw response details.
+    pub fn with_value<U>(self, value: U) -> ApiResponse<U> {
+        ApiResponse {
+            value,
+            status: self.status,
+            headers: self.headers,
+            body: self.body,
+            elapsed: self.elapsed,
+        }
+    }
+}
+
+/// A view of [`QPayClient`] whose endpoint methods return [`ApiResponse`].
+///
+/// Obtained with [`QPayClient::with_raw`].
+#[derive(Clone, Copy)]
+pub struct RawClient<'a> {
+    pub(crate) client: &'a QPayClient,
+}
+
+impl RawClient<'_> {
+    /// Call any QPay endpoint, keeping the raw HTTP response details.
+    /// See [`QPayClient::request`].
+    pub async fn request<B: serde::Serialize, R: serde::de::DeserializeOwned>(
+        &self,
+        method: reqwest::Method,
+        path: &str,
+        body: Option<&B>,
+    ) -> Result<ApiResponse<R>, QPayError> {
+        let path = if path.starts_with('/') {
+            path.to_string()
+        } else {
+            format!("/{}", path)
+        };
+        let raw = self.client.send_request(method, &path, body).await?;
+        let json = if raw.body.trim().is_empty() {
+            "null"
+        } else {
+            raw.body.as_str()
+        };
+        let value = serde_json::from_str(json)?;
+        Ok(raw.with_value(value))
+    }
+}
+
+impl QPayClient {
+    /// Return a view of this client whose endpoint methods also expose the
+    /// HTTP status, headers, raw body and elapsed time.
+    pub fn with_raw(&self) -> RawClient<'_> {
+        RawClient { client: self }
+    }
+}
@@ -0,0 +1,542 @@
+//! Recurring (subscription) invoices.
+//!
+//! An invoice created with `allow_subscribe` is charged again every
+//! `subscription_interval`, and QPay reports each charge to the
+//! `subscription_webhook`. Webhooks can be lost, so with the `chrono`
+//! feature a [`SubscriptionTracker`] keeps each subscription's next
+//! expected charge and [`QPayClient::missed_subscription_payments`]
+//! confirms overdue charges with `check_payment`.
+
+use std::collections::BTreeMap;
+use std::fmt;
+use std::str::FromStr;
+
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use serde_json::Value;
+
+use crate::callback::PAYMENT_ID_PARAMS;
+use crate::models::CreateInvoiceRequest;
+use crate::polling::PAYMENT_STATUS_PAID;
+use crate::validation::ValidationError;
+
+#[cfg(feature = "chrono")]
+pub use self::tracking::*;
+
+/// Fields QPay uses for the invoice id of a subscription charge.
+const INVOICE_ID_PARAMS: &[&str] = &["invoice_id", "object_id"];
+
+/// Unit of a [`SubscriptionInterval`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum IntervalUnit {
+    Day,
+    Week,
+    Month,
+    Year,
+}
+
+impl IntervalUnit {
+    /// The single-letter code QPay uses for this unit.
+    pub fn code(self) -> char {
+        match self {
+            Self::Day => 'D',
+            Self::Week => 'W',
+            Self::Month => 'M',
+            Self::Year => 'Y',
+        }
+    }
+}
+
+/// How often a subscription invoice is charged, e.g. `1M` for monthly.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct SubscriptionInterval {
+    pub count: u32,
+    pub unit: IntervalUnit,
+}
+
+impl SubscriptionInterval {
+    pub const DAILY: Self = Self::once(IntervalUnit::Day);
+    pub const WEEKLY: Self = Self::once(IntervalUnit::Week);
+    pub const MONTHLY: Self = Self::once(IntervalUnit::Month);
+    pub const YEARLY: Self = Self::once(IntervalUnit::Year);
+
+    const fn once(unit: IntervalUnit) -> Self {
+        Self { count: 1, unit }
+    }
+
+    /// An interval of `count` units. A count of zero is rejected, as it is
+    /// by [`parse`](Self::parse).
+    pub fn new(count: u32, unit: IntervalUnit) -> Result<Self, ValidationError> {
+        if count == 0 {
+            return Err(ValidationError::SubscriptionInterval(format!(
+                "0{}",
+                unit.code()
+            )));
+        }
+        Ok(Self { count, unit })
+    }
+
+    /// Parse `1M`, `2W`, `30D`, a bare unit letter, or a word such as
+    /// `MONTHLY` (case-insensitive).
+    pub fn parse(value: &str) -> Result<Self, ValidationError> {
+        let invalid = || ValidationError::SubscriptionInterval(value.to_string());
+        let upper = value.trim().to_ascii_uppercase();
+
+        let word = match upper.as_str() {
+            "DAY" | "DAILY" => Some(Self::DAILY),
+            "WEEK" | "WEEKLY" => Some(Self::WEEKLY),
+            "MONTH" | "MONTHLY" => Some(Self::MONTHLY),
+            "YEAR" | "YEARLY" | "ANNUAL" | "ANNUALLY" => Some(Self::YEARLY),
+            _ => None,
+        };
+        if let Some(interval) = word {
+            return Ok(interval);
+        }
+
+        let split = upper
+            .find(|c: char| !c.is_ascii_digit())
+            .ok_or_else(invalid)?;
+        let (count, unit) = upper.split_at(split);
+        let count = match count {
+            "" => 1,
+            digits => digits.parse().map_err(|_| invalid())?,
+        };
+        let unit = match unit {
+            "D" => IntervalUnit::Day,
+            "W" => IntervalUnit::Week,
+            "M" => IntervalUnit::Month,
+            "Y" => IntervalUnit::Year,
+            _ => return Err(invalid()),
+        };
+        Self::new(count, unit).map_err(|_| invalid())
+    }
+}
+
+impl fmt::Display for SubscriptionInterval {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}{}", self.count, self.unit.code())
+    }
+}
+
+impl FromStr for SubscriptionInterval {
+    type Err = ValidationError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Self::parse(s)
+    }
+}
+
+impl From<SubscriptionInterval> for String {
+    fn from(interval: SubscriptionInterval) -> Self {
+        interval.to_string()
+    }
+}
+
+impl Serialize for SubscriptionInterval {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+impl<'de> Deserialize<'de> for SubscriptionInterval {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let s = String::deserialize(deserializer)?;
+        Self::parse(&s).map_err(serde::de::Error::custom)
+    }
+}
+
+impl CreateInvoiceRequest {
+    /// Make this a recurring invoice charged every `interval`, with charges
+    /// reported to `webhook_url`.
+    pub fn with_subscription(
+        mut self,
+        interval: SubscriptionInterval,
+        webhook_url: impl Into<String>,
+    ) -> Self {
+        self.allow_subscribe = Some(true);
+        self.subscription_interval = Some(interval.into());
+        self.subscription_webhook = Some(webhook_url.into());
+        self
+    }
+
+    /// The parsed `subscription_interval`, if set and valid.
+    pub fn subscription_interval_parsed(&self) -> Option<SubscriptionInterval> {
+        self.subscription_interval.as_deref()?.parse().ok()
+    }
+}
+
+/// A subscription charge reported to the `subscription_webhook`.
+///
+/// Like a payment callback, a webhook is unauthenticated: confirm the
+/// charge with `get_payment` or `check_payment` before trusting it.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct SubscriptionWebhook {
+    pub invoice_id: Option<String>,
+    pub payment_id: Option<String>,
+    pub payment_status: Option<String>,
+    pub payment_amount: Option<f64>,
+    pub next_payment_date: Option<String>,
+    pub next_payment_datetime: Option<String>,
+    /// Every field of the payload, from the query string and the JSON or
+    /// form-encoded body.
+    pub params: BTreeMap<String, String>,
+}
+
+impl SubscriptionWebhook {
+    /// Parse a webhook from its query string and body. Body fields take
+    /// precedence over query parameters of the same name.
+    pub fn from_request(query: Option<&str>, body: &[u8]) -> Self {
+        let query = query.unwrap_or("");
+        let query = query.strip_prefix('?').unwrap_or(query);
+        let mut params: BTreeMap<String, String> = url::form_urlencoded::parse(query.as_bytes())
+            .into_owned()
+            .collect();
+
+        match serde_json::from_slice::<Value>(body) {
+            Ok(Value::Object(fields)) => {
+                for (key, value) in fields {
+                    let value = match value {
+                        Value::String(s) => s,
+                        Value::Number(n) => n.to_string(),
+                        Value::Bool(b) => b.to_string(),
+                        _ => continue,
+                    };
+                    params.insert(key, value);
+                }
+            }
+            _ => params.extend(url::form_urlencoded::parse(body).into_owned()),
+        }
+
+        let field = |keys: &[&str]| {
+            keys.iter()
+                .find_map(|key| params.get(*key))
+                .filter(|v| !v.is_empty())
+                .cloned()
+        };
+        Self {
+            invoice_id: field(INVOICE_ID_PARAMS),
+            payment_id: field(PAYMENT_ID_PARAMS),
+            payment_status: field(&["payment_status"]),
+            payment_amount: field(&["payment_amount", "amount"]).and_then(|a| a.parse().ok()),
+            next_payment_date: field(&["next_payment_date"]),
+            next_payment_datetime: field(&["next_payment_datetime"]),
+            params,
+        }
+    }
+
+    /// Whether the webhook reports a successful charge. A payload without a
+    /// status is taken as a charge notification.
+    pub fn is_paid(&self) -> bool {
+        self.payment_status
+            .as_deref()
+            .is_none_or(|status| status == PAYMENT_STATUS_PAID)
+    }
+
+    /// A payload field by name.
+    pub fn param(&self, name: &str) -> Option<&str> {
+        self.params.get(name).map(String::as_str)
+    }
+}
+
+#[cfg(feature = "chrono")]
+mod tracking {
+    use std::collections::{BTreeMap, HashMap, HashSet};
+    use std::time::Duration;
+
+    use chrono::{DateTime, Days, FixedOffset, Months, TimeDelta};
+    use futures::TryStreamExt;
+
+    use super::{IntervalUnit, SubscriptionInterval, SubscriptionWebhook};
+    use crate::client::QPayClient;
+    use crate::datetime::parse_datetime;
+    use crate::error::QPayError;
+    use crate::models::PaymentCheckRow;
+    use crate::polling::{invoice_check_request, PAYMENT_STATUS_PAID};
+    use crate::validation::ValidationError;
+
+    /// Default time after the expected charge before it counts as missed.
+    pub const DEFAULT_SUBSCRIPTION_GRACE: Duration = Duration::from_secs(24 * 60 * 60);
+
+    impl SubscriptionInterval {
+        /// The charge after one at `from`. Month and year steps keep the
+        /// day of month where possible (Jan 31 + 1M = Feb 28/29).
+        ///
+        /// Fails if the result is out of range.
+        pub fn next_after(
+            &self,
+            from: DateTime<FixedOffset>,
+        ) -> Result<DateTime<FixedOffset>, ValidationError> {
+            let next = match self.unit {
+                IntervalUnit::Day => from.checked_add_days(Days::new(self.count.into())),
+                IntervalUnit::Week => from.checked_add_days(Days::new(u64::from(self.count) * 7)),
+                IntervalUnit::Month => from.checked_add_months(Months::new(self.count)),
+                IntervalUnit::Year => self
+                    .count
+                    .checked_mul(12)
+                    .and_then(|months| from.checked_add_months(Months::new(months))),
+            };
+            next.ok_or_else(|| ValidationError::SubscriptionInterval(self.to_string()))
+        }
+    }
+
+    impl SubscriptionWebhook {
+        /// Next scheduled charge reported by QPay, if any.
+        pub fn next_payment_parsed(&self) -> Option<DateTime<FixedOffset>> {
+            self.next_payment_datetime
+                .as_deref()
+                .and_then(parse_datetime)
+                .or_else(|| self.next_payment_date.as_deref().and_then(parse_datetime))
+        }
+    }
+
+    /// A tracked subscription invoice.
+    #[derive(Debug, Clone, PartialEq)]
+    pub struct Subscription {
+        pub invoice_id: String,
+        pub interval: SubscriptionInterval,
+        /// When the next charge is expected.
+        pub next_charge: DateTime<FixedOffset>,
+        /// The most recently recorded charge.
+        pub last_payment_id: Option<String>,
+        /// Number of charges recorded since tracking started.
+        pub charges: u32,
+    }
+
+    /// A charge that is overdue by more than the grace period.
+    #[derive(Debug, Clone, PartialEq)]
+    pub struct MissedPayment {
+        pub invoice_id: String,
+        pub expected_at: DateTime<FixedOffset>,
+        pub overdue_by: TimeDelta,
+    }
+
+    /// State of a subscription at a point in time.
+    #[derive(Debug, Clone, PartialEq)]
+    pub enum SubscriptionStatus {
+        /// The next charge is not yet overdue.
+        Active { next_charge: DateTime<FixedOffset> },
+        /// The expected charge was not recorded in time.
+        Missed(MissedPayment),
+    }
+
+    /// Tracks the next expected charge of each subscription invoice.
+    ///
+    /// Charges are recorded from webhooks or `check_payment` rows; each
+    /// payment id is counted once per invoice, so both sources can be fed the same
+    /// charge. When a charge carries `next_payment_date`, that date is used;
+    /// otherwise the next charge is one interval after the expected one.
+    #[derive(Debug, Clone)]
+    pub struct SubscriptionTracker {
+        grace: TimeDelta,
+        subscriptions: BTreeMap<String, Subscription>,
+        /// Payment ids recorded for each tracked invoice.
+        seen: HashMap<String, HashSet<String>>,
+    }
+
+    impl Default for SubscriptionTracker {
+        fn default() -> Self {
+            Self::new(DEFAULT_SUBSCRIPTION_GRACE)
+        }
+    }
+
+    impl SubscriptionTracker {
+        /// Create a tracker that reports charges as missed `grace` after
+        /// they were expected.
+        pub fn new(grace: Duration) -> Self {
+            Self {
+                grace: TimeDelta::from_std(grace).unwrap_or(TimeDelta::MAX),
+                subscriptions: BTreeMap::new(),
+                seen: HashMap::new(),
+            }
+        }
+
+        /// Start tracking an invoice whose first (or next) charge is
+        /// expected at `next_charge`, replacing any previous state.
+        pub fn track(
+            &mut self,
+            invoice_id: impl Into<String>,
+            interval: SubscriptionInterval,
+            next_charge: DateTime<FixedOffset>,
+        ) {
+            let invoice_id = invoice_id.into();
+            self.seen.remove(&invoice_id);
+            self.subscriptions.insert(
+                invoice_id.clone(),
+                Subscription {
+                    invoice_id,
+                    interval,
+                    next_charge,
+                    last_payment_id: None,
+                    charges: 0,
+                },
+            );
+        }
+
+        /// Stop tracking an invoice.
+        pub fn untrack(&mut self, invoice_id: &str) -> Option<Subscription> {
+            self.seen.remove(invoice_id);
+            self.subscriptions.remove(invoice_id)
+        }
+
+        /// A tracked subscription by invoice id.
+        pub fn get(&self, invoice_id: &str) -> Option<&Subscription> {
+            self.subscriptions.get(invoice_id)
+        }
+
+        /// All tracked subscriptions, ordered by invoice id.
+        pub fn subscriptions(&self) -> impl Iterator<Item = &Subscription> {
+            self.subscriptions.values()
+        }
+
+        /// Record a charge. Returns `false` if the invoice is not tracked or
+        /// the payment was already recorded.
+        pub fn record_payment(
+            &mut self,
+            invoice_id: &str,
+            payment_id: &str,
+            next_payment: Option<DateTime<FixedOffset>>,
+        ) -> bool {
+            let Some(sub) = self.subscriptions.get_mut(invoice_id) else {
+                return false;
+            };
+            let seen = self.seen.entry(invoice_id.to_string()).or_default();
+            if !seen.insert(payment_id.to_string()) {
+                return false;
+            }
+            let next = next_payment.or_else(|| sub.interval.next_after(sub.next_charge).ok());
+            if let Some(next) = next.filter(|next| *next > sub.next_charge) {
+                sub.next_charge = next;
+            }
+            sub.last_payment_id = Some(payment_id.to_string());
+            sub.charges += 1;
+            true
+        }
+
+        /// Record the charge reported by a webhook, if it is a paid charge
+        /// for a tracked invoice.
+        pub fn record_webhook(&mut self, webhook: &SubscriptionWebhook) -> bool {
+            let (Some(invoice_id), Some(payment_id)) = (&webhook.invoice_id, &webhook.payment_id)
+            else {
+                return false;
+            };
+            webhook.is_paid()
+                && self.record_payment(invoice_id, payment_id, webhook.next_payment_parsed())
+        }
+
+        /// Record the paid rows returned by `check_payment` for an invoice.
+        /// Returns the number of new charges.
+        ///
+        /// `check_payment` lists every charge of the invoice, including the
+        /// first payment and those before tracking started, so only rows
+        /// paid within the grace period before the expected charge or later
+        /// are counted, oldest first. A row without `payment_date` counts
+        /// only if it moves `next_payment_date` past the expected charge.
+        pub fn record_rows(&mut self, invoice_id: &str, rows: &[PaymentCheckRow]) -> usize {
+            let mut paid: Vec<_> = rows
+                .iter()
+                .filter(|row| row.payment_status == PAYMENT_STATUS_PAID)
+                .collect();
+            paid.sort_by_key(|row| row.payment_date_parsed());
+            paid.dedup_by(|a, b| a.payment_id == b.payment_id);
+
+            let mut recorded = 0;
+            for row in paid {
+                let Some(sub) = self.subscriptions.get(invoice_id) else {
+                    break;
+                };
+                let next_payment = row.next_payment_parsed();
+                let current = match row.payment_date_parsed() {
+                    Some(paid_at) => sub
+                        .next_charge
+                        .checked_sub_signed(self.grace)
+                        .is_none_or(|window_start| paid_at >= window_start),
+                    None => next_payment.is_some_and(|next| next > sub.next_charge),
+                };
+                if current && self.record_payment(invoice_id, &row.payment_id, next_payment) {
+                    recorded += 1;
+                }
+            }
+            recorded
+        }
+
+        /// Status of a tracked invoice at `now`.
+        pub fn status(
+            &self,
+            invoice_id: &str,
+            now: DateTime<FixedOffset>,
+        ) -> Option<SubscriptionStatus> {
+            let sub = self.subscriptions.get(invoice_id)?;
+            Some(match self.missed_payment(sub, now) {
+                Some(missed) => SubscriptionStatus::Missed(missed),
+                None => SubscriptionStatus::Active {
+                    next_charge: sub.next_charge,
+                },
+            })
+        }
+
+        /// Subscriptions whose expected charge is overdue at `now`, based
+        /// on the charges recorded so far.
+        pub fn overdue(&self, now: DateTime<FixedOffset>) -> Vec<MissedPayment> {
+            self.subscriptions
+                .values()
+                .filter_map(|sub| self.missed_payment(sub, now))
+                .collect()
+        }
+
+        fn missed_payment(
+            &self,
+            sub: &Subscription,
+            now: DateTime<FixedOffset>,
+        ) -> Option<MissedPayment> {
+            let deadline = sub.next_charge.checked_add_signed(self.grace)?;
+            (now > deadline).then(|| MissedPayment {
+                invoice_id: sub.invoice_id.clone(),
+                expected_at: sub.next_charge,
+                overdue_by: now - sub.next_charge,
+            })
+        }
+    }
+
+    impl QPayClient {
+        /// Record every charge `check_payment` reports for a tracked invoice
+        /// and return its status at `now`. Returns `None` if the invoice is
+        /// not tracked.
+        pub async fn check_subscription(
+            &self,
+            tracker: &mut SubscriptionTracker,
+            invoice_id: &str,
+            now: DateTime<FixedOffset>,
+        ) -> Result<Option<SubscriptionStatus>, QPayError> {
+            if tracker.get(invoice_id).is_none() {
+                return Ok(None);
+            }
+            let rows: Vec<PaymentCheckRow> = self
+                .check_payment_stream(&invoice_check_request(invoice_id), 0)
+                .try_collect()
+                .await?;
+            tracker.record_rows(invoice_id, &rows);
+            Ok(tracker.status(invoice_id, now))
+        }
+
+        /// Confirm overdue charges with `check_payment`.
+        ///
+        /// Only subscriptions that look overdue from the recorded charges
+        /// are checked, so a lost webhook is caught without polling every
+        /// subscription. Returns the charges that are still missing.
+        pub async fn missed_subscription_payments(
+            &self,
+            tracker: &mut SubscriptionTracker,
+            now: DateTime<FixedOffset>,
+        ) -> Result<Vec<MissedPayment>, QPayError> {
+            let mut missed = Vec::new();
+            for candidate in tracker.overdue(now) {
+                if let Some(SubscriptionStatus::Missed(payment)) = self
+                    .check_subscription(tracker, &candidate.invoice_id, now)
+                    .await?
+                {
+                    missed.push(payment);
+                }
+            }
+            Ok(missed)
+        }
+    }
+}
@@ -0,0 +1,398 @@
+//! VAT and city tax for ebarimt invoices.
+//!
+//! Ebarimt prices include tax. For each line the ebarimt system takes the
+//! VAT (НӨАТ, 10%) and the city tax (ХНАТ, 1%, only on goods and services
+//! subject to it) out of the line total, rounds each to two decimals, and
+//! sums the rounded line amounts for the receipt. [`EbarimtTaxCalculator`]
+//! follows the same rules, so its lines can be sent with
+//! `create_ebarimt_invoice` and its [`TaxSummary`] compared with the
+//! resulting [`EbarimtResponse`].
+
+use std::fmt;
+use std::str::FromStr;
+
+use crate::callback::AMOUNT_TOLERANCE;
+use crate::models::{CreateEbarimtInvoiceRequest, EbarimtInvoiceLine, EbarimtResponse, TaxEntry};
+use crate::validation::ValidationError;
+
+/// VAT rate.
+pub const VAT_RATE: f64 = 0.10;
+
+/// City tax rate.
+pub const CITY_TAX_RATE: f64 = 0.01;
+
+/// `tax_code` of the VAT entry in `EbarimtInvoiceLine.taxes`.
+pub const TAX_CODE_VAT: &str = "VAT";
+
+/// `tax_code` of the city tax entry in `EbarimtInvoiceLine.taxes`.
+pub const TAX_CODE_CITY_TAX: &str = "CITY_TAX";
+
+/// Decimal places of `line_quantity`.
+const QUANTITY_DECIMALS: usize = 3;
+
+/// Decimal places of `line_unit_price`.
+const PRICE_DECIMALS: usize = 2;
+
+/// The `tax_type` of an ebarimt invoice.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum EbarimtTaxType {
+    /// Subject to VAT (code `1`).
+    VatAble,
+    /// Exempt from VAT (code `2`).
+    VatFree,
+    /// VAT at a zero rate (code `3`).
+    VatZero,
+    /// Not subject to Mongolian VAT, e.g. sold abroad (code `5`).
+    NoVat,
+}
+
+impl EbarimtTaxType {
+    /// The code sent as `tax_type`.
+    pub fn code(self) -> &'static str {
+        match self {
+            Self::VatAble => "1",
+            Self::VatFree => "2",
+            Self::VatZero => "3",
+            Self::NoVat => "5",
+        }
+    }
+
+    /// Parse a `tax_type` code or name, e.g. `1` or `VAT_ABLE`.
+    pub fn parse(value: &str) -> Result<Self, ValidationError> {
+        match value.trim().to_ascii_uppercase().as_str() {
+            "1" | "VAT_ABLE" => Ok(Self::VatAble),
+            "2" | "VAT_FREE" => Ok(Self::VatFree),
+            "3" | "VAT_ZERO" => Ok(Self::VatZero),
+            "5" | "NO_VAT" | "NOT_VAT" => Ok(Self::NoVat),
+            _ => Err(ValidationError::TaxType(value.to_string())),
+        }
+    }
+
+    /// Whether VAT is charged.
+    pub fn charges_vat(self) -> bool {
+        self == Self::VatAble
+    }
+
+    /// Whether city tax can be charged. Like VAT, it is only charged on
+    /// goods and services subject to VAT.
+    pub fn charges_city_tax(self) -> bool {
+        self == Self::VatAble
+    }
+}
+
+impl fmt::Display for EbarimtTaxType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.code())
+    }
+}
+
+impl FromStr for EbarimtTaxType {
+    type Err = ValidationError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Self::parse(s)
+    }
+}
+
+/// Round to two decimals, halves away from zero, as the ebarimt system does.
+pub fn round_amount(amount: f64) -> f64 {
+    round_to(amount, 2)
+}
+
+fn round_to(value: f64, decimals: usize) -> f64 {
+    let factor = 10f64.powi(decimals as i32);
+    let scaled = value * factor;
+    // Absorb representation error such as 1.005 * 100 = 100.49999...
+    (scaled + scaled.signum() * 1e-7).round() / factor
+}
+
+/// `value`, already rounded to `decimals` places, with no trailing zeros,
+/// e.g. `0.3` and `2.0` as `2`.
+fn format_decimal(value: f64, decimals: usize) -> String {
+    let formatted = format!("{:.*}", decimals, value);
+    let trimmed = if formatted.contains('.') {
+        formatted.trim_end_matches('0').trim_end_matches('.')
+    } else {
+        &formatted
+    };
+    match trimmed {
+        "-0" => "0".to_string(),
+        value => value.to_string(),
+    }
+}
+
+/// An invoice line before tax is worked out. Prices include tax.
+#[derive(Debug, Clone, PartialEq)]
+pub struct TaxableLine {
+    pub description: String,
+    pub quantity: f64,
+    pub unit_price: f64,
+    pub tax_product_code: Option<String>,
+    pub barcode: Option<String>,
+    pub classification_code: Option<String>,
+    pub note: Option<String>,
+    /// Whether the line is subject to city tax.
+    pub city_tax: bool,
+}
+
+impl TaxableLine {
+    pub fn new(description: impl Into<String>, quantity: f64, unit_price: f64) -> Self {
+        Self {
+            description: description.into(),
+            quantity,
+            unit_price,
+            tax_product_code: None,
+            barcode: None,
+            classification_code: None,
+            note: None,
+            city_tax: false,
+        }
+    }
+
+    pub fn with_tax_product_code(mut self, code: impl Into<String>) -> Self {
+        self.tax_product_code = Some(code.into());
+        self
+    }
+
+    pub fn with_barcode(mut self, barcode: impl Into<String>) -> Self {
+        self.barcode = Some(barcode.into());
+        self
+    }
+
+    pub fn with_classification_code(mut self, code: impl Into<String>) -> Self {
+        self.classification_code = Some(code.into());
+        self
+    }
+
+    pub fn with_note(mut self, note: impl Into<String>) -> Self {
+        self.note = Some(note.into());
+        self
+    }
+
+    /// Mark the line as subject to city tax.
+    pub fn with_city_tax(mut self) -> Self {
+        self.city_tax = true;
+        self
+    }
+
+    /// `quantity` rounded to the precision of `line_quantity`.
+    pub fn sent_quantity(&self) -> f64 {
+        round_to(self.quantity, QUANTITY_DECIMALS)
+    }
+
+    /// `unit_price` rounded to the precision of `line_unit_price`.
+    pub fn sent_unit_price(&self) -> f64 {
+        round_to(self.unit_price, PRICE_DECIMALS)
+    }
+}
+
+/// Tax of one line, rounded to two decimals.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct LineTax {
+    /// Line total including tax.
+    pub amount: f64,
+    /// Line total without VAT and city tax.
+    pub net_amount: f64,
+    pub vat: f64,
+    pub city_tax: f64,
+}
+
+/// Expected totals of an ebarimt invoice.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct TaxSummary {
+    pub amount: f64,
+    pub net_amount: f64,
+    pub vat_amount: f64,
+    pub city_tax_amount: f64,
+    /// Per-line breakdown, in line order.
+    pub lines: Vec<LineTax>,
+}
+
+impl TaxSummary {
+    /// Differences between these totals and a receipt's `amount`,
+    /// `vat_amount` and `city_tax_amount`; empty if they agree.
+    pub fn mismatches(&self, resp: &EbarimtResponse) -> Vec<TaxMismatch> {
+        [
+            ("amount", self.amount, &resp.amount),
+            ("vat_amount", self.vat_amount, &resp.vat_amount),
+            (
+                "city_tax_amount",
+                self.city_tax_amount,
+                &resp.city_tax_amount,
+            ),
+        ]
+        .into_iter()
+        .filter_map(|(field, expected, actual)| {
+            // Receipts without city tax may leave the field empty
+            let parsed = match actual.trim() {
+                "" => Some(0.0),
+                value => value.parse::<f64>().ok(),
+            };
+            match parsed {
+                Some(value) if (value - expected).abs() < AMOUNT_TOLERANCE => None,
+                _ => Some(TaxMismatch {
+                    field,
+                    expected,
+                    actual: actual.clone(),
+                }),
+            }
+        })
+        .collect()
+    }
+
+    /// Whether a receipt's totals agree with these.
+    pub fn matches(&self, resp: &EbarimtResponse) -> bool {
+        self.mismatches(resp).is_empty()
+    }
+}
+
+/// A receipt total that differs from the calculated one.
+#[derive(Debug, Clone, PartialEq)]
+pub struct TaxMismatch {
+    /// The [`EbarimtResponse`] field.
+    pub field: &'static str,
+    pub expected: f64,
+    /// The value as returned, which may not be a number.
+    pub actual: String,
+}
+
+impl fmt::Display for TaxMismatch {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{}: expected {:.2}, got {:?}",
+            self.field, self.expected, self.actual
+        )
+    }
+}
+
+/// Works out VAT and city tax for the lines of an ebarimt invoice.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct EbarimtTaxCalculator {
+    tax_type: EbarimtTaxType,
+    vat_rate: f64,
+    city_tax_rate: f64,
+}
+
+impl EbarimtTaxCalculator {
+    /// A calculator for `tax_type` at [`VAT_RATE`] and [`CITY_TAX_RATE`].
+    pub fn new(tax_type: EbarimtTaxType) -> Self {
+        Self {
+            tax_type,
+            vat_rate: VAT_RATE,
+            city_tax_rate: CITY_TAX_RATE,
+        }
+    }
+
+    /// Use a different VAT rate, e.g. `0.10` for 10%.
+    pub fn with_vat_rate(mut self, rate: f64) -> Self {
+        self.vat_rate = rate;
+        self
+    }
+
+    /// Use a different city tax rate, e.g. `0.02` for 2%.
+    pub fn with_city_tax_rate(mut self, rate: f64) -> Self {
+        self.city_tax_rate = rate;
+        self
+    }
+
+    pub fn tax_type(&self) -> EbarimtTaxType {
+        self.tax_type
+    }
+
+    /// Tax of one line.
+    ///
+    /// The total is worked out from the quantity and unit price as they are
+    /// sent (see [`TaxableLine::sent_quantity`]), so it matches what ebarimt
+    /// computes from the invoice line. VAT and city tax are taken out of the
+    /// rounded line total in proportion to their rates and rounded
+    /// separately; the net amount is what remains, so the three always add
+    /// up to the total.
+    pub fn line_tax(&self, line: &TaxableLine) -> LineTax {
+        let amount = round_amount(line.sent_quantity() * line.sent_unit_price());
+        let vat_rate = if self.tax_type.charges_vat() {
+            self.vat_rate
+        } else {
+            0.0
+        };
+        let city_tax_rate = if line.city_tax && self.tax_type.charges_city_tax() {
+            self.city_tax_rate
+        } else {
+            0.0
+        };
+
+        let base = amount / (1.0 + vat_rate + city_tax_rate);
+        let vat = round_amount(base * vat_rate);
+        let city_tax = round_amount(base * city_tax_rate);
+        LineTax {
+            amount,
+            net_amount: round_amount(amount - vat - city_tax),
+            vat,
+            city_tax,
+        }
+    }
+
+    /// Totals of `lines`, summed from the rounded line amounts.
+    pub fn summary(&self, lines: &[TaxableLine]) -> TaxSummary {
+        let lines: Vec<_> = lines.iter().map(|line| self.line_tax(line)).collect();
+        let sum = |f: fn(&LineTax) -> f64| round_amount(lines.iter().map(f).sum());
+        TaxSummary {
+            amount: sum(|l| l.amount),
+            net_amount: sum(|l| l.net_amount),
+            vat_amount: sum(|l| l.vat),
+            city_tax_amount: sum(|l| l.city_tax),
+            lines,
+        }
+    }
+
+    /// The invoice line for `line`, with its VAT and city tax in `taxes`.
+    pub fn invoice_line(&self, line: &TaxableLine) -> EbarimtInvoiceLine {
+        let tax = self.line_tax(line);
+        let mut taxes = Vec::new();
+        if tax.vat > 0.0 {
+            taxes.push(tax_entry(TAX_CODE_VAT, "НӨАТ", tax.vat));
+        }
+        if tax.city_tax > 0.0 {
+            taxes.push(tax_entry(TAX_CODE_CITY_TAX, "ХНАТ", tax.city_tax));
+        }
+
+        EbarimtInvoiceLine {
+            tax_product_code: line.tax_product_code.clone(),
+            line_description: line.description.clone(),
+            barcode: line.barcode.clone(),
+            line_quantity: format_decimal(line.sent_quantity(), QUANTITY_DECIMALS),
+            line_unit_price: format_decimal(line.sent_unit_price(), PRICE_DECIMALS),
+            note: line.note.clone(),
+            classification_code: line.classification_code.clone(),
+            taxes: (!taxes.is_empty()).then_some(taxes),
+        }
+    }
+
+    /// Invoice lines for `lines`, in order.
+    pub fn invoice_lines(&self, lines: &[TaxableLine]) -> Vec<EbarimtInvoiceLine> {
+        lines.iter().map(|line| self.invoice_line(line)).collect()
+    }
+
+    /// Set the `tax_type` and `lines` of `req` and return the totals the
+    /// receipt should show.
+    pub fn apply(
+        &self,
+        req: &mut CreateEbarimtInvoiceRequest,
+        lines: &[TaxableLine],
+    ) -> TaxSummary {
+        req.tax_type = self.tax_type.code().to_string();
+        req.lines = self.invoice_lines(lines);
+        self.summary(lines)
+    }
+}
+
+fn tax_entry(code: &str, description: &str, amount: f64) -> TaxEntry {
+    TaxEntry {
+        tax_code: Some(code.to_string()),
+        discount_code: None,
+        surcharge_code: None,
+        description: description.to_string(),
+        amount,
+        note: None,
+    }
+}
@@ -0,0 +1,494 @@
+//! Client-side validation of request data before it is sent to QPay.
+
+use std::fmt;
+use std::str::FromStr;
+
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+use crate::models::{
+    Account, CreateEbarimtRequest, CreateInvoiceRequest, InvoiceReceiverData, SenderBranchData,
+    Transaction,
+};
+use crate::subscription::SubscriptionInterval;
+
+/// Length of a Mongolian IBAN (`MNkk bbbb cccc cccc cccc`).
+pub const MN_IBAN_LENGTH: usize = 20;
+
+/// Validation failure for request data.
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+pub enum ValidationError {
+    #[error("iban must be {expected} characters, got {actual}")]
+    IbanLength { expected: usize, actual: usize },
+
+    #[error("iban country code must be MN, got {0}")]
+    IbanCountry(String),
+
+    #[error("iban contains invalid characters: {0}")]
+    IbanFormat(String),
+
+    #[error("iban check digits are invalid: {0}")]
+    IbanChecksum(String),
+
+    #[error("bank code must be six digits ending in 0000, got {0:?}")]
+    BankCodeFormat(String),
+
+    #[error("account number must be 6 to 16 digits, got {0:?}")]
+    AccountNumberFormat(String),
+
+    #[error(
+        "bank code {iban_bank_code} in iban does not match account_bank_code {account_bank_code}"
+    )]
+    BankCodeMismatch {
+        iban_bank_code: String,
+        account_bank_code: String,
+    },
+
+    #[error(
+        "account number {iban_account} in iban does not match account_number {account_number}"
+    )]
+    AccountNumberMismatch {
+        iban_account: String,
+        account_number: String,
+    },
+
+    #[error("register number must be two Cyrillic letters and eight digits, a seven-digit company register or an 11 to 14 digit TIN, got {0:?}")]
+    RegisterNumberFormat(String),
+
+    #[error("expected a {expected} register number, got {value:?}")]
+    RegisterNumberKind { expected: String, value: String },
+
+    #[error("subscription interval must be a count and a D, W, M or Y unit, got {0:?}")]
+    SubscriptionInterval(String),
+
+    #[error("ebarimt tax type must be 1, 2, 3 or 5, got {0:?}")]
+    TaxType(String),
+}
+
+/// The parts of a validated Mongolian IBAN.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MongolianIban {
+    /// ISO 13616 check digits.
+    pub check_digits: String,
+    /// Four-digit bank identifier (e.g. `"0005"` for Khan Bank).
+    pub bank_id: String,
+    /// Twelve-digit, zero-padded account number.
+    pub account: String,
+}
+
+impl MongolianIban {
+    /// The QPay six-digit bank code for this IBAN's bank (e.g. `"050000"`).
+    pub fn account_bank_code(&self) -> String {
+        format!("{}0000", &self.bank_id[2..])
+    }
+
+    /// The account number without leading zero padding.
+    pub fn account_number(&self) -> &str {
+        self.account.trim_start_matches('0')
+    }
+}
+
+impl fmt::Display for MongolianIban {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "MN{}{}{}", self.check_digits, self.bank_id, self.account)
+    }
+}
+
+/// Parse and validate a Mongolian IBAN, including its ISO 13616 check digits.
+///
+/// Spaces are ignored and letters are accepted in either case.
+pub fn validate_iban(iban: &str) -> Result<MongolianIban, ValidationError> {
+    let iban: String = iban
+        .chars()
+        .filter(|c| !c.is_whitespace())
+        .collect::<String>()
+        .to_ascii_uppercase();
+
+    if iban.len() != MN_IBAN_LENGTH || !iban.is_ascii() {
+        return Err(ValidationError::IbanLength {
+            expected: MN_IBAN_LENGTH,
+            actual: iban.chars().count(),
+        });
+    }
+    if &iban[..2] != "MN" {
+        return Err(ValidationError::IbanCountry(iban[..2].to_string()));
+    }
+    if !iban[2..].bytes().all(|b| b.is_ascii_digit()) {
+        return Err(ValidationError::IbanFormat(iban));
+    }
+    if iban_mod97(&iban) != 1 {
+        return Err(ValidationError::IbanChecksum(iban));
+    }
+
+    Ok(MongolianIban {
+        check_digits: iban[2..4].to_string(),
+        bank_id: iban[4..8].to_string(),
+        account: iban[8..].to_string(),
+    })
+}
+
+/// Build a Mongolian IBAN from a six-digit QPay bank code and an account number.
+pub fn build_iban(
+    account_bank_code: &str,
+    account_number: &str,
+) -> Result<MongolianIban, ValidationError> {
+    validate_account_number(account_number)?;
+    let bank_id = iban_bank_id(account_bank_code)
+        .ok_or_else(|| ValidationError::BankCodeFormat(account_bank_code.to_string()))?;
+    let account = format!("{:0>12}", account_number);
+    if account.len() != 12 {
+        return Err(ValidationError::AccountNumberFormat(
+            account_number.to_string(),
+        ));
+    }
+
+    let check = 98 - iban_mod97(&format!("MN00{}{}", bank_id, account));
+    Ok(MongolianIban {
+        check_digits: format!("{:02}", check),
+        bank_id,
+        account,
+    })
+}
+
+/// Check that an account number is 6 to 16 digits.
+pub fn validate_account_number(account_number: &str) -> Result<(), ValidationError> {
+    let valid = (6..=16).contains(&account_number.len())
+        && account_number.bytes().all(|b| b.is_ascii_digit());
+    if valid {
+        Ok(())
+    } else {
+        Err(ValidationError::AccountNumberFormat(
+            account_number.to_string(),
+        ))
+    }
+}
+
+/// Map a six-digit QPay bank code (`"050000"`) to the four-digit IBAN bank id (`"0005"`).
+fn iban_bank_id(account_bank_code: &str) -> Option<String> {
+    let code = account_bank_code.trim();
+    let valid =
+        code.len() == 6 && code.bytes().all(|b| b.is_ascii_digit()) && code.ends_with("0000");
+    valid.then(|| format!("00{}", &code[..2]))
+}
+
+/// ISO 13616 / ISO 7064 mod-97 of an IBAN made of ASCII letters and digits.
+fn iban_mod97(iban: &str) -> u32 {
+    let rearranged = iban[4..].chars().chain(iban[..4].chars());
+    rearranged.fold(0u32, |acc, c| {
+        let value = c.to_digit(36).unwrap_or(0);
+        if value >= 10 {
+            (acc * 100 + value) % 97
+        } else {
+            (acc * 10 + value) % 97
+        }
+    })
+}
+
+/// A citizen registry number (регистр) or company register / TIN.
+///
+/// Parsing accepts Latin letter prefixes (`"UA89010112"` becomes
+/// `"УА89010112"`) and serializes as the normalized string.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum RegisterNumber {
+    /// Citizen registry number: two Cyrillic letters and eight digits.
+    Citizen(String),
+    /// Seven-digit company register number.
+    Company(String),
+    /// 11 to 14 digit taxpayer identification number.
+    Tin(String),
+}
+
+impl RegisterNumber {
+    /// Parse and normalize a register number or TIN.
+    pub fn parse(value: &str) -> Result<Self, ValidationError> {
+        let invalid = || ValidationError::RegisterNumberFormat(value.to_string());
+        let compact: String = value
+            .chars()
+            .filter(|c| !c.is_whitespace() && *c != '-')
+            .collect();
+
+        if compact.chars().all(|c| c.is_ascii_digit()) {
+            return match compact.len() {
+                7 => Ok(Self::Company(compact)),
+                11..=14 => Ok(Self::Tin(compact)),
+                _ => Err(invalid()),
+            };
+        }
+
+        let split = compact
+            .find(|c: char| c.is_ascii_digit())
+            .ok_or_else(invalid)?;
+        let (prefix, digits) = compact.split_at(split);
+        if digits.len() != 8 || !digits.bytes().all(|b| b.is_ascii_digit()) {
+            return Err(invalid());
+        }
+        let letters = register_letters(prefix).ok_or_else(invalid)?;
+
+        // The first six digits are the birth date as YYMMDD, with 20 added
+        // to the month for people born in 2000 or later.
+        let month: u32 = digits[2..4].parse().map_err(|_| invalid())?;
+        let day: u32 = digits[4..6].parse().map_err(|_| invalid())?;
+        if !matches!(month, 1..=12 | 21..=32) || !(1..=31).contains(&day) {
+            return Err(invalid());
+        }
+
+        Ok(Self::Citizen(format!("{}{}", letters, digits)))
+    }
+
+    /// The normalized register number.
+    pub fn as_str(&self) -> &str {
+        match self {
+            Self::Citizen(s) | Self::Company(s) | Self::Tin(s) => s,
+        }
+    }
+
+    /// Whether this is a citizen registry number.
+    pub fn is_citizen(&self) -> bool {
+        matches!(self, Self::Citizen(_))
+    }
+
+    /// Whether this identifies a company (register number or TIN).
+    pub fn is_company(&self) -> bool {
+        matches!(self, Self::Company(_) | Self::Tin(_))
+    }
+}
+
+impl fmt::Display for RegisterNumber {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl FromStr for RegisterNumber {
+    type Err = ValidationError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Self::parse(s)
+    }
+}
+
+impl From<RegisterNumber> for String {
+    fn from(register: RegisterNumber) -> Self {
+        match register {
+            RegisterNumber::Citizen(s) | RegisterNumber::Company(s) | RegisterNumber::Tin(s) => s,
+        }
+    }
+}
+
+impl Serialize for RegisterNumber {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(self.as_str())
+    }
+}
+
+impl<'de> Deserialize<'de> for RegisterNumber {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let s = String::deserialize(deserializer)?;
+        Self::parse(&s).map_err(serde::de::Error::custom)
+    }
+}
+
+/// Parse and normalize a register number or TIN.
+pub fn validate_register_number(value: &str) -> Result<RegisterNumber, ValidationError> {
+    RegisterNumber::parse(value)
+}
+
+const MONGOLIAN_LETTERS: &str = "АБВГДЕЁЖЗИЙКЛМНОӨПРСТУҮФХЦЧШЩЪЫЬЭЮЯ";
+
+const LATIN_DIGRAPHS: &[(&str, char)] = &[
+    ("KH", 'Х'),
+    ("CH", 'Ч'),
+    ("SH", 'Ш'),
+    ("TS", 'Ц'),
+    ("YA", 'Я'),
+    ("YO", 'Ё'),
+    ("YU", 'Ю'),
+    ("YE", 'Е'),
+    ("OE", 'Ө'),
+    ("UE", 'Ү'),
+];
+
+fn latin_letter(c: char) -> Option<char> {
+    Some(match c {
+        'A' => 'А',
+        'B' => 'Б',
+        'V' | 'W' => 'В',
+        'G' => 'Г',
+        'D' => 'Д',
+        'E' => 'Э',
+        'J' => 'Ж',
+        'Z' => 'З',
+        'I' => 'И',
+        'K' => 'К',
+        'L' => 'Л',
+        'M' => 'М',
+        'N' => 'Н',
+        'O' => 'О',
+        'Ö' | 'Q' => 'Ө',
+        'P' => 'П',
+        'R' => 'Р',
+        'S' => 'С',
+        'T' => 'Т',
+        'U' => 'У',
+        'Ü' | 'Y' => 'Ү',
+        'F' => 'Ф',
+        'H' | 'X' => 'Х',
+        'C' => 'Ц',
+        _ => return None,
+    })
+}
+
+/// Turn a register prefix into two uppercase Mongolian Cyrillic letters.
+///
+/// Latin input is transliterated one letter at a time when it is two letters
+/// long, and with digraphs (`KH`, `CH`, `SH`, ...) otherwise, so `"YO"` is
+/// `"ҮО"` while `"YOKH"` is `"ЁХ"`.
+fn register_letters(prefix: &str) -> Option<String> {
+    let upper = prefix.to_uppercase();
+    let letters: String = if upper.chars().all(|c| MONGOLIAN_LETTERS.contains(c)) {
+        upper
+    } else if upper.chars().count() == 2 {
+        upper.chars().map(latin_letter).collect::<Option<_>>()?
+    } else {
+        let mut out = String::new();
+        let mut rest = upper.as_str();
+        while let Some(c) = rest.chars().next() {
+            if let Some((latin, cyrillic)) =
+                LATIN_DIGRAPHS.iter().find(|(d, _)| rest.starts_with(d))
+            {
+                out.push(*cyrillic);
+                rest = &rest[latin.len()..];
+            } else {
+                out.push(latin_letter(c)?);
+                rest = &rest[c.len_utf8()..];
+            }
+        }
+        out
+    };
+    (letters.chars().count() == 2).then_some(letters)
+}
+
+fn validate_optional_register(register: &Option<String>) -> Result<(), ValidationError> {
+    match register {
+        Some(value) => RegisterNumber::parse(value).map(|_| ()),
+        None => Ok(()),
+    }
+}
+
+impl SenderBranchData {
+    /// Set `register` to a normalized register number.
+    pub fn with_register(mut self, register: RegisterNumber) -> Self {
+        self.register = Some(register.into());
+        self
+    }
+
+    /// Validate `register`, if set.
+    pub fn validate(&self) -> Result<(), ValidationError> {
+        validate_optional_register(&self.register)
+    }
+}
+
+impl InvoiceReceiverData {
+    /// Set `register` to a normalized register number.
+    pub fn with_register(mut self, register: RegisterNumber) -> Self {
+        self.register = Some(register.into());
+        self
+    }
+
+    /// Validate `register`, if set.
+    pub fn validate(&self) -> Result<(), ValidationError> {
+        validate_optional_register(&self.register)
+    }
+}
+
+impl CreateEbarimtRequest {
+    /// Set `ebarimt_receiver` and a matching `ebarimt_receiver_type`
+    /// (`CITIZEN` or `COMPANY`).
+    pub fn with_ebarimt_receiver(mut self, receiver: RegisterNumber) -> Self {
+        self.ebarimt_receiver_type = if receiver.is_citizen() {
+            "CITIZEN".to_string()
+        } else {
+            "COMPANY".to_string()
+        };
+        self.ebarimt_receiver = Some(receiver.into());
+        self
+    }
+
+    /// Validate `ebarimt_receiver` against `ebarimt_receiver_type`.
+    ///
+    /// A `COMPANY` receiver must be a company register number or TIN. A
+    /// `CITIZEN` receiver is only checked when it starts with letters, since
+    /// QPay also accepts a phone number there.
+    pub fn validate(&self) -> Result<(), ValidationError> {
+        let Some(receiver) = &self.ebarimt_receiver else {
+            return Ok(());
+        };
+        match self.ebarimt_receiver_type.as_str() {
+            "COMPANY" if !RegisterNumber::parse(receiver)?.is_company() => {
+                return Err(ValidationError::RegisterNumberKind {
+                    expected: "company".to_string(),
+                    value: receiver.clone(),
+                });
+            }
+            "CITIZEN" if receiver.starts_with(|c: char| c.is_alphabetic()) => {
+                RegisterNumber::parse(receiver)?;
+            }
+            _ => {}
+        }
+        Ok(())
+    }
+}
+
+impl Account {
+    /// Validate the IBAN structure and check digits, and that the IBAN's
+    /// bank code and account number match `account_bank_code` and
+    /// `account_number`.
+    pub fn validate(&self) -> Result<(), ValidationError> {
+        validate_account_number(&self.account_number)?;
+        let iban = validate_iban(&self.iban_number)?;
+
+        if iban_bank_id(&self.account_bank_code).as_deref() != Some(iban.bank_id.as_str()) {
+            return Err(ValidationError::BankCodeMismatch {
+                iban_bank_code: iban.bank_id,
+                account_bank_code: self.account_bank_code.clone(),
+            });
+        }
+        if iban.account_number() != self.account_number.trim_start_matches('0') {
+            return Err(ValidationError::AccountNumberMismatch {
+                iban_account: iban.account,
+                account_number: self.account_number.clone(),
+            });
+        }
+        Ok(())
+    }
+}
+
+impl Transaction {
+    /// Validate every payout account of this transaction.
+    pub fn validate(&self) -> Result<(), ValidationError> {
+        self.accounts
+            .iter()
+            .flatten()
+            .try_for_each(Account::validate)
+    }
+}
+
+impl CreateInvoiceRequest {
+    /// Validate the request before calling `create_invoice`: register
+    /// numbers of the sender branch and receiver, the subscription
+    /// interval, and payout accounts.
+    pub fn validate(&self) -> Result<(), ValidationError> {
+        if let Some(branch) = &self.sender_branch_data {
+            branch.validate()?;
+        }
+        if let Some(receiver) = &self.invoice_receiver_data {
+            receiver.validate()?;
+        }
+        if let Some(interval) = &self.subscription_interval {
+            interval.parse::<SubscriptionInterval>()?;
+        }
+        self.transactions
+            .iter()
+            .flatten()
+            .try_for_each(Transaction::validate)
+    }
+}
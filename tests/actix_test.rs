@@ -0,0 +1,129 @@
+#![cfg(feature = "actix")]
+
+mod common;
+
+use std::sync::{Arc, Mutex};
+
+use actix_web::{test, App};
+use mockito::ServerGuard;
+use qpay::callback::*;
+use qpay::QPayClient;
+
+use common::{server_with_token, test_config};
+
+fn payment_json(status: &str, amount: &str, object_id: &str) -> String {
+    serde_json::json!({
+        "payment_id": "pay_1",
+        "payment_status": status,
+        "payment_fee": "0",
+        "payment_amount": amount,
+        "payment_currency": "MNT",
+        "payment_date": "2024-01-15 10:30:00",
+        "payment_wallet": "qPay",
+        "transaction_type": "P2P",
+        "object_type": "INVOICE",
+        "object_id": object_id,
+        "next_payment_date": null,
+        "next_payment_datetime": null
+    })
+    .to_string()
+}
+
+async fn mock_payment(server: &mut ServerGuard, body: String, hits: usize) -> mockito::Mock {
+    server
+        .mock("GET", "/v2/payment/pay_1")
+        .with_status(200)
+        .with_body(body)
+        .expect(hits)
+        .create_async()
+        .await
+}
+
+#[derive(Default)]
+struct Recorder {
+    payments: Mutex<Vec<String>>,
+    fail: bool,
+}
+
+impl CallbackHandler for Recorder {
+    async fn expected_payment(
+        &self,
+        event: &CallbackEvent,
+    ) -> Result<ExpectedPayment, HandlerError> {
+        match event.param("order") {
+            Some("ORD-1") => Ok(ExpectedPayment::invoice("inv_1", 5000.0)),
+            _ => Err("unknown order".into()),
+        }
+    }
+
+    async fn on_payment(&self, payment: VerifiedCallback) -> Result<(), HandlerError> {
+        if self.fail {
+            return Err("database unavailable".into());
+        }
+        self.payments.lock().unwrap().push(payment.payment_id);
+        Ok(())
+    }
+}
+
+fn processor(qpay_url: &str, handler: Recorder) -> Arc<CallbackProcessor<Recorder>> {
+    let client = Arc::new(QPayClient::new(test_config(qpay_url)));
+    Arc::new(CallbackProcessor::new(client, handler))
+}
+
+#[actix_web::test]
+async fn test_actix_callback_verified_and_deduplicated() {
+    let mut server = server_with_token().await;
+    let payment_mock = mock_payment(&mut server, payment_json("PAID", "5000", "inv_1"), 1).await;
+
+    let processor = processor(&server.url(), Recorder::default());
+    let app = test::init_service(App::new().configure(qpay::actix::callback_config(
+        "/qpay/callback",
+        processor.clone(),
+    )))
+    .await;
+
+    for _ in 0..2 {
+        let req = test::TestRequest::get()
+            .uri("/qpay/callback?order=ORD-1&payment_id=pay_1")
+            .to_request();
+        let resp = test::call_service(&app, req).await;
+        assert_eq!(resp.status(), 200);
+        let body = test::read_body(resp).await;
+        assert_eq!(body, CALLBACK_SUCCESS_BODY);
+    }
+
+    let req = test::TestRequest::post()
+        .uri("/qpay/callback?order=ORD-1")
+        .set_payload(r#"{"payment_id": "pay_1"}"#)
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), 200);
+
+    assert_eq!(*processor.handler().payments.lock().unwrap(), ["pay_1"]);
+    payment_mock.assert_async().await;
+}
+
+#[actix_web::test]
+async fn test_actix_callback_rejected_and_failed() {
+    let mut server = server_with_token().await;
+    mock_payment(&mut server, payment_json("PAID", "100", "inv_1"), 1).await;
+
+    let app = test::init_service(App::new().configure(qpay::actix::callback_config(
+        "/qpay/callback",
+        processor(&server.url(), Recorder::default()),
+    )))
+    .await;
+
+    let req = test::TestRequest::get()
+        .uri("/qpay/callback?order=ORD-1&payment_id=pay_1")
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), 400);
+
+    // Unknown order: expected_payment fails and QPay is asked to retry
+    let req = test::TestRequest::get()
+        .uri("/qpay/callback?order=ORD-X&payment_id=pay_2")
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), 500);
+}
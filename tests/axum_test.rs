@@ -0,0 +1,149 @@
+#![cfg(feature = "axum")]
+
+mod common;
+
+use std::sync::{Arc, Mutex};
+
+use mockito::ServerGuard;
+use qpay::callback::*;
+use qpay::QPayClient;
+
+use common::{server_with_token, test_config};
+
+fn payment_json(status: &str, amount: &str, object_id: &str) -> String {
+    serde_json::json!({
+        "payment_id": "pay_1",
+        "payment_status": status,
+        "payment_fee": "0",
+        "payment_amount": amount,
+        "payment_currency": "MNT",
+        "payment_date": "2024-01-15 10:30:00",
+        "payment_wallet": "qPay",
+        "transaction_type": "P2P",
+        "object_type": "INVOICE",
+        "object_id": object_id,
+        "next_payment_date": null,
+        "next_payment_datetime": null
+    })
+    .to_string()
+}
+
+async fn mock_payment(server: &mut ServerGuard, body: String, hits: usize) -> mockito::Mock {
+    server
+        .mock("GET", "/v2/payment/pay_1")
+        .with_status(200)
+        .with_body(body)
+        .expect(hits)
+        .create_async()
+        .await
+}
+
+#[derive(Default)]
+struct Recorder {
+    payments: Mutex<Vec<String>>,
+    fail: bool,
+}
+
+impl CallbackHandler for Recorder {
+    async fn expected_payment(
+        &self,
+        event: &CallbackEvent,
+    ) -> Result<ExpectedPayment, HandlerError> {
+        match event.param("order") {
+            Some("ORD-1") => Ok(ExpectedPayment::invoice("inv_1", 5000.0)),
+            _ => Err("unknown order".into()),
+        }
+    }
+
+    async fn on_payment(&self, payment: VerifiedCallback) -> Result<(), HandlerError> {
+        if self.fail {
+            return Err("database unavailable".into());
+        }
+        self.payments.lock().unwrap().push(payment.payment_id);
+        Ok(())
+    }
+}
+
+/// Serve the callback router on a local port and return its base URL.
+async fn serve(qpay_url: &str, handler: Recorder) -> (String, Arc<CallbackProcessor<Recorder>>) {
+    let client = Arc::new(QPayClient::new(test_config(qpay_url)));
+    let processor = Arc::new(CallbackProcessor::new(client, handler));
+    let app = qpay::axum::callback_router("/qpay/callback", processor.clone());
+
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
+    (format!("http://{}/qpay/callback", addr), processor)
+}
+
+#[tokio::test]
+async fn test_axum_callback_verified_and_deduplicated() {
+    let mut server = server_with_token().await;
+    let payment_mock = mock_payment(&mut server, payment_json("PAID", "5000", "inv_1"), 1).await;
+
+    let (url, processor) = serve(&server.url(), Recorder::default()).await;
+    let http = reqwest::Client::new();
+
+    for _ in 0..3 {
+        let resp = http
+            .get(format!("{}?order=ORD-1&payment_id=pay_1", url))
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(resp.status(), 200);
+        assert_eq!(resp.text().await.unwrap(), CALLBACK_SUCCESS_BODY);
+    }
+
+    // POST deliveries are handled the same way
+    let resp = http
+        .post(format!("{}?order=ORD-1", url))
+        .body(r#"{"payment_id": "pay_1"}"#)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), 200);
+
+    assert_eq!(*processor.handler().payments.lock().unwrap(), ["pay_1"]);
+    payment_mock.assert_async().await;
+}
+
+#[tokio::test]
+async fn test_axum_callback_rejected() {
+    let mut server = server_with_token().await;
+    mock_payment(&mut server, payment_json("PAID", "100", "inv_1"), 1).await;
+
+    let (url, processor) = serve(&server.url(), Recorder::default()).await;
+    let resp = reqwest::get(format!("{}?order=ORD-1&payment_id=pay_1", url))
+        .await
+        .unwrap();
+
+    assert_eq!(resp.status(), 400);
+    assert!(resp.text().await.unwrap().contains("does not match"));
+    assert!(processor.handler().payments.lock().unwrap().is_empty());
+}
+
+#[tokio::test]
+async fn test_axum_callback_handler_failure_is_retried() {
+    let mut server = server_with_token().await;
+    let payment_mock = mock_payment(&mut server, payment_json("PAID", "5000", "inv_1"), 2).await;
+
+    let handler = Recorder {
+        fail: true,
+        ..Default::default()
+    };
+    let (url, _) = serve(&server.url(), handler).await;
+    let target = format!("{}?order=ORD-1&payment_id=pay_1", url);
+
+    // A failed delivery is not remembered, so QPay's retry runs again
+    for _ in 0..2 {
+        let resp = reqwest::get(&target).await.unwrap();
+        assert_eq!(resp.status(), 500);
+    }
+
+    // Unknown order: expected_payment fails
+    let resp = reqwest::get(format!("{}?order=ORD-X&payment_id=pay_1", url))
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), 500);
+    payment_mock.assert_async().await;
+}
@@ -0,0 +1,253 @@
+mod common;
+
+use mockito::{Matcher, Server};
+use qpay::models::*;
+use qpay::{QPayClient, QPayError};
+
+use common::{future_timestamp, test_config, token_json};
+
+fn invoice_request(sender_invoice_no: &str) -> CreateInvoiceRequest {
+    CreateInvoiceRequest {
+        invoice_code: "CODE".to_string(),
+        sender_invoice_no: sender_invoice_no.to_string(),
+        sender_branch_code: None,
+        sender_branch_data: None,
+        sender_staff_data: None,
+        sender_staff_code: None,
+        invoice_receiver_code: "terminal".to_string(),
+        invoice_receiver_data: None,
+        invoice_description: "Utility bill".to_string(),
+        enable_expiry: None,
+        allow_partial: None,
+        minimum_amount: None,
+        allow_exceed: None,
+        maximum_amount: None,
+        amount: 10000.0,
+        callback_url: "https://cb.example.com".to_string(),
+        sender_terminal_code: None,
+        sender_terminal_data: None,
+        allow_subscribe: None,
+        subscription_interval: None,
+        subscription_webhook: None,
+        note: None,
+        transactions: None,
+        lines: None,
+    }
+}
+
+fn invoice_json(invoice_id: &str) -> String {
+    serde_json::json!({
+        "invoice_id": invoice_id,
+        "qr_text": "qr",
+        "qr_image": "img",
+        "qPay_shortUrl": "https://qpay.mn/q/short",
+        "urls": []
+    })
+    .to_string()
+}
+
+fn for_invoice(sender_invoice_no: &str) -> Matcher {
+    Matcher::PartialJson(serde_json::json!({ "sender_invoice_no": sender_invoice_no }))
+}
+
+#[tokio::test]
+async fn test_create_invoices_bulk() {
+    let mut server = Server::new_async().await;
+    let ts = future_timestamp();
+
+    let token_mock = server
+        .mock("POST", "/v2/auth/token")
+        .with_status(200)
+        .with_body(token_json(ts, ts + 1800))
+        .expect(1)
+        .create_async()
+        .await;
+
+    for i in 1..=5 {
+        let no = format!("BILL-{}", i);
+        if i == 3 {
+            continue;
+        }
+        server
+            .mock("POST", "/v2/invoice")
+            .match_header("authorization", "Bearer mock_access_token")
+            .match_body(for_invoice(&no))
+            .with_status(200)
+            .with_body(invoice_json(&format!("inv_{}", i)))
+            .create_async()
+            .await;
+    }
+
+    // BILL-3 fails permanently and must not abort the batch
+    let rejected = server
+        .mock("POST", "/v2/invoice")
+        .match_body(for_invoice("BILL-3"))
+        .with_status(400)
+        .with_body(r#"{"error": "INVALID_AMOUNT", "message": "Invalid amount"}"#)
+        .expect(1)
+        .create_async()
+        .await;
+
+    let client = QPayClient::new(test_config(&server.url()));
+    let requests = (1..=5).map(|i| invoice_request(&format!("BILL-{}", i)));
+    let results = client.create_invoices_bulk(requests, 3).await;
+
+    assert_eq!(results.len(), 5);
+    for (i, item) in results.iter().enumerate() {
+        assert_eq!(item.sender_invoice_no, format!("BILL-{}", i + 1));
+    }
+    assert_eq!(results[0].result.as_ref().unwrap().invoice_id, "inv_1");
+    assert_eq!(results[4].result.as_ref().unwrap().invoice_id, "inv_5");
+
+    let failed = &results[2];
+    assert_eq!(failed.attempts, 1);
+    let (status, code, _) = qpay::is_qpay_error(failed.result.as_ref().unwrap_err()).unwrap();
+    assert_eq!((status, code), (400, "INVALID_AMOUNT"));
+
+    token_mock.assert_async().await;
+    rejected.assert_async().await;
+}
+
+#[tokio::test]
+async fn test_create_invoices_bulk_retries_only_unsent_requests() {
+    let mut server = Server::new_async().await;
+    let ts = future_timestamp();
+
+    server
+        .mock("POST", "/v2/auth/token")
+        .with_status(200)
+        .with_body(token_json(ts, ts + 1800))
+        .create_async()
+        .await;
+
+    let limited = server
+        .mock("POST", "/v2/invoice")
+        .match_body(for_invoice("BILL-1"))
+        .with_status(429)
+        .with_body(r#"{"error": "TOO_MANY_REQUESTS", "message": "Slow down"}"#)
+        .expect(1)
+        .create_async()
+        .await;
+    server
+        .mock("POST", "/v2/invoice")
+        .match_body(for_invoice("BILL-1"))
+        .with_status(200)
+        .with_body(invoice_json("inv_1"))
+        .create_async()
+        .await;
+
+    // A server error may have created the invoice, so it is not resent
+    let down = server
+        .mock("POST", "/v2/invoice")
+        .match_body(for_invoice("BILL-2"))
+        .with_status(500)
+        .with_body(r#"{"error": "INTERNAL", "message": "boom"}"#)
+        .expect(1)
+        .create_async()
+        .await;
+
+    let client = QPayClient::new(test_config(&server.url()));
+    let results = client
+        .create_invoices_bulk(
+            vec![invoice_request("BILL-1"), invoice_request("BILL-2")],
+            2,
+        )
+        .await;
+
+    assert_eq!(results[0].attempts, 2);
+    assert_eq!(results[0].result.as_ref().unwrap().invoice_id, "inv_1");
+    assert_eq!(results[1].attempts, 1);
+    assert!(matches!(
+        results[1].result,
+        Err(QPayError::Api {
+            status_code: 500,
+            ..
+        })
+    ));
+
+    limited.assert_async().await;
+    down.assert_async().await;
+}
+
+#[tokio::test]
+async fn test_create_invoices_bulk_gives_up_on_connection_errors() {
+    // Nothing listens on this port, so every request fails to connect
+    let client = QPayClient::new(test_config("http://127.0.0.1:9"));
+    let results = client
+        .create_invoices_bulk(vec![invoice_request("BILL-1")], 1)
+        .await;
+
+    assert_eq!(results[0].attempts, qpay::bulk::BULK_MAX_ATTEMPTS);
+    assert!(results[0].result.is_err());
+}
+
+#[tokio::test]
+async fn test_create_invoices_bulk_retries_token_outages() {
+    let mut server = Server::new_async().await;
+    let ts = future_timestamp();
+
+    let unavailable = server
+        .mock("POST", "/v2/auth/token")
+        .with_status(503)
+        .with_body(r#"{"error": "UNAVAILABLE", "message": "Try again"}"#)
+        .expect(1)
+        .create_async()
+        .await;
+    server
+        .mock("POST", "/v2/auth/token")
+        .with_status(200)
+        .with_body(token_json(ts, ts + 1800))
+        .create_async()
+        .await;
+    server
+        .mock("POST", "/v2/invoice")
+        .with_status(200)
+        .with_body(invoice_json("inv_1"))
+        .create_async()
+        .await;
+
+    let client = QPayClient::new(test_config(&server.url()));
+    let results = client
+        .create_invoices_bulk(vec![invoice_request("BILL-1")], 1)
+        .await;
+
+    assert_eq!(results[0].attempts, 1);
+    assert_eq!(results[0].result.as_ref().unwrap().invoice_id, "inv_1");
+    unavailable.assert_async().await;
+}
+
+#[tokio::test]
+async fn test_create_invoices_bulk_fails_fast_on_rejected_credentials() {
+    let mut server = Server::new_async().await;
+
+    let token_mock = server
+        .mock("POST", "/v2/auth/token")
+        .with_status(401)
+        .with_body(r#"{"error": "AUTHENTICATION_FAILED", "message": "Bad credentials"}"#)
+        .expect(1)
+        .create_async()
+        .await;
+    let invoice_mock = server
+        .mock("POST", "/v2/invoice")
+        .expect(0)
+        .create_async()
+        .await;
+
+    let client = QPayClient::new(test_config(&server.url()));
+    let results = client
+        .create_invoices_bulk(
+            vec![invoice_request("BILL-1"), invoice_request("BILL-2")],
+            2,
+        )
+        .await;
+
+    for item in &results {
+        assert_eq!(item.attempts, 0);
+        match &item.result {
+            Err(QPayError::Token(reason)) => assert!(reason.contains("AUTHENTICATION_FAILED")),
+            other => panic!("expected QPayError::Token, got: {:?}", other),
+        }
+    }
+    token_mock.assert_async().await;
+    invoice_mock.assert_async().await;
+}
@@ -0,0 +1,534 @@
+mod common;
+
+use std::time::Duration;
+
+use mockito::{Matcher, ServerGuard};
+use qpay::callback::*;
+use qpay::dedup::FileDedupStore;
+use qpay::models::{CreateInvoiceRequest, CreateSimpleInvoiceRequest, InvoiceDetail};
+use qpay::QPayClient;
+use qpay::QPayConfig;
+
+use common::{server_with_token, test_config};
+
+fn payment_json(status: &str, amount: &str, object_id: &str) -> String {
+    serde_json::json!({
+        "payment_id": "pay_1",
+        "payment_status": status,
+        "payment_fee": "0",
+        "payment_amount": amount,
+        "payment_currency": "MNT",
+        "payment_date": "2024-01-15 10:30:00",
+        "payment_wallet": "qPay",
+        "transaction_type": "P2P",
+        "object_type": "INVOICE",
+        "object_id": object_id,
+        "next_payment_date": null,
+        "next_payment_datetime": null
+    })
+    .to_string()
+}
+
+async fn mock_payment(server: &mut ServerGuard, status: u16, body: String) {
+    server
+        .mock("GET", "/v2/payment/pay_1")
+        .with_status(status as usize)
+        .with_body(body)
+        .create_async()
+        .await;
+}
+
+#[test]
+fn test_callback_event_from_query() {
+    let event = CallbackEvent::from_query("?payment_id=pay_1&order=ORD%201");
+    assert_eq!(event.payment_id.as_deref(), Some("pay_1"));
+    assert_eq!(event.param("order"), Some("ORD 1"));
+
+    let event = CallbackEvent::from_query("qpay_payment_id=pay_2");
+    assert_eq!(event.payment_id.as_deref(), Some("pay_2"));
+
+    let event = CallbackEvent::from_query("payment_id=");
+    assert_eq!(event.payment_id, None);
+}
+
+#[test]
+fn test_callback_event_from_url_and_body() {
+    let event = CallbackEvent::from_url("https://shop.mn/qpay/callback?order=7&payment_id=pay_1#x");
+    assert_eq!(event.payment_id.as_deref(), Some("pay_1"));
+    assert_eq!(event.param("order"), Some("7"));
+
+    assert_eq!(
+        CallbackEvent::from_url("/qpay/callback"),
+        CallbackEvent::default()
+    );
+
+    let event = CallbackEvent::from_request(Some("order=7"), br#"{"payment_id": "pay_9"}"#);
+    assert_eq!(event.payment_id.as_deref(), Some("pay_9"));
+    assert_eq!(event.param("order"), Some("7"));
+
+    // The query takes precedence over the body
+    let event =
+        CallbackEvent::from_request(Some("payment_id=pay_1"), br#"{"payment_id": "pay_9"}"#);
+    assert_eq!(event.payment_id.as_deref(), Some("pay_1"));
+}
+
+#[tokio::test]
+async fn test_verify_callback_success() {
+    let mut server = server_with_token().await;
+    mock_payment(&mut server, 200, payment_json("PAID", "5000.00", "inv_1")).await;
+
+    let client = QPayClient::new(test_config(&server.url()));
+    let event = CallbackEvent::from_query("payment_id=pay_1");
+    let verified = client
+        .verify_callback(&event, &ExpectedPayment::invoice("inv_1", 5000.0))
+        .await
+        .unwrap();
+
+    assert_eq!(verified.payment_id, "pay_1");
+    assert_eq!(verified.object_id, "inv_1");
+    assert_eq!(verified.amount, 5000.0);
+    assert_eq!(verified.currency, "MNT");
+    assert_eq!(verified.event, event);
+}
+
+#[tokio::test]
+async fn test_verify_callback_rejections() {
+    let event = CallbackEvent::from_query("payment_id=pay_1");
+    let expected = ExpectedPayment::invoice("inv_1", 5000.0);
+
+    let mut server = server_with_token().await;
+    mock_payment(&mut server, 200, payment_json("PAID", "5000", "inv_other")).await;
+    let client = QPayClient::new(test_config(&server.url()));
+    let err = client.verify_callback(&event, &expected).await.unwrap_err();
+    assert!(
+        matches!(err, CallbackRejection::ObjectMismatch { ref actual, .. } if actual == "inv_other")
+    );
+    assert!(!err.is_retryable());
+
+    let mut server = server_with_token().await;
+    mock_payment(&mut server, 200, payment_json("PAID", "100", "inv_1")).await;
+    let client = QPayClient::new(test_config(&server.url()));
+    let err = client.verify_callback(&event, &expected).await.unwrap_err();
+    assert!(matches!(err, CallbackRejection::AmountMismatch { actual, .. } if actual == 100.0));
+
+    let mut server = server_with_token().await;
+    mock_payment(&mut server, 200, payment_json("NEW", "5000", "inv_1")).await;
+    let client = QPayClient::new(test_config(&server.url()));
+    let err = client.verify_callback(&event, &expected).await.unwrap_err();
+    assert!(matches!(err, CallbackRejection::NotPaid { ref status, .. } if status == "NEW"));
+
+    let mut server = server_with_token().await;
+    mock_payment(
+        &mut server,
+        404,
+        r#"{"error": "PAYMENT_NOTFOUND", "message": "Payment not found"}"#.to_string(),
+    )
+    .await;
+    let client = QPayClient::new(test_config(&server.url()));
+    let err = client.verify_callback(&event, &expected).await.unwrap_err();
+    assert!(matches!(err, CallbackRejection::UnknownPayment(ref id) if id == "pay_1"));
+
+    let mut server = server_with_token().await;
+    mock_payment(
+        &mut server,
+        502,
+        r#"{"error": "BAD_GATEWAY", "message": "upstream"}"#.to_string(),
+    )
+    .await;
+    let client = QPayClient::new(test_config(&server.url()));
+    let err = client.verify_callback(&event, &expected).await.unwrap_err();
+    assert!(matches!(err, CallbackRejection::Lookup(_)));
+    assert!(err.is_retryable());
+
+    let err = client
+        .verify_callback(&CallbackEvent::default(), &ExpectedPayment::default())
+        .await
+        .unwrap_err();
+    assert!(matches!(err, CallbackRejection::MissingPaymentId));
+}
+
+#[tokio::test]
+async fn test_verify_callback_by_invoice() {
+    let mut server = server_with_token().await;
+    server
+        .mock("POST", "/v2/payment/check")
+        .with_status(200)
+        .with_body(
+            serde_json::json!({
+                "count": 1,
+                "paid_amount": 5000.0,
+                "rows": [{
+                    "payment_id": "pay_7",
+                    "payment_status": "PAID",
+                    "payment_amount": "5000",
+                    "trx_fee": "0",
+                    "payment_currency": "MNT",
+                    "payment_wallet": "qPay",
+                    "payment_type": "P2P",
+                    "next_payment_date": null,
+                    "next_payment_datetime": null
+                }]
+            })
+            .to_string(),
+        )
+        .create_async()
+        .await;
+
+    let client = QPayClient::new(test_config(&server.url()));
+    let event = CallbackEvent::from_query("order=ORD-1");
+    let verified = client
+        .verify_callback(&event, &ExpectedPayment::invoice("inv_1", 5000.0))
+        .await
+        .unwrap();
+
+    assert_eq!(verified.payment_id, "pay_7");
+    assert_eq!(verified.object_id, "inv_1");
+}
+
+const SECRET: &str = "callback-secret";
+
+fn signer() -> CallbackSigner {
+    CallbackSigner::new("https://shop.mn/qpay/callback?shop=1", SECRET)
+        .with_ttl(Duration::from_secs(3600))
+}
+
+fn unix_now() -> u64 {
+    std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .unwrap()
+        .as_secs()
+}
+
+#[test]
+fn test_signed_callback_url_roundtrip() {
+    let url = signer().sign("INV 001").unwrap();
+    assert!(url.starts_with("https://shop.mn/qpay/callback?shop=1&qpay_ref=INV+001&qpay_exp="));
+
+    // QPay appends the payment id to the URL it was given
+    let event = CallbackEvent::from_url(&format!("{}&payment_id=pay_1", url));
+    assert_eq!(event.payment_id.as_deref(), Some("pay_1"));
+    assert_eq!(signer().verify(&event).unwrap(), "INV 001");
+
+    // Without a TTL the URL carries no expiry
+    let forever = CallbackSigner::new("https://shop.mn/qpay/callback", SECRET);
+    let url = forever.sign("INV-001").unwrap();
+    assert!(!url.contains("qpay_exp"));
+    assert_eq!(
+        forever.verify(&CallbackEvent::from_url(&url)).unwrap(),
+        "INV-001"
+    );
+}
+
+#[test]
+fn test_signed_callback_url_rejections() {
+    let url = signer().sign("INV-001").unwrap();
+
+    // Tampered reference
+    let tampered = url.replace("qpay_ref=INV-001", "qpay_ref=INV-002");
+    assert!(matches!(
+        signer().verify(&CallbackEvent::from_url(&tampered)),
+        Err(CallbackRejection::InvalidSignature)
+    ));
+
+    // Different secret
+    let other = CallbackSigner::new("https://shop.mn/qpay/callback", "other");
+    assert!(matches!(
+        other.verify(&CallbackEvent::from_url(&url)),
+        Err(CallbackRejection::InvalidSignature)
+    ));
+
+    // Unsigned callback
+    assert!(matches!(
+        signer().verify(&CallbackEvent::from_query("payment_id=pay_1")),
+        Err(CallbackRejection::InvalidSignature)
+    ));
+
+    // Expiry removed
+    let stripped = url
+        .split('&')
+        .filter(|pair| !pair.starts_with("qpay_exp="))
+        .collect::<Vec<_>>()
+        .join("&");
+    assert!(matches!(
+        signer().verify(&CallbackEvent::from_url(&stripped)),
+        Err(CallbackRejection::InvalidSignature)
+    ));
+
+    // Expired
+    let expired_at = unix_now() - 10;
+    let url = signer().sign_until("INV-001", expired_at).unwrap();
+    assert!(matches!(
+        signer().verify(&CallbackEvent::from_url(&url)),
+        Err(CallbackRejection::Expired(at)) if at == expired_at
+    ));
+}
+
+#[tokio::test]
+async fn test_create_invoice_fills_signed_callback_url() {
+    let mut server = server_with_token().await;
+    server
+        .mock("POST", "/v2/invoice")
+        .match_body(Matcher::Regex(
+            r#""callback_url":"https://shop\.mn/qpay/callback\?shop=1&qpay_ref=INV-001&qpay_exp=\d+&qpay_sig=[0-9a-f]{64}""#
+                .to_string(),
+        ))
+        .with_status(200)
+        .with_body(r#"{"invoice_id": "inv_1", "qr_text": "", "qr_image": "", "qPay_shortUrl": "", "urls": []}"#)
+        .expect(1)
+        .create_async()
+        .await;
+
+    let config = QPayConfig::new(
+        server.url(),
+        "test_user",
+        "test_pass",
+        "TEST_CODE",
+        "https://shop.mn/qpay/callback?shop=1",
+    )
+    .with_callback_secret(SECRET)
+    .with_callback_url_ttl(Duration::from_secs(3600));
+    let client = QPayClient::new(config);
+
+    let req = CreateInvoiceRequest {
+        invoice_code: "TEST_CODE".to_string(),
+        sender_invoice_no: "INV-001".to_string(),
+        sender_branch_code: None,
+        sender_branch_data: None,
+        sender_staff_data: None,
+        sender_staff_code: None,
+        invoice_receiver_code: "terminal".to_string(),
+        invoice_receiver_data: None,
+        invoice_description: "Signed callback".to_string(),
+        enable_expiry: None,
+        allow_partial: None,
+        minimum_amount: None,
+        allow_exceed: None,
+        maximum_amount: None,
+        amount: 1000.0,
+        // Left empty so the client fills in a signed URL
+        callback_url: String::new(),
+        sender_terminal_code: None,
+        sender_terminal_data: None,
+        allow_subscribe: None,
+        subscription_interval: None,
+        subscription_webhook: None,
+        note: None,
+        transactions: None,
+        lines: None,
+    };
+    let invoice = client.create_invoice(&req).await.unwrap();
+    assert_eq!(invoice.invoice_id, "inv_1");
+
+    let url = client.signed_callback_url("INV-001").unwrap();
+    assert!(signer().verify(&CallbackEvent::from_url(&url)).is_ok());
+
+    let unsigned = QPayClient::new(test_config(&server.url()));
+    assert!(matches!(
+        unsigned.signed_callback_url("INV-001"),
+        Err(qpay::QPayError::Config(_))
+    ));
+}
+
+#[derive(Default)]
+struct Counter {
+    calls: std::sync::atomic::AtomicUsize,
+}
+
+impl CallbackHandler for Counter {
+    async fn on_payment(&self, _payment: VerifiedCallback) -> Result<(), HandlerError> {
+        self.calls.fetch_add(1, std::sync::atomic::Ordering::SeqCst);
+        Ok(())
+    }
+}
+
+#[tokio::test]
+async fn test_processor_dedup_across_restarts() {
+    let mut server = server_with_token().await;
+    server
+        .mock("GET", "/v2/payment/pay_1")
+        .with_status(200)
+        .with_body(payment_json("PAID", "5000", "inv_1"))
+        .expect(1)
+        .create_async()
+        .await;
+
+    let path =
+        std::env::temp_dir().join(format!("qpay-callback-restart-{}.log", std::process::id()));
+    let _ = std::fs::remove_file(&path);
+    let client = std::sync::Arc::new(QPayClient::new(test_config(&server.url())));
+
+    let processor = CallbackProcessor::new(client.clone(), Counter::default())
+        .with_dedup_store(FileDedupStore::open(&path, Duration::from_secs(3600)).unwrap());
+    let outcome = processor.process(Some("payment_id=pay_1"), b"").await;
+    assert!(matches!(outcome, CallbackOutcome::Processed(_)));
+    assert_eq!(outcome.status_code(), 200);
+
+    // A new process with the same store skips the redelivery
+    let restarted = CallbackProcessor::new(client, Counter::default())
+        .with_dedup_store(FileDedupStore::open(&path, Duration::from_secs(3600)).unwrap());
+    let outcome = restarted.process(Some("payment_id=pay_1"), b"").await;
+    assert!(matches!(outcome, CallbackOutcome::Duplicate(ref id) if id == "pay_1"));
+    assert_eq!(outcome.body(), CALLBACK_SUCCESS_BODY);
+
+    let calls = |p: &CallbackProcessor<Counter>| {
+        p.handler().calls.load(std::sync::atomic::Ordering::SeqCst)
+    };
+    assert_eq!(calls(&processor), 1);
+    assert_eq!(calls(&restarted), 0);
+
+    std::fs::remove_file(&path).unwrap();
+}
+
+fn signed_config(url: &str) -> QPayConfig {
+    QPayConfig::new(
+        url,
+        "test_user",
+        "test_pass",
+        "TEST_CODE",
+        "https://shop.mn/qpay/callback",
+    )
+    .with_callback_secret(SECRET)
+}
+
+fn invoice_detail_json(invoice_id: &str, sender_invoice_no: &str) -> String {
+    serde_json::to_string(&InvoiceDetail {
+        invoice_id: invoice_id.to_string(),
+        invoice_status: "PAID".to_string(),
+        sender_invoice_no: sender_invoice_no.to_string(),
+        ..Default::default()
+    })
+    .unwrap()
+}
+
+#[tokio::test]
+async fn test_processor_accepts_callback_for_simple_invoice() {
+    let mut server = server_with_token().await;
+    server
+        .mock("POST", "/v2/invoice")
+        .match_body(Matcher::Regex(
+            r#""callback_url":"https://shop\.mn/qpay/callback\?qpay_ref=INV-001&qpay_sig=[0-9a-f]{64}""#
+                .to_string(),
+        ))
+        .with_status(200)
+        .with_body(r#"{"invoice_id": "inv_1", "qr_text": "", "qr_image": "", "qPay_shortUrl": "", "urls": []}"#)
+        .expect(1)
+        .create_async()
+        .await;
+    mock_payment(&mut server, 200, payment_json("PAID", "5000", "inv_1")).await;
+    server
+        .mock("GET", "/v2/invoice/inv_1")
+        .with_status(200)
+        .with_body(invoice_detail_json("inv_1", "INV-001"))
+        .create_async()
+        .await;
+
+    let client = std::sync::Arc::new(QPayClient::new(signed_config(&server.url())));
+    let req = CreateSimpleInvoiceRequest {
+        invoice_code: "TEST_CODE".to_string(),
+        sender_invoice_no: "INV-001".to_string(),
+        invoice_receiver_code: "terminal".to_string(),
+        invoice_description: "Simple invoice".to_string(),
+        sender_branch_code: None,
+        amount: 5000.0,
+        callback_url: String::new(),
+    };
+    client.create_simple_invoice(&req).await.unwrap();
+
+    // QPay calls back the signed URL with the payment id appended
+    let url = client.signed_callback_url("INV-001").unwrap();
+    let query = format!("{}&payment_id=pay_1", url.split_once('?').unwrap().1);
+    let processor = CallbackProcessor::new(client, Counter::default());
+    let outcome = processor.process(Some(&query), b"").await;
+    let CallbackOutcome::Processed(verified) = outcome else {
+        panic!("expected the callback to be processed, got {:?}", outcome);
+    };
+    assert_eq!(verified.signed_ref.as_deref(), Some("INV-001"));
+    assert_eq!(verified.object_id, "inv_1");
+}
+
+#[tokio::test]
+async fn test_processor_accepts_callback_for_explicit_callback_url() {
+    let mut server = server_with_token().await;
+    server
+        .mock("POST", "/v2/invoice")
+        .match_body(Matcher::Regex(
+            r#""callback_url":"https://shop\.mn/qpay/callback\?shop=1&qpay_ref=INV-001&qpay_sig=[0-9a-f]{64}""#
+                .to_string(),
+        ))
+        .with_status(200)
+        .with_body(r#"{"invoice_id": "inv_1", "qr_text": "", "qr_image": "", "qPay_shortUrl": "", "urls": []}"#)
+        .expect(2)
+        .create_async()
+        .await;
+    mock_payment(&mut server, 200, payment_json("PAID", "5000", "inv_1")).await;
+    server
+        .mock("GET", "/v2/invoice/inv_1")
+        .with_status(200)
+        .with_body(invoice_detail_json("inv_1", "INV-001"))
+        .create_async()
+        .await;
+
+    let client = std::sync::Arc::new(QPayClient::new(signed_config(&server.url())));
+    let explicit = "https://shop.mn/qpay/callback?shop=1";
+    let req = CreateSimpleInvoiceRequest {
+        invoice_code: "TEST_CODE".to_string(),
+        sender_invoice_no: "INV-001".to_string(),
+        invoice_receiver_code: "terminal".to_string(),
+        invoice_description: "Simple invoice".to_string(),
+        sender_branch_code: None,
+        amount: 5000.0,
+        callback_url: explicit.to_string(),
+    };
+    client.create_simple_invoice(&req).await.unwrap();
+
+    // An already signed URL is sent unchanged, not signed twice
+    let signed = client
+        .callback_signer()
+        .unwrap()
+        .sign_url(explicit, "INV-001")
+        .unwrap();
+    let req = CreateSimpleInvoiceRequest {
+        callback_url: signed.clone(),
+        ..req
+    };
+    client.create_simple_invoice(&req).await.unwrap();
+
+    let query = format!("{}&payment_id=pay_1", signed.split_once('?').unwrap().1);
+    let processor = CallbackProcessor::new(client, Counter::default());
+    let outcome = processor.process(Some(&query), b"").await;
+    let CallbackOutcome::Processed(verified) = outcome else {
+        panic!("expected the callback to be processed, got {:?}", outcome);
+    };
+    assert_eq!(verified.signed_ref.as_deref(), Some("INV-001"));
+}
+
+#[tokio::test]
+async fn test_processor_rejects_url_signed_for_another_invoice() {
+    let mut server = server_with_token().await;
+    mock_payment(&mut server, 200, payment_json("PAID", "5000", "inv_1")).await;
+    server
+        .mock("GET", "/v2/invoice/inv_1")
+        .with_status(200)
+        .with_body(invoice_detail_json("inv_1", "INV-001"))
+        .create_async()
+        .await;
+
+    let client = std::sync::Arc::new(QPayClient::new(signed_config(&server.url())));
+    let url = client.signed_callback_url("INV-002").unwrap();
+    let query = format!("{}&payment_id=pay_1", url.split_once('?').unwrap().1);
+    let processor = CallbackProcessor::new(client, Counter::default());
+
+    let outcome = processor.process(Some(&query), b"").await;
+    assert!(matches!(
+        outcome,
+        CallbackOutcome::Rejected(CallbackRejection::RefMismatch { ref signed_ref, ref object_id })
+            if signed_ref == "INV-002" && object_id == "inv_1"
+    ));
+    assert_eq!(
+        processor
+            .handler()
+            .calls
+            .load(std::sync::atomic::Ordering::SeqCst),
+        0
+    );
+}
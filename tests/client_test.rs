@@ -1,38 +1,10 @@
+mod common;
+
 use mockito::{Matcher, Server};
 use qpay::models::*;
-use qpay::{QPayClient, QPayConfig};
-
-fn test_config(server_url: &str) -> QPayConfig {
-    QPayConfig::new(
-        server_url,
-        "test_user",
-        "test_pass",
-        "TEST_CODE",
-        "https://example.com/callback",
-    )
-}
-
-fn token_json(expires_in: i64, refresh_expires_in: i64) -> String {
-    serde_json::json!({
-        "token_type": "Bearer",
-        "refresh_expires_in": refresh_expires_in,
-        "refresh_token": "mock_refresh_token",
-        "access_token": "mock_access_token",
-        "expires_in": expires_in,
-        "scope": "default",
-        "not-before-policy": "0",
-        "session_state": "mock_session"
-    })
-    .to_string()
-}
+use qpay::{QPayClient, QPayError, Strictness};
 
-fn future_timestamp() -> i64 {
-    std::time::SystemTime::now()
-        .duration_since(std::time::UNIX_EPOCH)
-        .unwrap()
-        .as_secs() as i64
-        + 3600
-}
+use common::{future_timestamp, test_config, token_json};
 
 // --- Auth: get_token ---
 
@@ -344,6 +316,146 @@ async fn test_create_ebarimt_invoice_success() {
     invoice_mock.assert_async().await;
 }
 
+// --- Invoice: get_invoice ---
+
+#[tokio::test]
+async fn test_get_invoice_success() {
+    let mut server = Server::new_async().await;
+    let ts = future_timestamp();
+
+    let token_mock = server
+        .mock("POST", "/v2/auth/token")
+        .with_status(200)
+        .with_body(token_json(ts, ts + 1800))
+        .create_async()
+        .await;
+
+    let invoice_json = r#"{
+        "invoice_id": "inv_123",
+        "invoice_status": "OPEN",
+        "sender_invoice_no": "INV-001",
+        "sender_branch_code": "BRANCH_01",
+        "sender_branch_data": null,
+        "sender_staff_code": null,
+        "sender_staff_data": null,
+        "sender_terminal_code": null,
+        "sender_terminal_data": null,
+        "invoice_receiver_code": "terminal",
+        "invoice_receiver_data": {
+            "register": "AA12345678",
+            "name": "Customer"
+        },
+        "invoice_description": "Order #001",
+        "invoice_due_date": null,
+        "enable_expiry": false,
+        "expiry_date": "2026-02-01 00:00:00",
+        "allow_partial": true,
+        "minimum_amount": 1000,
+        "allow_exceed": false,
+        "maximum_amount": null,
+        "total_amount": 10000,
+        "gross_amount": 10000,
+        "tax_amount": 0,
+        "surcharge_amount": 0,
+        "discount_amount": 0,
+        "callback_url": "https://example.com/cb",
+        "note": null,
+        "lines": [
+            {
+                "tax_product_code": "TAX001",
+                "line_description": "Product A",
+                "line_quantity": "2.00",
+                "line_unit_price": "5000.00",
+                "note": null,
+                "discounts": [],
+                "surcharges": [],
+                "taxes": [
+                    {
+                        "tax_code": "VAT",
+                        "description": "VAT",
+                        "amount": 909.09
+                    }
+                ]
+            }
+        ],
+        "transactions": [],
+        "inputs": [],
+        "payments": [
+            {
+                "payment_id": "pay_001",
+                "transaction_type": "P2P"
+            }
+        ]
+    }"#;
+
+    let invoice_mock = server
+        .mock("GET", "/v2/invoice/inv_123")
+        .match_header("authorization", "Bearer mock_access_token")
+        .with_status(200)
+        .with_header("content-type", "application/json")
+        .with_body(invoice_json)
+        .create_async()
+        .await;
+
+    let config = test_config(&server.url());
+    let client = QPayClient::new(config);
+
+    let result = client.get_invoice("inv_123").await;
+    assert!(result.is_ok());
+
+    let invoice = result.unwrap();
+    assert_eq!(invoice.invoice_id, "inv_123");
+    assert_eq!(invoice.invoice_status, "OPEN");
+    assert_eq!(invoice.sender_invoice_no, "INV-001");
+    assert_eq!(invoice.total_amount, 10000.0);
+    assert!(invoice.allow_partial);
+    assert_eq!(invoice.minimum_amount, Some(1000.0));
+    assert_eq!(
+        invoice.invoice_receiver_data.unwrap().register.as_deref(),
+        Some("AA12345678")
+    );
+    assert_eq!(invoice.lines.len(), 1);
+    assert_eq!(invoice.lines[0].line_description, "Product A");
+    assert_eq!(invoice.lines[0].taxes[0].amount, 909.09);
+    assert_eq!(invoice.payments.len(), 1);
+    assert_eq!(invoice.payments[0].payment_id, "pay_001");
+    assert!(invoice.extra.is_empty());
+
+    token_mock.assert_async().await;
+    invoice_mock.assert_async().await;
+}
+
+#[tokio::test]
+async fn test_get_invoice_not_found() {
+    let mut server = Server::new_async().await;
+    let ts = future_timestamp();
+
+    server
+        .mock("POST", "/v2/auth/token")
+        .with_status(200)
+        .with_body(token_json(ts, ts + 1800))
+        .create_async()
+        .await;
+
+    server
+        .mock("GET", "/v2/invoice/inv_nonexist")
+        .with_status(404)
+        .with_body(r#"{"code":"INVOICE_NOTFOUND","message":"Invoice not found"}"#)
+        .create_async()
+        .await;
+
+    let config = test_config(&server.url());
+    let client = QPayClient::new(config);
+
+    let result = client.get_invoice("inv_nonexist").await;
+    assert!(result.is_err());
+
+    let err = result.unwrap_err();
+    let (status, code, _) = qpay::is_qpay_error(&err).unwrap();
+    assert_eq!(status, 404);
+    assert_eq!(code, "INVOICE_NOTFOUND");
+}
+
 // --- Invoice: cancel_invoice ---
 
 #[tokio::test]
@@ -533,10 +645,8 @@ async fn test_get_payment_not_found() {
     assert_eq!(code, "PAYMENT_NOTFOUND");
 }
 
-// --- Payment: check_payment ---
-
 #[tokio::test]
-async fn test_check_payment_success() {
+async fn test_get_payment_strict_rejects_unknown_fields() {
     let mut server = Server::new_async().await;
     let ts = future_timestamp();
 
@@ -547,54 +657,41 @@ async fn test_check_payment_success() {
         .create_async()
         .await;
 
-    let check_response = serde_json::json!({
-        "count": 1,
-        "paid_amount": 5000.0,
-        "rows": [
-            {
-                "payment_id": "pay_001",
-                "payment_status": "PAID",
-                "payment_amount": "5000",
-                "trx_fee": "50",
-                "payment_currency": "MNT",
-                "payment_wallet": "qPay",
-                "payment_type": "P2P",
-                "next_payment_date": null,
-                "next_payment_datetime": null,
-                "card_transactions": [],
-                "p2p_transactions": []
-            }
-        ]
+    let payment_json = serde_json::json!({
+        "payment_id": "pay_001",
+        "payment_status": "PAID",
+        "payment_fee": "100",
+        "payment_amount": "5000",
+        "payment_currency": "MNT",
+        "payment_date": "2026-01-15",
+        "payment_wallet": "qPay",
+        "transaction_type": "P2P",
+        "object_type": "INVOICE",
+        "object_id": "inv_001",
+        "next_payment_date": null,
+        "next_payment_datetime": null,
+        "loyalty_points": 10
     });
 
     server
-        .mock("POST", "/v2/payment/check")
-        .match_header("authorization", "Bearer mock_access_token")
+        .mock("GET", "/v2/payment/pay_001")
         .with_status(200)
-        .with_body(check_response.to_string())
+        .with_body(payment_json.to_string())
         .create_async()
         .await;
 
-    let config = test_config(&server.url());
+    let config = test_config(&server.url()).with_strictness(Strictness::Strict);
     let client = QPayClient::new(config);
 
-    let req = PaymentCheckRequest {
-        object_type: "INVOICE".to_string(),
-        object_id: "inv_001".to_string(),
-        offset: None,
-    };
-
-    let result = client.check_payment(&req).await;
-    assert!(result.is_ok());
-
-    let resp = result.unwrap();
-    assert_eq!(resp.count, 1);
-    assert_eq!(resp.paid_amount, Some(5000.0));
-    assert_eq!(resp.rows[0].payment_status, "PAID");
+    let err = client.get_payment("pay_001").await.unwrap_err();
+    match err {
+        QPayError::Json(e) => assert!(e.to_string().contains("loyalty_points")),
+        other => panic!("expected QPayError::Json, got: {:?}", other),
+    }
 }
 
 #[tokio::test]
-async fn test_check_payment_no_payments() {
+async fn test_get_payment_lenient_fills_missing_fields() {
     let mut server = Server::new_async().await;
     let ts = future_timestamp();
 
@@ -605,34 +702,38 @@ async fn test_check_payment_no_payments() {
         .create_async()
         .await;
 
+    // payment_fee and transaction_type are missing, loyalty_points is unknown
+    let payment_json = serde_json::json!({
+        "payment_id": "pay_001",
+        "payment_status": "PAID",
+        "payment_amount": "5000",
+        "payment_currency": "MNT",
+        "payment_date": "2026-01-15",
+        "payment_wallet": "qPay",
+        "object_type": "INVOICE",
+        "object_id": "inv_001",
+        "loyalty_points": 10
+    });
+
     server
-        .mock("POST", "/v2/payment/check")
+        .mock("GET", "/v2/payment/pay_001")
         .with_status(200)
-        .with_body(r#"{"count": 0, "rows": []}"#)
+        .with_body(payment_json.to_string())
         .create_async()
         .await;
 
     let config = test_config(&server.url());
     let client = QPayClient::new(config);
 
-    let req = PaymentCheckRequest {
-        object_type: "INVOICE".to_string(),
-        object_id: "inv_empty".to_string(),
-        offset: None,
-    };
-
-    let result = client.check_payment(&req).await;
-    assert!(result.is_ok());
-
-    let resp = result.unwrap();
-    assert_eq!(resp.count, 0);
-    assert!(resp.rows.is_empty());
+    let detail = client.get_payment("pay_001").await.unwrap();
+    assert_eq!(detail.payment_id, "pay_001");
+    assert_eq!(detail.payment_fee, "");
+    assert_eq!(detail.transaction_type, "");
+    assert_eq!(detail.extra.get("loyalty_points").unwrap(), 10);
 }
 
-// --- Payment: list_payments ---
-
 #[tokio::test]
-async fn test_list_payments_success() {
+async fn test_lenient_never_defaults_identifiers() {
     let mut server = Server::new_async().await;
     let ts = future_timestamp();
 
@@ -643,77 +744,45 @@ async fn test_list_payments_success() {
         .create_async()
         .await;
 
-    let list_response = serde_json::json!({
-        "count": 2,
-        "rows": [
-            {
-                "payment_id": "pay_001",
-                "payment_date": "2026-01-15",
-                "payment_status": "PAID",
-                "payment_fee": "100",
-                "payment_amount": "5000",
-                "payment_currency": "MNT",
-                "payment_wallet": "qPay",
-                "payment_name": "Payment 1",
-                "payment_description": "Test 1",
-                "qr_code": "qr1",
-                "paid_by": "user1",
-                "object_type": "INVOICE",
-                "object_id": "inv_001"
-            },
-            {
-                "payment_id": "pay_002",
-                "payment_date": "2026-01-16",
-                "payment_status": "PAID",
-                "payment_fee": "200",
-                "payment_amount": "10000",
-                "payment_currency": "MNT",
-                "payment_wallet": "qPay",
-                "payment_name": "Payment 2",
-                "payment_description": "Test 2",
-                "qr_code": "qr2",
-                "paid_by": "user2",
-                "object_type": "INVOICE",
-                "object_id": "inv_002"
-            }
-        ]
+    // payment_fee could be defaulted, but payment_id and object_id must not be
+    let payment_json = serde_json::json!({
+        "payment_id": null,
+        "payment_status": "PAID",
+        "payment_amount": "5000",
+        "payment_currency": "MNT",
+        "payment_date": "2026-01-15",
+        "payment_wallet": "qPay",
+        "transaction_type": "P2P",
+        "object_type": "INVOICE"
     });
 
     server
-        .mock("POST", "/v2/payment/list")
+        .mock("GET", "/v2/payment/pay_001")
         .with_status(200)
-        .with_body(list_response.to_string())
+        .with_body(payment_json.to_string())
         .create_async()
         .await;
 
-    let config = test_config(&server.url());
-    let client = QPayClient::new(config);
-
-    let req = PaymentListRequest {
-        object_type: "INVOICE".to_string(),
-        object_id: "inv_001".to_string(),
-        start_date: "2026-01-01".to_string(),
-        end_date: "2026-01-31".to_string(),
-        offset: Offset {
-            page_number: 1,
-            page_limit: 20,
-        },
-    };
+    let client = QPayClient::new(test_config(&server.url()));
+    let err = client.get_payment("pay_001").await.unwrap_err();
+    assert!(matches!(err, QPayError::Json(_)), "got: {:?}", err);
 
-    let result = client.list_payments(&req).await;
-    assert!(result.is_ok());
+    server
+        .mock("GET", "/v2/invoice/inv_001")
+        .with_status(200)
+        .with_body(r#"{"invoice_status":"OPEN","sender_invoice_no":"INV-001","invoice_description":"","total_amount":100,"callback_url":""}"#)
+        .create_async()
+        .await;
 
-    let resp = result.unwrap();
-    assert_eq!(resp.count, 2);
-    assert_eq!(resp.rows.len(), 2);
-    assert_eq!(resp.rows[0].payment_id, "pay_001");
-    assert_eq!(resp.rows[1].payment_amount, "10000");
+    let err = client.get_invoice("inv_001").await.unwrap_err();
+    match err {
+        QPayError::Json(e) => assert!(e.to_string().contains("invoice_id")),
+        other => panic!("expected QPayError::Json, got: {:?}", other),
+    }
 }
 
-// --- Payment: cancel_payment ---
-
 #[tokio::test]
-async fn test_cancel_payment_success() {
+async fn test_get_invoice_checks_nested_detail_fields() {
     let mut server = Server::new_async().await;
     let ts = future_timestamp();
 
@@ -724,28 +793,46 @@ async fn test_cancel_payment_success() {
         .create_async()
         .await;
 
+    // The transaction is missing its amount and has an unknown field
+    let invoice_json = serde_json::json!({
+        "invoice_id": "inv_001",
+        "invoice_status": "OPEN",
+        "sender_invoice_no": "INV-001",
+        "invoice_description": "Order",
+        "total_amount": 100,
+        "callback_url": "",
+        "sender_branch_data": {"name": "Branch", "branch_rank": 1},
+        "transactions": [{"description": "Split", "settled": true}]
+    });
+
     server
-        .mock("DELETE", "/v2/payment/cancel/pay_001")
-        .match_header("authorization", "Bearer mock_access_token")
+        .mock("GET", "/v2/invoice/inv_001")
         .with_status(200)
-        .with_body("")
+        .with_body(invoice_json.to_string())
+        .expect(2)
         .create_async()
         .await;
 
-    let config = test_config(&server.url());
-    let client = QPayClient::new(config);
-
-    let req = PaymentCancelRequest {
-        callback_url: Some("https://cb.example.com".to_string()),
-        note: Some("User cancelled".to_string()),
-    };
+    let client = QPayClient::new(test_config(&server.url()));
+    let invoice = client.get_invoice("inv_001").await.unwrap();
+    assert_eq!(invoice.transactions[0].amount, "");
+    assert_eq!(invoice.transactions[0].extra.get("settled").unwrap(), true);
+    assert_eq!(
+        invoice.sender_branch_data.unwrap().extra.get("branch_rank").unwrap(),
+        1
+    );
 
-    let result = client.cancel_payment("pay_001", &req).await;
-    assert!(result.is_ok());
+    let config = test_config(&server.url()).with_strictness(Strictness::Strict);
+    let client = QPayClient::new(config);
+    let err = client.get_invoice("inv_001").await.unwrap_err();
+    match err {
+        QPayError::Json(e) => assert!(e.to_string().contains("amount")),
+        other => panic!("expected QPayError::Json, got: {:?}", other),
+    }
 }
 
 #[tokio::test]
-async fn test_cancel_payment_already_canceled() {
+async fn test_get_payment_strict_rejects_missing_fields() {
     let mut server = Server::new_async().await;
     let ts = future_timestamp();
 
@@ -757,30 +844,836 @@ async fn test_cancel_payment_already_canceled() {
         .await;
 
     server
-        .mock("DELETE", "/v2/payment/cancel/pay_cancelled")
-        .with_status(400)
-        .with_body(
-            r#"{"code":"PAYMENT_ALREADY_CANCELED","message":"Payment is already canceled"}"#,
-        )
+        .mock("GET", "/v2/payment/pay_001")
+        .with_status(200)
+        .with_body(r#"{"payment_id":"pay_001"}"#)
+        .create_async()
+        .await;
+
+    let config = test_config(&server.url()).with_strictness(Strictness::Strict);
+    let client = QPayClient::new(config);
+
+    let err = client.get_payment("pay_001").await.unwrap_err();
+    assert!(matches!(err, QPayError::Json(_)));
+}
+
+// --- Raw responses ---
+
+#[tokio::test]
+async fn test_with_raw_get_payment() {
+    let mut server = Server::new_async().await;
+    let ts = future_timestamp();
+
+    server
+        .mock("POST", "/v2/auth/token")
+        .with_status(200)
+        .with_body(token_json(ts, ts + 1800))
+        .create_async()
+        .await;
+
+    let payment_json = serde_json::json!({
+        "payment_id": "pay_001",
+        "payment_status": "PAID",
+        "payment_fee": "100",
+        "payment_amount": "5000",
+        "payment_currency": "MNT",
+        "payment_date": "2026-01-15",
+        "payment_wallet": "qPay",
+        "transaction_type": "P2P",
+        "object_type": "INVOICE",
+        "object_id": "inv_001",
+        "next_payment_date": null,
+        "next_payment_datetime": null
+    })
+    .to_string();
+
+    server
+        .mock("GET", "/v2/payment/pay_001")
+        .with_status(200)
+        .with_header("x-request-id", "req-123")
+        .with_body(payment_json.clone())
+        .create_async()
+        .await;
+
+    let config = test_config(&server.url());
+    let client = QPayClient::new(config);
+
+    let resp = client.with_raw().get_payment("pay_001").await.unwrap();
+    assert_eq!(resp.status.as_u16(), 200);
+    assert_eq!(resp.header("x-request-id"), Some("req-123"));
+    assert_eq!(resp.body, payment_json);
+    assert_eq!(resp.value.payment_id, "pay_001");
+
+    let detail = resp.into_inner();
+    assert_eq!(detail.payment_amount, "5000");
+}
+
+#[tokio::test]
+async fn test_with_raw_cancel_invoice() {
+    let mut server = Server::new_async().await;
+    let ts = future_timestamp();
+
+    server
+        .mock("POST", "/v2/auth/token")
+        .with_status(200)
+        .with_body(token_json(ts, ts + 1800))
+        .create_async()
+        .await;
+
+    server
+        .mock("DELETE", "/v2/invoice/inv_001")
+        .with_status(200)
+        .with_header("x-ratelimit-remaining", "99")
+        .create_async()
+        .await;
+
+    let config = test_config(&server.url());
+    let client = QPayClient::new(config);
+
+    let resp = client.with_raw().cancel_invoice("inv_001").await.unwrap();
+    assert!(resp.status.is_success());
+    assert_eq!(resp.header("x-ratelimit-remaining"), Some("99"));
+    assert!(resp.body.is_empty());
+}
+
+#[tokio::test]
+async fn test_with_raw_api_error() {
+    let mut server = Server::new_async().await;
+    let ts = future_timestamp();
+
+    server
+        .mock("POST", "/v2/auth/token")
+        .with_status(200)
+        .with_body(token_json(ts, ts + 1800))
+        .create_async()
+        .await;
+
+    server
+        .mock("GET", "/v2/payment/missing")
+        .with_status(404)
+        .with_body(r#"{"code":"PAYMENT_NOTFOUND","message":"Payment not found"}"#)
+        .create_async()
+        .await;
+
+    let config = test_config(&server.url());
+    let client = QPayClient::new(config);
+
+    let err = client.with_raw().get_payment("missing").await.unwrap_err();
+    let (status, code, _) = qpay::is_qpay_error(&err).unwrap();
+    assert_eq!(status, 404);
+    assert_eq!(code, "PAYMENT_NOTFOUND");
+}
+
+// --- Payment: check_payment ---
+
+#[tokio::test]
+async fn test_check_payment_success() {
+    let mut server = Server::new_async().await;
+    let ts = future_timestamp();
+
+    server
+        .mock("POST", "/v2/auth/token")
+        .with_status(200)
+        .with_body(token_json(ts, ts + 1800))
+        .create_async()
+        .await;
+
+    let check_response = serde_json::json!({
+        "count": 1,
+        "paid_amount": 5000.0,
+        "rows": [
+            {
+                "payment_id": "pay_001",
+                "payment_status": "PAID",
+                "payment_amount": "5000",
+                "trx_fee": "50",
+                "payment_currency": "MNT",
+                "payment_wallet": "qPay",
+                "payment_type": "P2P",
+                "next_payment_date": null,
+                "next_payment_datetime": null,
+                "card_transactions": [],
+                "p2p_transactions": []
+            }
+        ]
+    });
+
+    server
+        .mock("POST", "/v2/payment/check")
+        .match_header("authorization", "Bearer mock_access_token")
+        .with_status(200)
+        .with_body(check_response.to_string())
+        .create_async()
+        .await;
+
+    let config = test_config(&server.url());
+    let client = QPayClient::new(config);
+
+    let req = PaymentCheckRequest {
+        object_type: "INVOICE".to_string(),
+        object_id: "inv_001".to_string(),
+        offset: None,
+    };
+
+    let result = client.check_payment(&req).await;
+    assert!(result.is_ok());
+
+    let resp = result.unwrap();
+    assert_eq!(resp.count, 1);
+    assert_eq!(resp.paid_amount, Some(5000.0));
+    assert_eq!(resp.rows[0].payment_status, "PAID");
+}
+
+#[tokio::test]
+async fn test_check_payment_no_payments() {
+    let mut server = Server::new_async().await;
+    let ts = future_timestamp();
+
+    server
+        .mock("POST", "/v2/auth/token")
+        .with_status(200)
+        .with_body(token_json(ts, ts + 1800))
+        .create_async()
+        .await;
+
+    server
+        .mock("POST", "/v2/payment/check")
+        .with_status(200)
+        .with_body(r#"{"count": 0, "rows": []}"#)
+        .create_async()
+        .await;
+
+    let config = test_config(&server.url());
+    let client = QPayClient::new(config);
+
+    let req = PaymentCheckRequest {
+        object_type: "INVOICE".to_string(),
+        object_id: "inv_empty".to_string(),
+        offset: None,
+    };
+
+    let result = client.check_payment(&req).await;
+    assert!(result.is_ok());
+
+    let resp = result.unwrap();
+    assert_eq!(resp.count, 0);
+    assert!(resp.rows.is_empty());
+}
+
+// --- Payment: list_payments ---
+
+#[tokio::test]
+async fn test_list_payments_success() {
+    let mut server = Server::new_async().await;
+    let ts = future_timestamp();
+
+    server
+        .mock("POST", "/v2/auth/token")
+        .with_status(200)
+        .with_body(token_json(ts, ts + 1800))
+        .create_async()
+        .await;
+
+    let list_response = serde_json::json!({
+        "count": 2,
+        "rows": [
+            {
+                "payment_id": "pay_001",
+                "payment_date": "2026-01-15",
+                "payment_status": "PAID",
+                "payment_fee": "100",
+                "payment_amount": "5000",
+                "payment_currency": "MNT",
+                "payment_wallet": "qPay",
+                "payment_name": "Payment 1",
+                "payment_description": "Test 1",
+                "qr_code": "qr1",
+                "paid_by": "user1",
+                "object_type": "INVOICE",
+                "object_id": "inv_001"
+            },
+            {
+                "payment_id": "pay_002",
+                "payment_date": "2026-01-16",
+                "payment_status": "PAID",
+                "payment_fee": "200",
+                "payment_amount": "10000",
+                "payment_currency": "MNT",
+                "payment_wallet": "qPay",
+                "payment_name": "Payment 2",
+                "payment_description": "Test 2",
+                "qr_code": "qr2",
+                "paid_by": "user2",
+                "object_type": "INVOICE",
+                "object_id": "inv_002"
+            }
+        ]
+    });
+
+    server
+        .mock("POST", "/v2/payment/list")
+        .with_status(200)
+        .with_body(list_response.to_string())
+        .create_async()
+        .await;
+
+    let config = test_config(&server.url());
+    let client = QPayClient::new(config);
+
+    let req = PaymentListRequest {
+        object_type: "INVOICE".to_string(),
+        object_id: "inv_001".to_string(),
+        start_date: "2026-01-01".to_string(),
+        end_date: "2026-01-31".to_string(),
+        offset: Offset {
+            page_number: 1,
+            page_limit: 20,
+        },
+    };
+
+    let result = client.list_payments(&req).await;
+    assert!(result.is_ok());
+
+    let resp = result.unwrap();
+    assert_eq!(resp.count, 2);
+    assert_eq!(resp.rows.len(), 2);
+    assert_eq!(resp.rows[0].payment_id, "pay_001");
+    assert_eq!(resp.rows[1].payment_amount, "10000");
+}
+
+// --- Payment: cancel_payment ---
+
+#[tokio::test]
+async fn test_cancel_payment_success() {
+    let mut server = Server::new_async().await;
+    let ts = future_timestamp();
+
+    server
+        .mock("POST", "/v2/auth/token")
+        .with_status(200)
+        .with_body(token_json(ts, ts + 1800))
+        .create_async()
+        .await;
+
+    server
+        .mock("DELETE", "/v2/payment/cancel/pay_001")
+        .match_header("authorization", "Bearer mock_access_token")
+        .with_status(200)
+        .with_body("")
+        .create_async()
+        .await;
+
+    let config = test_config(&server.url());
+    let client = QPayClient::new(config);
+
+    let req = PaymentCancelRequest {
+        callback_url: Some("https://cb.example.com".to_string()),
+        note: Some("User cancelled".to_string()),
+    };
+
+    let result = client.cancel_payment("pay_001", &req).await;
+    assert!(result.is_ok());
+}
+
+#[tokio::test]
+async fn test_cancel_payment_already_canceled() {
+    let mut server = Server::new_async().await;
+    let ts = future_timestamp();
+
+    server
+        .mock("POST", "/v2/auth/token")
+        .with_status(200)
+        .with_body(token_json(ts, ts + 1800))
+        .create_async()
+        .await;
+
+    server
+        .mock("DELETE", "/v2/payment/cancel/pay_cancelled")
+        .with_status(400)
+        .with_body(
+            r#"{"code":"PAYMENT_ALREADY_CANCELED","message":"Payment is already canceled"}"#,
+        )
+        .create_async()
+        .await;
+
+    let config = test_config(&server.url());
+    let client = QPayClient::new(config);
+
+    let req = PaymentCancelRequest::default();
+    let result = client.cancel_payment("pay_cancelled", &req).await;
+    assert!(result.is_err());
+
+    let err = result.unwrap_err();
+    let (_, code, _) = qpay::is_qpay_error(&err).unwrap();
+    assert_eq!(code, "PAYMENT_ALREADY_CANCELED");
+}
+
+// --- Payment: refund_payment ---
+
+#[tokio::test]
+async fn test_refund_payment_success() {
+    let mut server = Server::new_async().await;
+    let ts = future_timestamp();
+
+    server
+        .mock("POST", "/v2/auth/token")
+        .with_status(200)
+        .with_body(token_json(ts, ts + 1800))
+        .create_async()
+        .await;
+
+    server
+        .mock("DELETE", "/v2/payment/refund/pay_001")
+        .match_header("authorization", "Bearer mock_access_token")
+        .with_status(200)
+        .with_body("")
+        .create_async()
+        .await;
+
+    let config = test_config(&server.url());
+    let client = QPayClient::new(config);
+
+    let req = PaymentRefundRequest {
+        callback_url: Some("https://cb.example.com".to_string()),
+        note: Some("Refund requested".to_string()),
+    };
+
+    let result = client.refund_payment("pay_001", &req).await;
+    assert!(result.is_ok());
+}
+
+// --- Ebarimt: create_ebarimt ---
+
+#[tokio::test]
+async fn test_create_ebarimt_success() {
+    let mut server = Server::new_async().await;
+    let ts = future_timestamp();
+
+    server
+        .mock("POST", "/v2/auth/token")
+        .with_status(200)
+        .with_body(token_json(ts, ts + 1800))
+        .create_async()
+        .await;
+
+    let ebarimt_response = serde_json::json!({
+        "id": "eb_001",
+        "ebarimt_by": "system",
+        "g_wallet_id": "w_001",
+        "g_wallet_customer_id": "wc_001",
+        "ebarimt_receiver_type": "83",
+        "ebarimt_receiver": "",
+        "ebarimt_district_code": "23",
+        "ebarimt_bill_type": "1",
+        "g_merchant_id": "m_001",
+        "merchant_branch_code": "br_001",
+        "merchant_terminal_code": null,
+        "merchant_staff_code": null,
+        "merchant_register_no": "1234567",
+        "g_payment_id": "pay_001",
+        "paid_by": "user1",
+        "object_type": "INVOICE",
+        "object_id": "inv_001",
+        "amount": "5000",
+        "vat_amount": "500",
+        "city_tax_amount": "50",
+        "ebarimt_qr_data": "qr_data",
+        "ebarimt_lottery": "AB12345678",
+        "note": null,
+        "barimt_status": "SUCCESS",
+        "barimt_status_date": "2026-01-15",
+        "ebarimt_sent_email": null,
+        "ebarimt_receiver_phone": "99001122",
+        "tax_type": "1",
+        "merchant_tin": null,
+        "ebarimt_receipt_id": null,
+        "created_by": "system",
+        "created_date": "2026-01-15",
+        "updated_by": "system",
+        "updated_date": "2026-01-15",
+        "status": true,
+        "barimt_items": [],
+        "barimt_transactions": [],
+        "barimt_histories": []
+    });
+
+    server
+        .mock("POST", "/v2/ebarimt_v3/create")
+        .match_header("authorization", "Bearer mock_access_token")
+        .with_status(200)
+        .with_body(ebarimt_response.to_string())
+        .create_async()
+        .await;
+
+    let config = test_config(&server.url());
+    let client = QPayClient::new(config);
+
+    let req = CreateEbarimtRequest {
+        payment_id: "pay_001".to_string(),
+        ebarimt_receiver_type: "83".to_string(),
+        ebarimt_receiver: None,
+        district_code: Some("23".to_string()),
+        classification_code: None,
+    };
+
+    let result = client.create_ebarimt(&req).await;
+    assert!(result.is_ok());
+
+    let ebarimt = result.unwrap();
+    assert_eq!(ebarimt.id, "eb_001");
+    assert_eq!(ebarimt.barimt_status, "SUCCESS");
+    assert_eq!(ebarimt.ebarimt_lottery, "AB12345678");
+    assert!(ebarimt.status);
+}
+
+// --- Ebarimt: cancel_ebarimt ---
+
+#[tokio::test]
+async fn test_cancel_ebarimt_success() {
+    let mut server = Server::new_async().await;
+    let ts = future_timestamp();
+
+    server
+        .mock("POST", "/v2/auth/token")
+        .with_status(200)
+        .with_body(token_json(ts, ts + 1800))
+        .create_async()
+        .await;
+
+    let ebarimt_response = serde_json::json!({
+        "id": "eb_001",
+        "ebarimt_by": "system",
+        "g_wallet_id": "w_001",
+        "g_wallet_customer_id": "wc_001",
+        "ebarimt_receiver_type": "83",
+        "ebarimt_receiver": "",
+        "ebarimt_district_code": "23",
+        "ebarimt_bill_type": "1",
+        "g_merchant_id": "m_001",
+        "merchant_branch_code": "br_001",
+        "merchant_terminal_code": null,
+        "merchant_staff_code": null,
+        "merchant_register_no": "1234567",
+        "g_payment_id": "pay_001",
+        "paid_by": "user1",
+        "object_type": "INVOICE",
+        "object_id": "inv_001",
+        "amount": "5000",
+        "vat_amount": "500",
+        "city_tax_amount": "50",
+        "ebarimt_qr_data": "qr_data",
+        "ebarimt_lottery": "AB12345678",
+        "note": null,
+        "barimt_status": "CANCELLED",
+        "barimt_status_date": "2026-01-16",
+        "ebarimt_sent_email": null,
+        "ebarimt_receiver_phone": "99001122",
+        "tax_type": "1",
+        "merchant_tin": null,
+        "ebarimt_receipt_id": null,
+        "created_by": "system",
+        "created_date": "2026-01-15",
+        "updated_by": "system",
+        "updated_date": "2026-01-16",
+        "status": false,
+        "barimt_items": [],
+        "barimt_transactions": [],
+        "barimt_histories": []
+    });
+
+    server
+        .mock("DELETE", "/v2/ebarimt_v3/pay_001")
+        .match_header("authorization", "Bearer mock_access_token")
+        .with_status(200)
+        .with_body(ebarimt_response.to_string())
         .create_async()
         .await;
 
     let config = test_config(&server.url());
     let client = QPayClient::new(config);
 
-    let req = PaymentCancelRequest::default();
-    let result = client.cancel_payment("pay_cancelled", &req).await;
+    let result = client.cancel_ebarimt("pay_001").await;
+    assert!(result.is_ok());
+
+    let ebarimt = result.unwrap();
+    assert_eq!(ebarimt.barimt_status, "CANCELLED");
+    assert!(!ebarimt.status);
+}
+
+// --- Token auto-refresh ---
+
+#[tokio::test]
+async fn test_auto_token_refresh_on_expired() {
+    let mut server = Server::new_async().await;
+    let ts = future_timestamp();
+
+    // Token endpoint called first for initial auth, then later when refresh fails
+    let _token_mock = server
+        .mock("POST", "/v2/auth/token")
+        .with_status(200)
+        .with_body(token_json(ts, ts + 1800))
+        .expect_at_least(1)
+        .create_async()
+        .await;
+
+    let check_response = serde_json::json!({
+        "count": 0,
+        "rows": []
+    });
+
+    server
+        .mock("POST", "/v2/payment/check")
+        .with_status(200)
+        .with_body(check_response.to_string())
+        .create_async()
+        .await;
+
+    let config = test_config(&server.url());
+    let client = QPayClient::new(config);
+
+    // Make a request -- ensure_token should get a new token automatically
+    let req = PaymentCheckRequest {
+        object_type: "INVOICE".to_string(),
+        object_id: "inv_001".to_string(),
+        offset: None,
+    };
+
+    let result = client.check_payment(&req).await;
+    assert!(result.is_ok());
+}
+
+// --- with_http_client ---
+
+#[tokio::test]
+async fn test_with_http_client() {
+    let mut server = Server::new_async().await;
+    let ts = future_timestamp();
+
+    server
+        .mock("POST", "/v2/auth/token")
+        .with_status(200)
+        .with_body(token_json(ts, ts + 1800))
+        .create_async()
+        .await;
+
+    let config = test_config(&server.url());
+    let http = reqwest::Client::builder()
+        .timeout(std::time::Duration::from_secs(10))
+        .build()
+        .unwrap();
+    let client = QPayClient::with_http_client(config, http);
+
+    let result = client.get_token().await;
+    assert!(result.is_ok());
+}
+
+// --- Server error (500) ---
+
+#[tokio::test]
+async fn test_server_error_500() {
+    let mut server = Server::new_async().await;
+    let ts = future_timestamp();
+
+    server
+        .mock("POST", "/v2/auth/token")
+        .with_status(200)
+        .with_body(token_json(ts, ts + 1800))
+        .create_async()
+        .await;
+
+    server
+        .mock("POST", "/v2/invoice")
+        .with_status(500)
+        .with_body("Internal Server Error")
+        .create_async()
+        .await;
+
+    let config = test_config(&server.url());
+    let client = QPayClient::new(config);
+
+    let req = CreateSimpleInvoiceRequest {
+        invoice_code: "CODE".to_string(),
+        sender_invoice_no: "INV-001".to_string(),
+        invoice_receiver_code: "terminal".to_string(),
+        invoice_description: "Test".to_string(),
+        sender_branch_code: None,
+        amount: 1000.0,
+        callback_url: "https://cb.example.com".to_string(),
+    };
+
+    let result = client.create_simple_invoice(&req).await;
     assert!(result.is_err());
 
     let err = result.unwrap_err();
-    let (_, code, _) = qpay::is_qpay_error(&err).unwrap();
-    assert_eq!(code, "PAYMENT_ALREADY_CANCELED");
+    let (status, _, _) = qpay::is_qpay_error(&err).unwrap();
+    assert_eq!(status, 500);
+}
+
+// --- Token error (non-JSON response from auth) ---
+
+#[tokio::test]
+async fn test_get_token_non_json_error() {
+    let mut server = Server::new_async().await;
+
+    server
+        .mock("POST", "/v2/auth/token")
+        .with_status(503)
+        .with_body("Service Unavailable")
+        .create_async()
+        .await;
+
+    let config = test_config(&server.url());
+    let client = QPayClient::new(config);
+
+    let result = client.get_token().await;
+    assert!(result.is_err());
+}
+
+// --- Generic requests ---
+
+#[tokio::test]
+async fn test_request_typed_custom_endpoint() {
+    #[derive(serde::Serialize)]
+    struct Query {
+        page: i32,
+    }
+
+    #[derive(serde::Deserialize)]
+    struct Merchant {
+        id: String,
+        name: String,
+    }
+
+    let mut server = Server::new_async().await;
+    let ts = future_timestamp();
+
+    server
+        .mock("POST", "/v2/auth/token")
+        .with_status(200)
+        .with_body(token_json(ts, ts + 1800))
+        .create_async()
+        .await;
+
+    let mock = server
+        .mock("POST", "/v2/merchant/list")
+        .match_header("authorization", "Bearer mock_access_token")
+        .match_body(Matcher::Json(serde_json::json!({"page": 2})))
+        .with_status(200)
+        .with_body(r#"[{"id":"m_1","name":"Shop"}]"#)
+        .create_async()
+        .await;
+
+    let config = test_config(&server.url());
+    let client = QPayClient::new(config);
+
+    let merchants: Vec<Merchant> = client
+        .request(qpay::Method::POST, "/v2/merchant/list", Some(&Query { page: 2 }))
+        .await
+        .unwrap();
+    assert_eq!(merchants.len(), 1);
+    assert_eq!(merchants[0].id, "m_1");
+    assert_eq!(merchants[0].name, "Shop");
+
+    mock.assert_async().await;
+}
+
+#[tokio::test]
+async fn test_request_raw_returns_json_value() {
+    let mut server = Server::new_async().await;
+    let ts = future_timestamp();
+
+    server
+        .mock("POST", "/v2/auth/token")
+        .with_status(200)
+        .with_body(token_json(ts, ts + 1800))
+        .create_async()
+        .await;
+
+    server
+        .mock("GET", "/v2/aimag_city")
+        .with_status(200)
+        .with_body(r#"{"rows":[{"code":"11","name":"Ulaanbaatar"}]}"#)
+        .create_async()
+        .await;
+
+    let config = test_config(&server.url());
+    let client = QPayClient::new(config);
+
+    // A leading slash is added when missing
+    let value = client
+        .request_raw::<()>(qpay::Method::GET, "v2/aimag_city", None)
+        .await
+        .unwrap();
+    assert_eq!(value["rows"][0]["code"], "11");
+}
+
+#[tokio::test]
+async fn test_request_empty_body_and_errors() {
+    let mut server = Server::new_async().await;
+    let ts = future_timestamp();
+
+    server
+        .mock("POST", "/v2/auth/token")
+        .with_status(200)
+        .with_body(token_json(ts, ts + 1800))
+        .create_async()
+        .await;
+
+    server
+        .mock("DELETE", "/v2/merchant/m_1")
+        .with_status(200)
+        .create_async()
+        .await;
+
+    server
+        .mock("DELETE", "/v2/merchant/m_2")
+        .with_status(404)
+        .with_body(r#"{"error":"MERCHANT_NOTFOUND","message":"Merchant not found"}"#)
+        .create_async()
+        .await;
+
+    let config = test_config(&server.url());
+    let client = QPayClient::new(config);
+
+    client
+        .request::<(), ()>(qpay::Method::DELETE, "/v2/merchant/m_1", None)
+        .await
+        .unwrap();
+
+    let err = client
+        .request_raw::<()>(qpay::Method::DELETE, "/v2/merchant/m_2", None)
+        .await
+        .unwrap_err();
+    let (status, code, _) = qpay::is_qpay_error(&err).unwrap();
+    assert_eq!(status, 404);
+    assert_eq!(code, qpay::error::ERR_MERCHANT_NOT_FOUND);
 }
 
-// --- Payment: refund_payment ---
+// --- Merchant ---
+
+fn merchant_json(id: &str, merchant_type: &str) -> String {
+    serde_json::json!({
+        "id": id,
+        "type": merchant_type,
+        "register_number": "1234567",
+        "name": "Test LLC",
+        "name_eng": "Test LLC",
+        "owner_register_no": "AA12345678",
+        "owner_first_name": "Bat",
+        "owner_last_name": "Dorj",
+        "mcc_code": "5411",
+        "city": "11",
+        "district": "1101",
+        "address": "Peace avenue 1",
+        "phone": "99001122",
+        "email": "info@example.com",
+        "created_date": "2026-01-15 10:00:00"
+    })
+    .to_string()
+}
 
 #[tokio::test]
-async fn test_refund_payment_success() {
+async fn test_create_company_merchant_success() {
     let mut server = Server::new_async().await;
     let ts = future_timestamp();
 
@@ -791,30 +1684,46 @@ async fn test_refund_payment_success() {
         .create_async()
         .await;
 
-    server
-        .mock("DELETE", "/v2/payment/refund/pay_001")
+    let mock = server
+        .mock("POST", "/v2/merchant/company")
         .match_header("authorization", "Bearer mock_access_token")
+        .match_body(Matcher::PartialJson(serde_json::json!({
+            "register_number": "1234567",
+            "mcc_code": "5411"
+        })))
         .with_status(200)
-        .with_body("")
+        .with_body(merchant_json("m_001", "COMPANY"))
         .create_async()
         .await;
 
     let config = test_config(&server.url());
     let client = QPayClient::new(config);
 
-    let req = PaymentRefundRequest {
-        callback_url: Some("https://cb.example.com".to_string()),
-        note: Some("Refund requested".to_string()),
+    let req = CompanyMerchantRequest {
+        owner_register_no: "AA12345678".to_string(),
+        owner_first_name: "Bat".to_string(),
+        owner_last_name: "Dorj".to_string(),
+        register_number: "1234567".to_string(),
+        name: "Test LLC".to_string(),
+        mcc_code: "5411".to_string(),
+        city: "11".to_string(),
+        district: "1101".to_string(),
+        address: "Peace avenue 1".to_string(),
+        phone: "99001122".to_string(),
+        email: "info@example.com".to_string(),
+        ..Default::default()
     };
 
-    let result = client.refund_payment("pay_001", &req).await;
-    assert!(result.is_ok());
-}
+    let merchant = client.create_company_merchant(&req).await.unwrap();
+    assert_eq!(merchant.id, "m_001");
+    assert_eq!(merchant.merchant_type, "COMPANY");
+    assert_eq!(merchant.name.as_deref(), Some("Test LLC"));
 
-// --- Ebarimt: create_ebarimt ---
+    mock.assert_async().await;
+}
 
 #[tokio::test]
-async fn test_create_ebarimt_success() {
+async fn test_create_person_merchant_already_registered() {
     let mut server = Server::new_async().await;
     let ts = future_timestamp();
 
@@ -825,80 +1734,39 @@ async fn test_create_ebarimt_success() {
         .create_async()
         .await;
 
-    let ebarimt_response = serde_json::json!({
-        "id": "eb_001",
-        "ebarimt_by": "system",
-        "g_wallet_id": "w_001",
-        "g_wallet_customer_id": "wc_001",
-        "ebarimt_receiver_type": "83",
-        "ebarimt_receiver": "",
-        "ebarimt_district_code": "23",
-        "ebarimt_bill_type": "1",
-        "g_merchant_id": "m_001",
-        "merchant_branch_code": "br_001",
-        "merchant_terminal_code": null,
-        "merchant_staff_code": null,
-        "merchant_register_no": "1234567",
-        "g_payment_id": "pay_001",
-        "paid_by": "user1",
-        "object_type": "INVOICE",
-        "object_id": "inv_001",
-        "amount": "5000",
-        "vat_amount": "500",
-        "city_tax_amount": "50",
-        "ebarimt_qr_data": "qr_data",
-        "ebarimt_lottery": "AB12345678",
-        "note": null,
-        "barimt_status": "SUCCESS",
-        "barimt_status_date": "2026-01-15",
-        "ebarimt_sent_email": null,
-        "ebarimt_receiver_phone": "99001122",
-        "tax_type": "1",
-        "merchant_tin": null,
-        "ebarimt_receipt_id": null,
-        "created_by": "system",
-        "created_date": "2026-01-15",
-        "updated_by": "system",
-        "updated_date": "2026-01-15",
-        "status": true,
-        "barimt_items": [],
-        "barimt_transactions": [],
-        "barimt_histories": []
-    });
-
     server
-        .mock("POST", "/v2/ebarimt_v3/create")
-        .match_header("authorization", "Bearer mock_access_token")
-        .with_status(200)
-        .with_body(ebarimt_response.to_string())
+        .mock("POST", "/v2/merchant/person")
+        .with_status(400)
+        .with_body(
+            r#"{"error":"MERCHANT_ALREADY_REGISTERED","message":"Merchant already registered"}"#,
+        )
         .create_async()
         .await;
 
     let config = test_config(&server.url());
     let client = QPayClient::new(config);
 
-    let req = CreateEbarimtRequest {
-        payment_id: "pay_001".to_string(),
-        ebarimt_receiver_type: "83".to_string(),
-        ebarimt_receiver: None,
-        district_code: Some("23".to_string()),
-        classification_code: None,
+    let req = PersonMerchantRequest {
+        register_number: "AA12345678".to_string(),
+        first_name: "Bat".to_string(),
+        last_name: "Dorj".to_string(),
+        mcc_code: "5411".to_string(),
+        city: "11".to_string(),
+        district: "1101".to_string(),
+        address: "Peace avenue 1".to_string(),
+        phone: "99001122".to_string(),
+        email: "bat@example.com".to_string(),
+        ..Default::default()
     };
 
-    let result = client.create_ebarimt(&req).await;
-    assert!(result.is_ok());
-
-    let ebarimt = result.unwrap();
-    assert_eq!(ebarimt.id, "eb_001");
-    assert_eq!(ebarimt.barimt_status, "SUCCESS");
-    assert_eq!(ebarimt.ebarimt_lottery, "AB12345678");
-    assert!(ebarimt.status);
+    let err = client.create_person_merchant(&req).await.unwrap_err();
+    let (status, code, _) = qpay::is_qpay_error(&err).unwrap();
+    assert_eq!(status, 400);
+    assert_eq!(code, qpay::error::ERR_MERCHANT_ALREADY_REGISTERED);
 }
 
-// --- Ebarimt: cancel_ebarimt ---
-
 #[tokio::test]
-async fn test_cancel_ebarimt_success() {
+async fn test_get_and_update_merchant() {
     let mut server = Server::new_async().await;
     let ts = future_timestamp();
 
@@ -909,112 +1777,96 @@ async fn test_cancel_ebarimt_success() {
         .create_async()
         .await;
 
-    let ebarimt_response = serde_json::json!({
-        "id": "eb_001",
-        "ebarimt_by": "system",
-        "g_wallet_id": "w_001",
-        "g_wallet_customer_id": "wc_001",
-        "ebarimt_receiver_type": "83",
-        "ebarimt_receiver": "",
-        "ebarimt_district_code": "23",
-        "ebarimt_bill_type": "1",
-        "g_merchant_id": "m_001",
-        "merchant_branch_code": "br_001",
-        "merchant_terminal_code": null,
-        "merchant_staff_code": null,
-        "merchant_register_no": "1234567",
-        "g_payment_id": "pay_001",
-        "paid_by": "user1",
-        "object_type": "INVOICE",
-        "object_id": "inv_001",
-        "amount": "5000",
-        "vat_amount": "500",
-        "city_tax_amount": "50",
-        "ebarimt_qr_data": "qr_data",
-        "ebarimt_lottery": "AB12345678",
-        "note": null,
-        "barimt_status": "CANCELLED",
-        "barimt_status_date": "2026-01-16",
-        "ebarimt_sent_email": null,
-        "ebarimt_receiver_phone": "99001122",
-        "tax_type": "1",
-        "merchant_tin": null,
-        "ebarimt_receipt_id": null,
-        "created_by": "system",
-        "created_date": "2026-01-15",
-        "updated_by": "system",
-        "updated_date": "2026-01-16",
-        "status": false,
-        "barimt_items": [],
-        "barimt_transactions": [],
-        "barimt_histories": []
-    });
+    let get_mock = server
+        .mock("GET", "/v2/merchant/m_001")
+        .with_status(200)
+        .with_body(merchant_json("m_001", "COMPANY"))
+        .create_async()
+        .await;
 
-    server
-        .mock("DELETE", "/v2/ebarimt_v3/pay_001")
-        .match_header("authorization", "Bearer mock_access_token")
+    let update_mock = server
+        .mock("PUT", "/v2/merchant/company/m_001")
+        .match_body(Matcher::PartialJson(serde_json::json!({
+            "address": "New address"
+        })))
         .with_status(200)
-        .with_body(ebarimt_response.to_string())
+        .with_body(merchant_json("m_001", "COMPANY"))
         .create_async()
         .await;
 
     let config = test_config(&server.url());
     let client = QPayClient::new(config);
 
-    let result = client.cancel_ebarimt("pay_001").await;
-    assert!(result.is_ok());
+    let merchant = client.get_merchant("m_001").await.unwrap();
+    assert_eq!(merchant.mcc_code, "5411");
 
-    let ebarimt = result.unwrap();
-    assert_eq!(ebarimt.barimt_status, "CANCELLED");
-    assert!(!ebarimt.status);
-}
+    let req = CompanyMerchantRequest {
+        register_number: merchant.register_number.clone(),
+        address: "New address".to_string(),
+        ..Default::default()
+    };
+    let updated = client.update_company_merchant("m_001", &req).await.unwrap();
+    assert_eq!(updated.id, "m_001");
 
-// --- Token auto-refresh ---
+    get_mock.assert_async().await;
+    update_mock.assert_async().await;
+}
 
 #[tokio::test]
-async fn test_auto_token_refresh_on_expired() {
+async fn test_list_and_delete_merchants() {
     let mut server = Server::new_async().await;
     let ts = future_timestamp();
 
-    // Token endpoint called first for initial auth, then later when refresh fails
-    let _token_mock = server
+    server
         .mock("POST", "/v2/auth/token")
         .with_status(200)
         .with_body(token_json(ts, ts + 1800))
-        .expect_at_least(1)
         .create_async()
         .await;
 
-    let check_response = serde_json::json!({
-        "count": 0,
-        "rows": []
-    });
+    let list_body = format!(
+        r#"{{"count": 2, "rows": [{}, {}]}}"#,
+        merchant_json("m_001", "COMPANY"),
+        merchant_json("m_002", "PERSON")
+    );
+
+    let list_mock = server
+        .mock("POST", "/v2/merchant/list")
+        .match_body(Matcher::Json(serde_json::json!({
+            "offset": {"page_number": 1, "page_limit": 20}
+        })))
+        .with_status(200)
+        .with_body(list_body)
+        .create_async()
+        .await;
 
-    server
-        .mock("POST", "/v2/payment/check")
+    let delete_mock = server
+        .mock("DELETE", "/v2/merchant/m_002")
         .with_status(200)
-        .with_body(check_response.to_string())
         .create_async()
         .await;
 
     let config = test_config(&server.url());
     let client = QPayClient::new(config);
 
-    // Make a request -- ensure_token should get a new token automatically
-    let req = PaymentCheckRequest {
-        object_type: "INVOICE".to_string(),
-        object_id: "inv_001".to_string(),
-        offset: None,
+    let req = MerchantListRequest {
+        offset: Offset {
+            page_number: 1,
+            page_limit: 20,
+        },
     };
+    let list = client.list_merchants(&req).await.unwrap();
+    assert_eq!(list.count, 2);
+    assert_eq!(list.rows[1].merchant_type, "PERSON");
 
-    let result = client.check_payment(&req).await;
-    assert!(result.is_ok());
-}
+    client.delete_merchant("m_002").await.unwrap();
 
-// --- with_http_client ---
+    list_mock.assert_async().await;
+    delete_mock.assert_async().await;
+}
 
 #[tokio::test]
-async fn test_with_http_client() {
+async fn test_add_merchant_bank_account() {
     let mut server = Server::new_async().await;
     let ts = future_timestamp();
 
@@ -1025,21 +1877,53 @@ async fn test_with_http_client() {
         .create_async()
         .await;
 
+    let mock = server
+        .mock("POST", "/v2/merchant/m_001/bank_account")
+        .match_body(Matcher::Json(serde_json::json!({
+            "account_bank_code": "050000",
+            "account_number": "5000123456",
+            "account_name": "Test LLC",
+            "is_default": true
+        })))
+        .with_status(200)
+        .with_body(
+            serde_json::json!({
+                "id": "acc_001",
+                "account_bank_code": "050000",
+                "account_number": "5000123456",
+                "account_name": "Test LLC",
+                "iban_number": null,
+                "is_default": true
+            })
+            .to_string(),
+        )
+        .create_async()
+        .await;
+
     let config = test_config(&server.url());
-    let http = reqwest::Client::builder()
-        .timeout(std::time::Duration::from_secs(10))
-        .build()
+    let client = QPayClient::new(config);
+
+    let req = MerchantBankAccountRequest {
+        account_bank_code: "050000".to_string(),
+        account_number: "5000123456".to_string(),
+        account_name: "Test LLC".to_string(),
+        iban_number: None,
+        is_default: true,
+    };
+    let account = client
+        .add_merchant_bank_account("m_001", &req)
+        .await
         .unwrap();
-    let client = QPayClient::with_http_client(config, http);
+    assert_eq!(account.id, "acc_001");
+    assert!(account.is_default);
 
-    let result = client.get_token().await;
-    assert!(result.is_ok());
+    mock.assert_async().await;
 }
 
-// --- Server error (500) ---
+// --- Reference data ---
 
 #[tokio::test]
-async fn test_server_error_500() {
+async fn test_get_banks_is_cached() {
     let mut server = Server::new_async().await;
     let ts = future_timestamp();
 
@@ -1050,50 +1934,124 @@ async fn test_server_error_500() {
         .create_async()
         .await;
 
-    server
-        .mock("POST", "/v2/invoice")
-        .with_status(500)
-        .with_body("Internal Server Error")
+    let mock = server
+        .mock("GET", "/v2/banks")
+        .match_header("authorization", "Bearer mock_access_token")
+        .with_status(200)
+        .with_body(
+            r#"[
+                {"bank_code": "050000", "name": "Хаан банк", "name_eng": "Khan Bank"},
+                {"bank_code": "040000", "name": "Төрийн банк", "name_eng": "State Bank"}
+            ]"#,
+        )
+        .expect(1)
         .create_async()
         .await;
 
     let config = test_config(&server.url());
     let client = QPayClient::new(config);
 
-    let req = CreateSimpleInvoiceRequest {
-        invoice_code: "CODE".to_string(),
-        sender_invoice_no: "INV-001".to_string(),
-        invoice_receiver_code: "terminal".to_string(),
-        invoice_description: "Test".to_string(),
-        sender_branch_code: None,
-        amount: 1000.0,
-        callback_url: "https://cb.example.com".to_string(),
-    };
+    let banks = client.get_banks().await.unwrap();
+    assert_eq!(banks.len(), 2);
+    assert_eq!(banks[0].bank_code, "050000");
+    assert_eq!(banks[0].name_eng.as_deref(), Some("Khan Bank"));
 
-    let result = client.create_simple_invoice(&req).await;
-    assert!(result.is_err());
+    // Second call is served from the cache
+    let banks = client.get_banks().await.unwrap();
+    assert_eq!(banks.len(), 2);
 
-    let err = result.unwrap_err();
-    let (status, _, _) = qpay::is_qpay_error(&err).unwrap();
-    assert_eq!(status, 500);
+    mock.assert_async().await;
 }
 
-// --- Token error (non-JSON response from auth) ---
+#[tokio::test]
+async fn test_reference_cache_ttl_and_clear() {
+    let mut server = Server::new_async().await;
+    let ts = future_timestamp();
+
+    server
+        .mock("POST", "/v2/auth/token")
+        .with_status(200)
+        .with_body(token_json(ts, ts + 1800))
+        .create_async()
+        .await;
+
+    let cities_mock = server
+        .mock("GET", "/v2/aimag_city")
+        .with_status(200)
+        .with_body(r#"[{"code": "11", "name": "Улаанбаатар"}]"#)
+        .expect(2)
+        .create_async()
+        .await;
+
+    let districts_mock = server
+        .mock("GET", "/v2/aimag_city/11")
+        .with_status(200)
+        .with_body(r#"[{"code": "1101", "name": "Баянзүрх", "city_code": "11"}]"#)
+        .expect(2)
+        .create_async()
+        .await;
+
+    let mcc_mock = server
+        .mock("GET", "/v2/mcc")
+        .with_status(200)
+        .with_body(r#"[{"code": "5411", "name": "Хүнсний дэлгүүр"}]"#)
+        .expect(1)
+        .create_async()
+        .await;
+
+    // A zero TTL disables caching
+    let config = test_config(&server.url()).with_reference_data_ttl(std::time::Duration::ZERO);
+    let client = QPayClient::new(config);
+    client.get_cities().await.unwrap();
+    let cities = client.get_cities().await.unwrap();
+    assert_eq!(cities[0].code, "11");
+    cities_mock.assert_async().await;
+
+    let client = QPayClient::new(test_config(&server.url()));
+    let districts = client.get_districts("11").await.unwrap();
+    assert_eq!(districts[0].code, "1101");
+    assert_eq!(districts[0].city_code.as_deref(), Some("11"));
+    client.get_mcc_codes().await.unwrap();
+
+    // Clearing the cache forces a refetch
+    client.clear_reference_cache().await;
+    client.get_districts("11").await.unwrap();
+    districts_mock.assert_async().await;
+    mcc_mock.assert_async().await;
+}
 
 #[tokio::test]
-async fn test_get_token_non_json_error() {
+async fn test_with_raw_reference_data_refreshes_cache() {
     let mut server = Server::new_async().await;
+    let ts = future_timestamp();
 
     server
         .mock("POST", "/v2/auth/token")
-        .with_status(503)
-        .with_body("Service Unavailable")
+        .with_status(200)
+        .with_body(token_json(ts, ts + 1800))
+        .create_async()
+        .await;
+
+    let banks_mock = server
+        .mock("GET", "/v2/banks")
+        .with_status(200)
+        .with_header("x-request-id", "req-banks")
+        .with_body(r#"[{"bank_code": "050000", "name": "Хаан банк"}]"#)
+        .expect(2)
         .create_async()
         .await;
 
     let config = test_config(&server.url());
     let client = QPayClient::new(config);
 
-    let result = client.get_token().await;
-    assert!(result.is_err());
+    // Raw calls always reach QPay
+    client.with_raw().get_banks().await.unwrap();
+    let resp = client.with_raw().get_banks().await.unwrap();
+    assert_eq!(resp.header("x-request-id"), Some("req-banks"));
+    assert_eq!(resp.value[0].bank_code, "050000");
+
+    // ...and leave the decoded value in the cache
+    let banks = client.get_banks().await.unwrap();
+    assert_eq!(banks[0].name, "Хаан банк");
+    banks_mock.assert_async().await;
 }
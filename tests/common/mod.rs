@@ -0,0 +1,50 @@
+//! Helpers shared by the integration tests.
+
+#![allow(dead_code)]
+
+use mockito::{Server, ServerGuard};
+use qpay::QPayConfig;
+
+pub fn test_config(server_url: &str) -> QPayConfig {
+    QPayConfig::new(
+        server_url,
+        "test_user",
+        "test_pass",
+        "TEST_CODE",
+        "https://example.com/callback",
+    )
+}
+
+pub fn token_json(expires_in: i64, refresh_expires_in: i64) -> String {
+    serde_json::json!({
+        "token_type": "Bearer",
+        "refresh_expires_in": refresh_expires_in,
+        "refresh_token": "mock_refresh_token",
+        "access_token": "mock_access_token",
+        "expires_in": expires_in,
+        "scope": "default",
+        "not-before-policy": "0",
+        "session_state": "mock_session"
+    })
+    .to_string()
+}
+
+pub fn future_timestamp() -> i64 {
+    std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .unwrap()
+        .as_secs() as i64
+        + 3600
+}
+
+pub async fn server_with_token() -> ServerGuard {
+    let mut server = Server::new_async().await;
+    let ts = future_timestamp();
+    server
+        .mock("POST", "/v2/auth/token")
+        .with_status(200)
+        .with_body(token_json(ts, ts + 1800))
+        .create_async()
+        .await;
+    server
+}
@@ -1,4 +1,4 @@
-use qpay::{QPayConfig, QPayError};
+use qpay::{QPayConfig, QPayError, Strictness};
 use serial_test::serial;
 
 #[test]
@@ -220,3 +220,83 @@ fn test_config_from_env_error_is_config_variant() {
         _ => panic!("expected QPayError::Config, got: {:?}", err),
     }
 }
+
+#[test]
+fn test_config_default_strictness_is_lenient() {
+    let config = QPayConfig::new("url", "user", "pass", "code", "callback");
+    assert_eq!(config.strictness, Strictness::Lenient);
+
+    let config = config.with_strictness(Strictness::Strict);
+    assert_eq!(config.strictness, Strictness::Strict);
+}
+
+#[test]
+fn test_strictness_from_str() {
+    assert_eq!("strict".parse::<Strictness>().unwrap(), Strictness::Strict);
+    assert_eq!(
+        "LENIENT".parse::<Strictness>().unwrap(),
+        Strictness::Lenient
+    );
+    assert!(matches!(
+        "loose".parse::<Strictness>(),
+        Err(QPayError::Config(_))
+    ));
+}
+
+#[test]
+#[serial]
+fn test_config_from_env_strictness() {
+    std::env::set_var("QPAY_BASE_URL", "url");
+    std::env::set_var("QPAY_USERNAME", "user");
+    std::env::set_var("QPAY_PASSWORD", "pass");
+    std::env::set_var("QPAY_INVOICE_CODE", "code");
+    std::env::set_var("QPAY_CALLBACK_URL", "cb");
+    std::env::set_var("QPAY_STRICTNESS", "strict");
+
+    let config = QPayConfig::from_env().expect("should load from env");
+    assert_eq!(config.strictness, Strictness::Strict);
+
+    // Clean up
+    std::env::remove_var("QPAY_BASE_URL");
+    std::env::remove_var("QPAY_USERNAME");
+    std::env::remove_var("QPAY_PASSWORD");
+    std::env::remove_var("QPAY_INVOICE_CODE");
+    std::env::remove_var("QPAY_CALLBACK_URL");
+    std::env::remove_var("QPAY_STRICTNESS");
+}
+
+#[test]
+#[serial]
+fn test_config_from_env_callback_secret() {
+    std::env::set_var("QPAY_BASE_URL", "url");
+    std::env::set_var("QPAY_USERNAME", "user");
+    std::env::set_var("QPAY_PASSWORD", "pass");
+    std::env::set_var("QPAY_INVOICE_CODE", "code");
+    std::env::set_var("QPAY_CALLBACK_URL", "cb");
+    std::env::set_var("QPAY_CALLBACK_SECRET", "s3cret");
+
+    let config = QPayConfig::from_env().expect("should load from env");
+    assert_eq!(config.callback_secret.as_deref(), Some("s3cret"));
+    assert_eq!(config.callback_url_ttl, None);
+
+    // Clean up
+    std::env::remove_var("QPAY_BASE_URL");
+    std::env::remove_var("QPAY_USERNAME");
+    std::env::remove_var("QPAY_PASSWORD");
+    std::env::remove_var("QPAY_INVOICE_CODE");
+    std::env::remove_var("QPAY_CALLBACK_URL");
+    std::env::remove_var("QPAY_CALLBACK_SECRET");
+
+    let config = QPayConfig::new("url", "user", "pass", "code", "callback");
+    assert!(config.callback_secret.is_none());
+}
+
+#[test]
+fn test_config_debug_redacts_secrets() {
+    let config = QPayConfig::new("url", "user", "hunter2", "code", "callback")
+        .with_callback_secret("s3cret");
+    let debug = format!("{:?}", config);
+    assert!(debug.contains("user"));
+    assert!(!debug.contains("hunter2"));
+    assert!(!debug.contains("s3cret"));
+}
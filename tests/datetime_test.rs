@@ -0,0 +1,130 @@
+#![cfg(feature = "chrono")]
+
+use chrono::{NaiveDate, TimeZone, Timelike, Utc};
+use qpay::datetime::*;
+use qpay::models::*;
+
+#[test]
+fn test_parse_rfc3339_keeps_offset() {
+    let dt = parse_datetime("2026-01-15T10:30:00.000Z").unwrap();
+    assert_eq!(dt.offset().local_minus_utc(), 0);
+    assert_eq!(dt.hour(), 10);
+}
+
+#[test]
+fn test_parse_naive_uses_ulaanbaatar_offset() {
+    let dt = parse_datetime("2026-01-15 10:30:00").unwrap();
+    assert_eq!(dt.offset().local_minus_utc(), ULAANBAATAR_OFFSET_SECONDS);
+    assert_eq!(dt.with_timezone(&Utc).hour(), 2);
+
+    let dt = parse_datetime("2026-01-15T10:30:00.123").unwrap();
+    assert_eq!(dt.offset().local_minus_utc(), ULAANBAATAR_OFFSET_SECONDS);
+    assert_eq!(dt.minute(), 30);
+}
+
+#[test]
+fn test_parse_with_explicit_offset() {
+    let dt = parse_datetime("2026-01-15 10:30:00+09:00").unwrap();
+    assert_eq!(dt.offset().local_minus_utc(), 9 * 3600);
+}
+
+#[test]
+fn test_parse_date_only() {
+    let dt = parse_datetime("2026-01-15").unwrap();
+    assert_eq!(dt.hour(), 0);
+    assert_eq!(dt.offset().local_minus_utc(), ULAANBAATAR_OFFSET_SECONDS);
+    assert_eq!(
+        parse_date("2026/01/15"),
+        NaiveDate::from_ymd_opt(2026, 1, 15)
+    );
+}
+
+#[test]
+fn test_parse_date_converts_to_local_day() {
+    // 20:00 UTC is already the next day in Ulaanbaatar
+    assert_eq!(
+        parse_date("2026-01-15T20:00:00Z"),
+        NaiveDate::from_ymd_opt(2026, 1, 16)
+    );
+}
+
+#[test]
+fn test_parse_invalid() {
+    assert!(parse_datetime("").is_none());
+    assert!(parse_datetime("   ").is_none());
+    assert!(parse_datetime("not a date").is_none());
+}
+
+#[test]
+fn test_format_datetime_converts_to_ulaanbaatar() {
+    let dt = Utc.with_ymd_and_hms(2026, 1, 31, 16, 0, 0).unwrap();
+    assert_eq!(format_datetime(&dt), "2026-02-01 00:00:00");
+}
+
+#[test]
+fn test_payment_list_request_with_date_range() {
+    let start = ulaanbaatar().with_ymd_and_hms(2026, 1, 1, 0, 0, 0).unwrap();
+    let end = ulaanbaatar()
+        .with_ymd_and_hms(2026, 1, 31, 23, 59, 59)
+        .unwrap();
+
+    let req = PaymentListRequest {
+        object_type: "INVOICE".to_string(),
+        object_id: "inv_001".to_string(),
+        start_date: String::new(),
+        end_date: String::new(),
+        offset: Offset {
+            page_number: 1,
+            page_limit: 20,
+        },
+    }
+    .with_date_range(&start, &end);
+
+    assert_eq!(req.start_date, "2026-01-01 00:00:00");
+    assert_eq!(req.end_date, "2026-01-31 23:59:59");
+    assert_eq!(req.start_date_parsed(), Some(start));
+    assert_eq!(req.end_date_parsed(), Some(end));
+}
+
+#[test]
+fn test_payment_detail_parsed_dates() {
+    let json = r#"{
+        "payment_id": "pay_789",
+        "payment_status": "PAID",
+        "payment_fee": "100",
+        "payment_amount": "10000",
+        "payment_currency": "MNT",
+        "payment_date": "2026-01-15T08:00:00.000Z",
+        "payment_wallet": "qPay",
+        "transaction_type": "P2P",
+        "object_type": "INVOICE",
+        "object_id": "inv_001",
+        "next_payment_date": "2026-02-15",
+        "next_payment_datetime": null
+    }"#;
+
+    let detail: PaymentDetail = serde_json::from_str(json).unwrap();
+    let paid = detail.payment_date_parsed().unwrap();
+    assert_eq!(paid.with_timezone(&Utc).hour(), 8);
+
+    let next = detail.next_payment_parsed().unwrap();
+    assert_eq!(
+        next.date_naive(),
+        NaiveDate::from_ymd_opt(2026, 2, 15).unwrap()
+    );
+}
+
+#[test]
+fn test_card_transaction_date_fallback() {
+    let json = r#"{
+        "card_type": "VISA",
+        "is_cross_border": false,
+        "date": "2026-01-15 12:00:00",
+        "settlement_status": "SETTLED",
+        "settlement_status_date": "2026-01-16 09:00:00"
+    }"#;
+
+    let tx: CardTransaction = serde_json::from_str(json).unwrap();
+    assert_eq!(tx.transaction_date_parsed().unwrap().hour(), 12);
+    assert_eq!(tx.settlement_status_date_parsed().unwrap().hour(), 9);
+}
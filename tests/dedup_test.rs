@@ -0,0 +1,86 @@
+use std::path::PathBuf;
+use std::time::Duration;
+
+use qpay::dedup::{CallbackDedupStore, FileDedupStore, MemoryDedupStore};
+
+fn temp_path(name: &str) -> PathBuf {
+    let path = std::env::temp_dir().join(format!("qpay-{}-{}.log", name, std::process::id()));
+    let _ = std::fs::remove_file(&path);
+    path
+}
+
+#[test]
+fn test_memory_store_claims_once() {
+    let store = MemoryDedupStore::default();
+
+    assert!(store.claim("pay_1").unwrap());
+    assert!(!store.claim("pay_1").unwrap());
+    assert!(store.contains("pay_1").unwrap());
+    assert!(!store.contains("pay_2").unwrap());
+
+    store.release("pay_1").unwrap();
+    assert!(!store.contains("pay_1").unwrap());
+    assert!(store.claim("pay_1").unwrap());
+}
+
+#[test]
+fn test_memory_store_ttl() {
+    let store = MemoryDedupStore::new(Duration::from_millis(20));
+    assert!(store.claim("pay_1").unwrap());
+
+    std::thread::sleep(Duration::from_millis(40));
+    assert!(!store.contains("pay_1").unwrap());
+    assert!(store.claim("pay_1").unwrap());
+}
+
+#[test]
+fn test_file_store_survives_reopen() {
+    let path = temp_path("dedup-reopen");
+    let ttl = Duration::from_secs(3600);
+
+    let store = FileDedupStore::open(&path, ttl).unwrap();
+    assert!(store.claim("pay_1").unwrap());
+    assert!(store.claim("pay_2").unwrap());
+    store.release("pay_2").unwrap();
+    drop(store);
+
+    let store = FileDedupStore::open(&path, ttl).unwrap();
+    assert!(store.contains("pay_1").unwrap());
+    assert!(!store.claim("pay_1").unwrap());
+    assert!(!store.contains("pay_2").unwrap());
+    assert!(store.claim("pay_2").unwrap());
+
+    // Reopening compacts the log to one line per live claim
+    drop(store);
+    let _ = FileDedupStore::open(&path, ttl).unwrap();
+    assert_eq!(std::fs::read_to_string(&path).unwrap().lines().count(), 2);
+
+    std::fs::remove_file(&path).unwrap();
+}
+
+#[test]
+fn test_file_store_drops_expired_entries() {
+    let path = temp_path("dedup-expired");
+    std::fs::write(&path, "1 pay_old\n").unwrap();
+
+    let store = FileDedupStore::open(&path, Duration::from_secs(3600)).unwrap();
+    assert!(!store.contains("pay_old").unwrap());
+    assert!(store.claim("pay_old").unwrap());
+
+    std::fs::remove_file(&path).unwrap();
+}
+
+#[test]
+fn test_file_store_keeps_sibling_files() {
+    let path = temp_path("dedup-sibling");
+    // Compaction must not write over a file that only shares the stem
+    let sibling = path.with_extension("tmp");
+    std::fs::write(&sibling, "not ours").unwrap();
+
+    let store = FileDedupStore::open(&path, Duration::from_secs(3600)).unwrap();
+    assert!(store.claim("pay_1").unwrap());
+    assert_eq!(std::fs::read_to_string(&sibling).unwrap(), "not ours");
+
+    std::fs::remove_file(&path).unwrap();
+    std::fs::remove_file(&sibling).unwrap();
+}
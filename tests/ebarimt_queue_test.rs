@@ -0,0 +1,368 @@
+mod common;
+
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::Duration;
+
+use qpay::callback::{
+    CallbackHandler, CallbackOutcome, CallbackProcessor, HandlerError, VerifiedCallback,
+};
+use qpay::ebarimt_queue::{
+    EbarimtAttempt, EbarimtFailure, EbarimtQueue, EbarimtQueueStore, FileEbarimtQueueStore,
+    QueuedEbarimt,
+};
+use qpay::issuance::{EbarimtCallbackHandler, EbarimtIssuer};
+use qpay::models::{CreateEbarimtRequest, EbarimtResponse};
+use qpay::validation::ValidationError;
+use qpay::{QPayClient, QPayError};
+
+use common::{server_with_token, test_config};
+
+fn temp_path(name: &str) -> PathBuf {
+    let path = std::env::temp_dir().join(format!("qpay-{}-{}.log", name, std::process::id()));
+    let _ = std::fs::remove_file(&path);
+    path
+}
+
+fn request(payment_id: &str) -> CreateEbarimtRequest {
+    CreateEbarimtRequest {
+        payment_id: payment_id.to_string(),
+        ebarimt_receiver_type: "CITIZEN".to_string(),
+        ebarimt_receiver: None,
+        district_code: None,
+        classification_code: None,
+    }
+}
+
+fn ebarimt_json(payment_id: &str) -> String {
+    serde_json::to_string(&EbarimtResponse {
+        id: format!("eb_{}", payment_id),
+        g_payment_id: payment_id.to_string(),
+        barimt_status: "REGISTERED".to_string(),
+        ..Default::default()
+    })
+    .unwrap()
+}
+
+fn api_error(status: u16, code: &str) -> QPayError {
+    QPayError::Api {
+        status_code: status,
+        code: code.to_string(),
+        message: String::new(),
+        raw_body: String::new(),
+    }
+}
+
+#[test]
+fn test_classify_errors() {
+    assert_eq!(
+        EbarimtFailure::classify(&api_error(503, "SERVICE_UNAVAILABLE")),
+        EbarimtFailure::Retryable
+    );
+    assert_eq!(
+        EbarimtFailure::classify(&api_error(400, "EBARIMT_NOT_REGISTERED")),
+        EbarimtFailure::Retryable
+    );
+    assert_eq!(
+        EbarimtFailure::classify(&api_error(400, "PAYMENT_NOT_PAID")),
+        EbarimtFailure::Retryable
+    );
+    assert_eq!(
+        EbarimtFailure::classify(&api_error(404, "PAYMENT_NOTFOUND")),
+        EbarimtFailure::Permanent
+    );
+    assert_eq!(
+        EbarimtFailure::classify(&api_error(400, "CUSTOMER_REGISTER_INVALID")),
+        EbarimtFailure::Permanent
+    );
+    assert_eq!(
+        EbarimtFailure::classify(&QPayError::Validation(
+            ValidationError::RegisterNumberFormat("ABC".to_string())
+        )),
+        EbarimtFailure::Permanent
+    );
+}
+
+#[test]
+fn test_file_store_survives_restart() {
+    let path = temp_path("ebarimt-queue");
+
+    let store = FileEbarimtQueueStore::open(&path).unwrap();
+    store.put(&QueuedEbarimt::new(request("pay_1"))).unwrap();
+    store.put(&QueuedEbarimt::new(request("pay_2"))).unwrap();
+    let mut entry = store.get("pay_1").unwrap().unwrap();
+    entry.attempts = 2;
+    entry.dead_letter = true;
+    store.put(&entry).unwrap();
+    store.remove("pay_2").unwrap();
+    drop(store);
+
+    let store = FileEbarimtQueueStore::open(&path).unwrap();
+    let entries = store.list().unwrap();
+    assert_eq!(entries.len(), 1);
+    assert_eq!(entries[0].payment_id(), "pay_1");
+    assert_eq!(entries[0].attempts, 2);
+    assert!(entries[0].dead_letter);
+
+    // Reopening compacts the log to one line per entry
+    assert_eq!(std::fs::read_to_string(&path).unwrap().lines().count(), 1);
+
+    std::fs::remove_file(&path).unwrap();
+}
+
+#[tokio::test]
+async fn test_submit_issues_immediately() {
+    let mut server = server_with_token().await;
+    let created = server
+        .mock("POST", "/v2/ebarimt_v3/create")
+        .with_status(200)
+        .with_body(ebarimt_json("pay_1"))
+        .expect(1)
+        .create_async()
+        .await;
+
+    let client = Arc::new(QPayClient::new(test_config(&server.url())));
+    let queue = EbarimtQueue::new(client);
+    let attempt = queue.submit(request("pay_1")).await.unwrap();
+    assert!(
+        matches!(attempt, EbarimtAttempt::Issued { ref receipt, .. } if receipt.id == "eb_pay_1")
+    );
+    assert!(queue.store().list().unwrap().is_empty());
+    created.assert_async().await;
+}
+
+#[tokio::test]
+async fn test_transient_failures_back_off_then_dead_letter() {
+    let mut server = server_with_token().await;
+    let failed = server
+        .mock("POST", "/v2/ebarimt_v3/create")
+        .with_status(503)
+        .with_body(r#"{"error":"SERVICE_UNAVAILABLE","message":"try again"}"#)
+        .expect(3)
+        .create_async()
+        .await;
+
+    let client = Arc::new(QPayClient::new(test_config(&server.url())));
+    let queue = EbarimtQueue::new(client)
+        .with_max_attempts(3)
+        .with_backoff(Duration::ZERO, Duration::ZERO);
+
+    let attempt = queue.submit(request("pay_1")).await.unwrap();
+    assert!(matches!(attempt, EbarimtAttempt::Retrying(ref e) if e.attempts == 1));
+
+    let attempts = queue.process_due().await.unwrap();
+    assert!(matches!(attempts[..], [EbarimtAttempt::Retrying(ref e)] if e.attempts == 2));
+
+    let attempts = queue.process_due().await.unwrap();
+    assert!(matches!(attempts[..], [EbarimtAttempt::DeadLettered(ref e)] if e.attempts == 3));
+
+    // Dead letters are not retried automatically
+    assert!(queue.process_due().await.unwrap().is_empty());
+    assert!(queue.pending().await.unwrap().is_empty());
+    let dead = queue.dead_letters().await.unwrap();
+    assert_eq!(dead.len(), 1);
+    assert!(dead[0]
+        .last_error
+        .as_deref()
+        .unwrap()
+        .contains("SERVICE_UNAVAILABLE"));
+    failed.assert_async().await;
+}
+
+#[tokio::test]
+async fn test_backoff_delays_next_attempt() {
+    let mut server = server_with_token().await;
+    server
+        .mock("POST", "/v2/ebarimt_v3/create")
+        .with_status(502)
+        .expect(1)
+        .create_async()
+        .await;
+
+    let client = Arc::new(QPayClient::new(test_config(&server.url())));
+    let queue =
+        EbarimtQueue::new(client).with_backoff(Duration::from_secs(60), Duration::from_secs(3600));
+
+    let attempt = queue.submit(request("pay_1")).await.unwrap();
+    let EbarimtAttempt::Retrying(entry) = attempt else {
+        panic!("expected a retry");
+    };
+    assert!(entry.next_attempt_at >= entry.enqueued_at + 60);
+
+    // Not due yet
+    assert!(queue.process_due().await.unwrap().is_empty());
+    assert_eq!(queue.pending().await.unwrap().len(), 1);
+}
+
+#[tokio::test]
+async fn test_permanent_failure_dead_letters_and_can_be_retried_or_dropped() {
+    let mut server = server_with_token().await;
+    let rejected = server
+        .mock("POST", "/v2/ebarimt_v3/create")
+        .with_status(400)
+        .with_body(r#"{"error":"CUSTOMER_REGISTER_INVALID","message":"invalid"}"#)
+        .expect(2)
+        .create_async()
+        .await;
+
+    let client = Arc::new(QPayClient::new(test_config(&server.url())));
+    let queue = EbarimtQueue::new(client);
+
+    for payment_id in ["pay_1", "pay_2"] {
+        let attempt = queue.submit(request(payment_id)).await.unwrap();
+        assert!(matches!(attempt, EbarimtAttempt::DeadLettered(ref e) if e.attempts == 1));
+    }
+    rejected.assert_async().await;
+
+    let created = server
+        .mock("POST", "/v2/ebarimt_v3/create")
+        .with_status(200)
+        .with_body(ebarimt_json("pay_1"))
+        .expect(1)
+        .create_async()
+        .await;
+
+    let attempt = queue.retry_dead_letter("pay_1").await.unwrap().unwrap();
+    assert!(
+        matches!(attempt, EbarimtAttempt::Issued { ref payment_id, .. } if payment_id == "pay_1")
+    );
+    assert!(queue.retry_dead_letter("pay_1").await.unwrap().is_none());
+
+    let dropped = queue.drop_dead_letter("pay_2").await.unwrap().unwrap();
+    assert_eq!(dropped.payment_id(), "pay_2");
+    assert!(queue.dead_letters().await.unwrap().is_empty());
+    created.assert_async().await;
+}
+
+#[tokio::test]
+async fn test_concurrent_attempts_of_one_payment_call_api_once() {
+    let mut server = server_with_token().await;
+    let created = server
+        .mock("POST", "/v2/ebarimt_v3/create")
+        .with_status(200)
+        .with_body(ebarimt_json("pay_1"))
+        .expect(1)
+        .create_async()
+        .await;
+
+    let client = Arc::new(QPayClient::new(test_config(&server.url())));
+    let queue = EbarimtQueue::new(client);
+
+    // process_due runs while submit is waiting for the API
+    let (submitted, processed) = tokio::join!(queue.submit(request("pay_1")), queue.process_due());
+    assert!(matches!(submitted.unwrap(), EbarimtAttempt::Issued { .. }));
+    assert!(processed.unwrap().is_empty());
+    assert!(queue.store().list().unwrap().is_empty());
+    created.assert_async().await;
+}
+
+#[tokio::test]
+async fn test_queue_issues_through_issuer() {
+    let mut server = server_with_token().await;
+    let created = server
+        .mock("POST", "/v2/ebarimt_v3/create")
+        .match_body(mockito::Matcher::PartialJson(
+            serde_json::json!({ "payment_id": "pay_2" }),
+        ))
+        .with_status(200)
+        .with_body(ebarimt_json("pay_2"))
+        .expect(1)
+        .create_async()
+        .await;
+
+    let client = Arc::new(QPayClient::new(test_config(&server.url())));
+    let issuer = Arc::new(EbarimtIssuer::new(client.clone()));
+    let issued = serde_json::from_str(&ebarimt_json("pay_1")).unwrap();
+    issuer.store().record_receipt("pay_1", &issued).unwrap();
+    let queue = EbarimtQueue::new(client).with_issuer(issuer.clone());
+
+    // pay_1 already has a receipt, so only pay_2 is sent
+    queue.enqueue(request("pay_1")).await.unwrap();
+    queue.enqueue(request("pay_2")).await.unwrap();
+    let attempts = queue.process_due().await.unwrap();
+    assert_eq!(attempts.len(), 2);
+    assert!(attempts
+        .iter()
+        .all(|a| matches!(a, EbarimtAttempt::Issued { .. })));
+    assert_eq!(
+        issuer.store().receipt("pay_2").unwrap().unwrap().id,
+        "eb_pay_2"
+    );
+    created.assert_async().await;
+}
+
+#[derive(Default)]
+struct Counter {
+    calls: std::sync::atomic::AtomicUsize,
+}
+
+impl CallbackHandler for Counter {
+    async fn on_payment(&self, _payment: VerifiedCallback) -> Result<(), HandlerError> {
+        self.calls.fetch_add(1, std::sync::atomic::Ordering::SeqCst);
+        Ok(())
+    }
+}
+
+#[tokio::test]
+async fn test_callback_handler_queues_failed_receipts() {
+    let mut server = server_with_token().await;
+    server
+        .mock("GET", "/v2/payment/pay_1")
+        .with_status(200)
+        .with_body(
+            serde_json::json!({
+                "payment_id": "pay_1",
+                "payment_status": "PAID",
+                "payment_fee": "0",
+                "payment_amount": "5000",
+                "payment_currency": "MNT",
+                "payment_date": "2024-01-15 10:30:00",
+                "payment_wallet": "qPay",
+                "transaction_type": "P2P",
+                "object_type": "INVOICE",
+                "object_id": "inv_1"
+            })
+            .to_string(),
+        )
+        .create_async()
+        .await;
+    let failed = server
+        .mock("POST", "/v2/ebarimt_v3/create")
+        .with_status(503)
+        .expect(1)
+        .create_async()
+        .await;
+
+    let client = Arc::new(QPayClient::new(test_config(&server.url())));
+    let issuer = Arc::new(EbarimtIssuer::new(client.clone()).with_retry(1, Duration::ZERO));
+    let queue = Arc::new(EbarimtQueue::new(client.clone()).with_issuer(issuer.clone()));
+    let handler =
+        EbarimtCallbackHandler::new(Counter::default(), issuer.clone()).with_queue(queue.clone());
+    let processor = CallbackProcessor::new(client, handler);
+
+    // The receipt is queued instead of asking QPay to deliver again
+    let outcome = processor.process(Some("payment_id=pay_1"), b"").await;
+    assert!(matches!(outcome, CallbackOutcome::Processed(_)));
+    assert_eq!(
+        processor
+            .handler()
+            .inner()
+            .calls
+            .load(std::sync::atomic::Ordering::SeqCst),
+        1
+    );
+    assert_eq!(queue.pending().await.unwrap().len(), 1);
+    failed.assert_async().await;
+
+    let created = server
+        .mock("POST", "/v2/ebarimt_v3/create")
+        .with_status(200)
+        .with_body(ebarimt_json("pay_1"))
+        .expect(1)
+        .create_async()
+        .await;
+    let attempts = queue.process_due().await.unwrap();
+    assert!(matches!(attempts[..], [EbarimtAttempt::Issued { .. }]));
+    assert!(issuer.store().receipt("pay_1").unwrap().is_some());
+    created.assert_async().await;
+}
@@ -44,6 +44,16 @@ fn test_token_error_display() {
     );
 }
 
+#[test]
+fn test_timeout_and_cancelled_error_display() {
+    let err = QPayError::Timeout("invoice inv_1 was not paid in time".to_string());
+    assert_eq!(err.to_string(), "timed out: invoice inv_1 was not paid in time");
+
+    let err = QPayError::Cancelled("stopped".to_string());
+    assert_eq!(err.to_string(), "cancelled: stopped");
+    assert!(is_qpay_error(&err).is_none());
+}
+
 #[test]
 fn test_json_error_display() {
     let json_err = serde_json::from_str::<serde_json::Value>("not valid json").unwrap_err();
@@ -161,3 +171,19 @@ fn test_json_error_from_conversion() {
         _ => panic!("expected QPayError::Json"),
     }
 }
+
+#[test]
+fn test_is_transient() {
+    let api = |status_code| QPayError::Api {
+        status_code,
+        code: String::new(),
+        message: String::new(),
+        raw_body: String::new(),
+    };
+    assert!(api(500).is_transient());
+    assert!(api(503).is_transient());
+    assert!(api(429).is_transient());
+    assert!(!api(400).is_transient());
+    assert!(!api(404).is_transient());
+    assert!(!QPayError::Config("x".to_string()).is_transient());
+}
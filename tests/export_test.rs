@@ -0,0 +1,161 @@
+#![cfg(feature = "chrono")]
+
+mod common;
+
+use futures::StreamExt;
+use mockito::{Matcher, ServerGuard};
+use qpay::export::{DateRangeError, DateWindow};
+use qpay::models::*;
+use qpay::{QPayClient, QPayError};
+
+use common::{server_with_token, test_config};
+
+fn list_item(payment_id: &str, payment_date: &str) -> serde_json::Value {
+    serde_json::json!({
+        "payment_id": payment_id,
+        "payment_date": payment_date,
+        "payment_status": "PAID",
+        "payment_fee": "0",
+        "payment_amount": "1000",
+        "payment_currency": "MNT",
+        "payment_wallet": "qPay",
+        "payment_name": "Test",
+        "payment_description": "Test payment",
+        "qr_code": "",
+        "paid_by": "P2P",
+        "object_type": "MERCHANT",
+        "object_id": "merchant_1"
+    })
+}
+
+fn request(start_date: &str, end_date: &str) -> PaymentListRequest {
+    PaymentListRequest {
+        object_type: "MERCHANT".to_string(),
+        object_id: "merchant_1".to_string(),
+        start_date: start_date.to_string(),
+        end_date: end_date.to_string(),
+        offset: Offset {
+            page_number: 3,
+            page_limit: 50,
+        },
+    }
+}
+
+fn ranges(windows: &[PaymentListRequest]) -> Vec<(&str, &str)> {
+    windows
+        .iter()
+        .map(|w| (w.start_date.as_str(), w.end_date.as_str()))
+        .collect()
+}
+
+#[test]
+fn test_split_by_day() {
+    let windows = request("2024-01-01 10:00:00", "2024-01-03")
+        .split_date_range(DateWindow::Day)
+        .unwrap();
+
+    assert_eq!(
+        ranges(&windows),
+        [
+            ("2024-01-01 10:00:00", "2024-01-01 23:59:59"),
+            ("2024-01-02 00:00:00", "2024-01-02 23:59:59"),
+            ("2024-01-03 00:00:00", "2024-01-03 23:59:59"),
+        ]
+    );
+    assert!(windows.iter().all(|w| w.offset.page_number == 1));
+    assert!(windows.iter().all(|w| w.offset.page_limit == 50));
+}
+
+#[test]
+fn test_split_by_hour_in_ulaanbaatar_time() {
+    // 02:30 UTC is 10:30 in Ulaanbaatar
+    let windows = request("2024-01-01T02:30:00Z", "2024-01-01 12:15:00")
+        .split_date_range(DateWindow::Hour)
+        .unwrap();
+
+    assert_eq!(
+        ranges(&windows),
+        [
+            ("2024-01-01 10:30:00", "2024-01-01 10:59:59"),
+            ("2024-01-01 11:00:00", "2024-01-01 11:59:59"),
+            ("2024-01-01 12:00:00", "2024-01-01 12:15:00"),
+        ]
+    );
+}
+
+#[test]
+fn test_split_invalid_range() {
+    let err = request("2024-01-05", "2024-01-01")
+        .split_date_range(DateWindow::Day)
+        .unwrap_err();
+    assert_eq!(
+        err,
+        DateRangeError {
+            start_date: "2024-01-05".to_string(),
+            end_date: "2024-01-01".to_string(),
+        }
+    );
+
+    assert!(request("yesterday", "2024-01-01")
+        .split_date_range(DateWindow::Day)
+        .is_err());
+}
+
+async fn mock_window(server: &mut ServerGuard, start_date: &str, rows: Vec<serde_json::Value>) {
+    server
+        .mock("POST", "/v2/payment/list")
+        .match_body(Matcher::PartialJson(serde_json::json!({
+            "start_date": start_date
+        })))
+        .with_status(200)
+        .with_body(serde_json::json!({"count": rows.len(), "rows": rows}).to_string())
+        .create_async()
+        .await;
+}
+
+#[tokio::test]
+async fn test_list_payments_chunked_merges_in_order() {
+    let mut server = server_with_token().await;
+    mock_window(
+        &mut server,
+        "2024-01-01 00:00:00",
+        vec![
+            list_item("p2", "2024-01-01 18:00:00"),
+            list_item("p1", "2024-01-01 09:00:00"),
+        ],
+    )
+    .await;
+    mock_window(
+        &mut server,
+        "2024-01-02 00:00:00",
+        vec![
+            list_item("p4", "2024-01-02 12:00:00"),
+            // Boundary payment returned by both windows
+            list_item("p2", "2024-01-01 18:00:00"),
+            list_item("p3", "2024-01-02 00:10:00"),
+        ],
+    )
+    .await;
+    mock_window(&mut server, "2024-01-03 00:00:00", vec![]).await;
+
+    let client = QPayClient::new(test_config(&server.url()));
+    let ids: Vec<String> = client
+        .list_payments_chunked(&request("2024-01-01", "2024-01-03"), DateWindow::Day, 2)
+        .map(|item| item.unwrap().payment_id)
+        .collect()
+        .await;
+
+    assert_eq!(ids, ["p1", "p2", "p3", "p4"]);
+}
+
+#[tokio::test]
+async fn test_list_payments_chunked_invalid_range() {
+    let client = QPayClient::new(test_config("http://127.0.0.1:1"));
+    let items: Vec<_> = client
+        .list_payments_chunked(&request("2024-01-05", "2024-01-01"), DateWindow::Day, 2)
+        .collect()
+        .await;
+
+    assert_eq!(items.len(), 1);
+    assert!(matches!(items[0], Err(QPayError::DateRange(_))));
+}
@@ -0,0 +1,291 @@
+mod common;
+
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+use mockito::{Matcher, ServerGuard};
+use qpay::callback::{
+    CallbackHandler, CallbackOutcome, CallbackProcessor, HandlerError, VerifiedCallback,
+};
+use qpay::issuance::{EbarimtCallbackHandler, EbarimtIssuer, EbarimtReceiver};
+use qpay::models::EbarimtResponse;
+use qpay::polling::WaitOptions;
+use qpay::validation::RegisterNumber;
+use qpay::{QPayClient, QPayError};
+
+use common::{server_with_token, test_config};
+
+fn ebarimt_json(payment_id: &str) -> String {
+    serde_json::to_string(&EbarimtResponse {
+        id: format!("eb_{}", payment_id),
+        g_payment_id: payment_id.to_string(),
+        barimt_status: "REGISTERED".to_string(),
+        ..Default::default()
+    })
+    .unwrap()
+}
+
+fn payment_json() -> String {
+    serde_json::json!({
+        "payment_id": "pay_1",
+        "payment_status": "PAID",
+        "payment_fee": "0",
+        "payment_amount": "5000",
+        "payment_currency": "MNT",
+        "payment_date": "2024-01-15 10:30:00",
+        "payment_wallet": "qPay",
+        "transaction_type": "P2P",
+        "object_type": "INVOICE",
+        "object_id": "inv_1"
+    })
+    .to_string()
+}
+
+fn issuer(server: &ServerGuard) -> EbarimtIssuer {
+    let client = Arc::new(QPayClient::new(test_config(&server.url())));
+    EbarimtIssuer::new(client).with_retry(3, Duration::from_millis(1))
+}
+
+#[test]
+fn test_receiver_requests() {
+    let company = EbarimtReceiver::register(RegisterNumber::parse("1234567").unwrap())
+        .with_district_code("34");
+    let req = company.request("pay_1");
+    assert_eq!(req.payment_id, "pay_1");
+    assert_eq!(req.ebarimt_receiver_type, "COMPANY");
+    assert_eq!(req.ebarimt_receiver.as_deref(), Some("1234567"));
+    assert_eq!(req.district_code.as_deref(), Some("34"));
+
+    let citizen = EbarimtReceiver::register(RegisterNumber::parse("UA89010112").unwrap());
+    assert_eq!(citizen.receiver_type, "CITIZEN");
+    assert_eq!(citizen.receiver.as_deref(), Some("УА89010112"));
+
+    let anonymous = EbarimtReceiver::default().request("pay_2");
+    assert_eq!(anonymous.ebarimt_receiver_type, "CITIZEN");
+    assert_eq!(anonymous.ebarimt_receiver, None);
+}
+
+#[tokio::test]
+async fn test_issue_retries_transient_failures_once_per_payment() {
+    let mut server = server_with_token().await;
+    let failed = server
+        .mock("POST", "/v2/ebarimt_v3/create")
+        .with_status(503)
+        .with_body(r#"{"error":"SERVICE_UNAVAILABLE","message":"try again"}"#)
+        .expect(1)
+        .create_async()
+        .await;
+    let created = server
+        .mock("POST", "/v2/ebarimt_v3/create")
+        .match_body(Matcher::PartialJson(serde_json::json!({
+            "payment_id": "pay_1",
+            "ebarimt_receiver_type": "COMPANY",
+            "ebarimt_receiver": "1234567"
+        })))
+        .with_status(200)
+        .with_body(ebarimt_json("pay_1"))
+        .expect(1)
+        .create_async()
+        .await;
+
+    let issuer = issuer(&server);
+    let company = EbarimtReceiver::register(RegisterNumber::parse("1234567").unwrap());
+    issuer.set_receiver("inv_1", &company).await.unwrap();
+
+    let receipt = issuer.issue("inv_1", "pay_1").await.unwrap();
+    assert_eq!(receipt.id, "eb_pay_1");
+
+    // A second report of the same payment returns the recorded receipt
+    let again = issuer.issue("inv_1", "pay_1").await.unwrap();
+    assert_eq!(again.id, "eb_pay_1");
+    assert!(issuer.store().receipt("pay_1").unwrap().is_some());
+
+    failed.assert_async().await;
+    created.assert_async().await;
+}
+
+#[tokio::test]
+async fn test_issue_does_not_retry_permanent_failures() {
+    let mut server = server_with_token().await;
+    let rejected = server
+        .mock("POST", "/v2/ebarimt_v3/create")
+        .with_status(400)
+        .with_body(r#"{"error":"EBARIMT_NOT_REGISTERED","message":"not registered"}"#)
+        .expect(1)
+        .create_async()
+        .await;
+
+    let issuer = issuer(&server);
+    let err = issuer.issue("inv_1", "pay_1").await.unwrap_err();
+    assert!(matches!(err, QPayError::Api { ref code, .. } if code == "EBARIMT_NOT_REGISTERED"));
+    assert!(issuer.store().receipt("pay_1").unwrap().is_none());
+    rejected.assert_async().await;
+}
+
+#[tokio::test]
+async fn test_wait_and_issue() {
+    let mut server = server_with_token().await;
+    server
+        .mock("POST", "/v2/payment/check")
+        .with_status(200)
+        .with_body(
+            serde_json::json!({
+                "count": 2,
+                "paid_amount": 10000,
+                "rows": [
+                    {"payment_id": "pay_1", "payment_status": "PAID", "payment_amount": "4000",
+                     "trx_fee": "0", "payment_currency": "MNT", "payment_wallet": "qPay",
+                     "payment_type": "P2P"},
+                    {"payment_id": "pay_2", "payment_status": "PAID", "payment_amount": "6000",
+                     "trx_fee": "0", "payment_currency": "MNT", "payment_wallet": "qPay",
+                     "payment_type": "P2P"}
+                ]
+            })
+            .to_string(),
+        )
+        .create_async()
+        .await;
+    for payment_id in ["pay_1", "pay_2"] {
+        server
+            .mock("POST", "/v2/ebarimt_v3/create")
+            .match_body(Matcher::PartialJson(
+                serde_json::json!({ "payment_id": payment_id }),
+            ))
+            .with_status(200)
+            .with_body(ebarimt_json(payment_id))
+            .create_async()
+            .await;
+    }
+
+    let issuer = issuer(&server);
+    let receipts = issuer
+        .wait_and_issue("inv_1", &WaitOptions::default())
+        .await
+        .unwrap();
+    let ids: Vec<_> = receipts.iter().map(|r| r.id.as_str()).collect();
+    assert_eq!(ids, ["eb_pay_1", "eb_pay_2"]);
+}
+
+#[derive(Default)]
+struct Counter {
+    calls: AtomicUsize,
+}
+
+impl CallbackHandler for Counter {
+    async fn on_payment(&self, _payment: VerifiedCallback) -> Result<(), HandlerError> {
+        self.calls.fetch_add(1, Ordering::SeqCst);
+        Ok(())
+    }
+}
+
+#[tokio::test]
+async fn test_callback_handler_issues_receipt() {
+    let mut server = server_with_token().await;
+    server
+        .mock("GET", "/v2/payment/pay_1")
+        .with_status(200)
+        .with_body(payment_json())
+        .create_async()
+        .await;
+    let created = server
+        .mock("POST", "/v2/ebarimt_v3/create")
+        .with_status(200)
+        .with_body(ebarimt_json("pay_1"))
+        .expect(1)
+        .create_async()
+        .await;
+
+    let client = Arc::new(QPayClient::new(test_config(&server.url())));
+    let issuer = Arc::new(EbarimtIssuer::new(client.clone()));
+    let processor = CallbackProcessor::new(
+        client,
+        EbarimtCallbackHandler::new(Counter::default(), issuer.clone()),
+    );
+
+    let outcome = processor.process(Some("payment_id=pay_1"), b"").await;
+    assert!(matches!(outcome, CallbackOutcome::Processed(_)));
+    assert_eq!(processor.handler().inner().calls.load(Ordering::SeqCst), 1);
+    assert_eq!(
+        issuer.store().receipt("pay_1").unwrap().unwrap().id,
+        "eb_pay_1"
+    );
+    created.assert_async().await;
+}
+
+#[tokio::test]
+async fn test_callback_handler_asks_for_redelivery_on_transient_failure() {
+    let mut server = server_with_token().await;
+    server
+        .mock("GET", "/v2/payment/pay_1")
+        .with_status(200)
+        .with_body(payment_json())
+        .create_async()
+        .await;
+    server
+        .mock("POST", "/v2/ebarimt_v3/create")
+        .with_status(502)
+        .create_async()
+        .await;
+
+    let client = Arc::new(QPayClient::new(test_config(&server.url())));
+    let issuer = Arc::new(EbarimtIssuer::new(client.clone()).with_retry(1, Duration::ZERO));
+    let processor = CallbackProcessor::new(
+        client,
+        EbarimtCallbackHandler::new(Counter::default(), issuer),
+    );
+
+    let outcome = processor.process(Some("payment_id=pay_1"), b"").await;
+    assert!(matches!(outcome, CallbackOutcome::HandlerFailed(_)));
+    assert_eq!(outcome.status_code(), 500);
+    assert_eq!(processor.handler().inner().calls.load(Ordering::SeqCst), 0);
+}
+
+#[tokio::test]
+async fn test_callback_handler_records_permanent_failure_for_retry() {
+    let mut server = server_with_token().await;
+    server
+        .mock("GET", "/v2/payment/pay_1")
+        .with_status(200)
+        .with_body(payment_json())
+        .create_async()
+        .await;
+    let rejected = server
+        .mock("POST", "/v2/ebarimt_v3/create")
+        .with_status(400)
+        .with_body(r#"{"error":"EBARIMT_NOT_REGISTERED","message":"not registered"}"#)
+        .expect(1)
+        .create_async()
+        .await;
+
+    let client = Arc::new(QPayClient::new(test_config(&server.url())));
+    let issuer = Arc::new(EbarimtIssuer::new(client.clone()));
+    let processor = CallbackProcessor::new(
+        client,
+        EbarimtCallbackHandler::new(Counter::default(), issuer.clone()),
+    );
+
+    // The payment is confirmed, so it is still passed on
+    let outcome = processor.process(Some("payment_id=pay_1"), b"").await;
+    assert!(matches!(outcome, CallbackOutcome::Processed(_)));
+    assert_eq!(processor.handler().inner().calls.load(Ordering::SeqCst), 1);
+
+    let failures = issuer.failures().await.unwrap();
+    assert_eq!(failures.len(), 1);
+    assert_eq!(failures[0].invoice_id, "inv_1");
+    assert_eq!(failures[0].payment_id, "pay_1");
+    assert!(failures[0].error.contains("EBARIMT_NOT_REGISTERED"));
+    rejected.assert_async().await;
+
+    let created = server
+        .mock("POST", "/v2/ebarimt_v3/create")
+        .with_status(200)
+        .with_body(ebarimt_json("pay_1"))
+        .expect(1)
+        .create_async()
+        .await;
+    let receipts = issuer.retry_failures().await.unwrap();
+    assert_eq!(receipts.len(), 1);
+    assert!(issuer.failures().await.unwrap().is_empty());
+    created.assert_async().await;
+}
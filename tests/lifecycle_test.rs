@@ -0,0 +1,424 @@
+mod common;
+
+use std::sync::Arc;
+
+use mockito::{Matcher, ServerGuard};
+use qpay::lifecycle::{
+    InvoiceLifecycle, InvoiceRecord, InvoiceRepository, InvoiceState, LifecycleError,
+    MemoryInvoiceRepository, PaymentState,
+};
+use qpay::models::*;
+use qpay::{QPayClient, QPayError};
+
+use common::{server_with_token, test_config};
+
+fn check_json(rows: &[(&str, &str, &str)]) -> String {
+    let rows: Vec<_> = rows
+        .iter()
+        .map(|(payment_id, status, amount)| {
+            serde_json::json!({
+                "payment_id": payment_id,
+                "payment_status": status,
+                "payment_amount": amount,
+                "trx_fee": "0",
+                "payment_currency": "MNT",
+                "payment_wallet": "qPay",
+                "payment_type": "CARD",
+                "next_payment_date": null,
+                "next_payment_datetime": null
+            })
+        })
+        .collect();
+    serde_json::json!({ "count": rows.len(), "rows": rows }).to_string()
+}
+
+fn invoice_json(status: &str) -> String {
+    serde_json::json!({
+        "invoice_id": "inv_1",
+        "invoice_status": status,
+        "sender_invoice_no": "ORDER-1"
+    })
+    .to_string()
+}
+
+fn invoice_request(amount: f64) -> CreateInvoiceRequest {
+    CreateInvoiceRequest {
+        invoice_code: "TEST_CODE".to_string(),
+        sender_invoice_no: "ORDER-1".to_string(),
+        sender_branch_code: None,
+        sender_branch_data: None,
+        sender_staff_data: None,
+        sender_staff_code: None,
+        invoice_receiver_code: "terminal".to_string(),
+        invoice_receiver_data: None,
+        invoice_description: "Order 1".to_string(),
+        enable_expiry: None,
+        allow_partial: Some(true),
+        minimum_amount: None,
+        allow_exceed: None,
+        maximum_amount: None,
+        amount,
+        callback_url: "https://example.com/callback".to_string(),
+        sender_terminal_code: None,
+        sender_terminal_data: None,
+        allow_subscribe: None,
+        subscription_interval: None,
+        subscription_webhook: None,
+        note: None,
+        transactions: None,
+        lines: None,
+    }
+}
+
+fn record(state: InvoiceState) -> InvoiceRecord {
+    InvoiceRecord {
+        invoice_id: "inv_1".to_string(),
+        sender_invoice_no: "ORDER-1".to_string(),
+        amount: 100.0,
+        state,
+        payments: Vec::new(),
+        updated_at: 0,
+    }
+}
+
+fn paid_row(payment_id: &str, status: &str, amount: &str) -> PaymentCheckRow {
+    PaymentCheckRow {
+        payment_id: payment_id.to_string(),
+        payment_status: status.to_string(),
+        payment_amount: amount.to_string(),
+        ..Default::default()
+    }
+}
+
+async fn mock_create(server: &mut ServerGuard) {
+    server
+        .mock("POST", "/v2/invoice")
+        .with_status(200)
+        .with_body(
+            serde_json::json!({
+                "invoice_id": "inv_1",
+                "qr_text": "qr",
+                "qr_image": "",
+                "qPay_shortUrl": "",
+                "urls": []
+            })
+            .to_string(),
+        )
+        .create_async()
+        .await;
+}
+
+#[test]
+fn test_invoice_transitions() {
+    use InvoiceState::*;
+
+    assert!(Created.can_transition_to(PartiallyPaid));
+    assert!(Created.can_transition_to(Paid));
+    assert!(Created.can_transition_to(Cancelled));
+    assert!(Created.can_transition_to(Expired));
+    assert!(PartiallyPaid.can_transition_to(Paid));
+    assert!(Paid.can_transition_to(Refunded));
+
+    assert!(!Paid.can_transition_to(Cancelled));
+    assert!(!Cancelled.can_transition_to(Paid));
+    assert!(!Expired.can_transition_to(Created));
+    assert!(!Created.can_transition_to(Refunded));
+    assert!(Refunded.is_terminal() && Cancelled.is_terminal() && Expired.is_terminal());
+
+    assert!(PaymentState::Paid.can_transition_to(PaymentState::Refunded));
+    assert!(!PaymentState::Refunded.can_transition_to(PaymentState::Cancelled));
+}
+
+#[test]
+fn test_apply_rows() {
+    let mut invoice = record(InvoiceState::Created);
+
+    assert!(invoice
+        .apply_rows(&[paid_row("pay_1", "PAID", "40")])
+        .unwrap());
+    assert_eq!(invoice.state, InvoiceState::PartiallyPaid);
+
+    // Rows already recorded change nothing
+    assert!(!invoice
+        .apply_rows(&[paid_row("pay_1", "PAID", "40")])
+        .unwrap());
+
+    invoice
+        .apply_rows(&[
+            paid_row("pay_1", "PAID", "40"),
+            paid_row("pay_2", "PAID", "60"),
+            paid_row("pay_3", "FAILED", "60"),
+        ])
+        .unwrap();
+    assert_eq!(invoice.state, InvoiceState::Paid);
+    assert_eq!(invoice.paid_amount(), 100.0);
+    assert_eq!(invoice.payments.len(), 2);
+
+    // A partial refund keeps the invoice paid; refunding the rest does not
+    invoice
+        .apply_rows(&[paid_row("pay_1", "REFUNDED", "40")])
+        .unwrap();
+    assert_eq!(invoice.state, InvoiceState::Paid);
+    assert_eq!(invoice.paid_amount(), 60.0);
+
+    invoice
+        .apply_rows(&[paid_row("pay_2", "REFUNDED", "60")])
+        .unwrap();
+    assert_eq!(invoice.state, InvoiceState::Refunded);
+}
+
+#[test]
+fn test_invalid_steps() {
+    let mut invoice = record(InvoiceState::Paid);
+    assert_eq!(
+        invoice.transition(InvoiceState::Cancelled),
+        Err(LifecycleError::InvalidTransition {
+            invoice_id: "inv_1".to_string(),
+            from: InvoiceState::Paid,
+            to: InvoiceState::Cancelled,
+        })
+    );
+    assert!(matches!(
+        invoice.transition_payment("pay_9", PaymentState::Refunded),
+        Err(LifecycleError::UnknownPayment { .. })
+    ));
+
+    // Cancelled invoices ignore late payment rows
+    let mut invoice = record(InvoiceState::Cancelled);
+    assert!(!invoice
+        .apply_rows(&[paid_row("pay_1", "PAID", "100")])
+        .unwrap());
+    assert!(invoice.payments.is_empty());
+}
+
+#[test]
+fn test_memory_repository() {
+    let repo = MemoryInvoiceRepository::new();
+    repo.save(&record(InvoiceState::Created)).unwrap();
+    repo.save(&InvoiceRecord {
+        invoice_id: "inv_2".to_string(),
+        ..record(InvoiceState::Paid)
+    })
+    .unwrap();
+
+    assert_eq!(
+        repo.load("inv_1").unwrap().unwrap().state,
+        InvoiceState::Created
+    );
+    assert!(repo.load("inv_9").unwrap().is_none());
+    assert_eq!(repo.list(InvoiceState::Paid).unwrap().len(), 1);
+    assert!(repo.list(InvoiceState::Expired).unwrap().is_empty());
+}
+
+#[tokio::test]
+async fn test_create_pay_and_refund() {
+    let mut server = server_with_token().await;
+    mock_create(&mut server).await;
+    server
+        .mock("POST", "/v2/payment/check")
+        .with_status(200)
+        .with_body(check_json(&[("pay_1", "PAID", "60")]))
+        .expect(1)
+        .create_async()
+        .await;
+    server
+        .mock("POST", "/v2/payment/check")
+        .with_status(200)
+        .with_body(check_json(&[
+            ("pay_1", "PAID", "60"),
+            ("pay_2", "PAID", "40"),
+        ]))
+        .create_async()
+        .await;
+    let refund = server
+        .mock("DELETE", "/v2/payment/refund/pay_1")
+        .with_status(200)
+        .create_async()
+        .await;
+    let cancel = server
+        .mock("DELETE", "/v2/payment/cancel/pay_2")
+        .with_status(400)
+        .with_body(r#"{"error":"PAYMENT_ALREADY_CANCELED","message":"already canceled"}"#)
+        .create_async()
+        .await;
+
+    let client = Arc::new(QPayClient::new(test_config(&server.url())));
+    let lifecycle = InvoiceLifecycle::new(client);
+
+    let invoice = lifecycle.create(&invoice_request(100.0)).await.unwrap();
+    assert_eq!(invoice.state, InvoiceState::Created);
+
+    let invoice = lifecycle.sync("inv_1").await.unwrap();
+    assert_eq!(invoice.state, InvoiceState::PartiallyPaid);
+
+    let invoice = lifecycle.sync("inv_1").await.unwrap();
+    assert_eq!(invoice.state, InvoiceState::Paid);
+
+    // Paid invoices cannot be cancelled; nothing is sent to QPay
+    let err = lifecycle.cancel("inv_1").await.unwrap_err();
+    assert!(matches!(
+        err,
+        QPayError::Lifecycle(LifecycleError::InvalidTransition { .. })
+    ));
+
+    let invoice = lifecycle
+        .refund_payment("inv_1", "pay_1", &PaymentRefundRequest::default())
+        .await
+        .unwrap();
+    assert_eq!(invoice.state, InvoiceState::Paid);
+    assert_eq!(
+        invoice.payment("pay_1").unwrap().state,
+        PaymentState::Refunded
+    );
+
+    let invoice = lifecycle
+        .cancel_payment("inv_1", "pay_2", &PaymentCancelRequest::default())
+        .await
+        .unwrap();
+    assert_eq!(invoice.state, InvoiceState::Refunded);
+    assert_eq!(
+        lifecycle.get("inv_1").unwrap().state,
+        InvoiceState::Refunded
+    );
+
+    // A refunded payment cannot be refunded again
+    let err = lifecycle
+        .refund_payment("inv_1", "pay_1", &PaymentRefundRequest::default())
+        .await
+        .unwrap_err();
+    assert!(matches!(err, QPayError::Lifecycle(_)));
+
+    refund.assert_async().await;
+    cancel.assert_async().await;
+}
+
+#[tokio::test]
+async fn test_cancel_and_expire() {
+    let mut server = server_with_token().await;
+    mock_create(&mut server).await;
+    let cancel = server
+        .mock("DELETE", "/v2/invoice/inv_1")
+        .with_status(200)
+        .create_async()
+        .await;
+
+    let client = Arc::new(QPayClient::new(test_config(&server.url())));
+    let repo = MemoryInvoiceRepository::new();
+    repo.save(&InvoiceRecord {
+        invoice_id: "inv_old".to_string(),
+        ..record(InvoiceState::Created)
+    })
+    .unwrap();
+    let lifecycle = InvoiceLifecycle::with_repository(client, repo);
+
+    lifecycle.create(&invoice_request(100.0)).await.unwrap();
+    let invoice = lifecycle.cancel("inv_1").await.unwrap();
+    assert_eq!(invoice.state, InvoiceState::Cancelled);
+    cancel.assert_async().await;
+
+    // Syncing a cancelled invoice makes no API call
+    assert_eq!(
+        lifecycle.sync("inv_1").await.unwrap().state,
+        InvoiceState::Cancelled
+    );
+
+    let invoice = lifecycle.expire("inv_old").unwrap();
+    assert_eq!(invoice.state, InvoiceState::Expired);
+    assert_eq!(
+        lifecycle
+            .repository()
+            .list(InvoiceState::Expired)
+            .unwrap()
+            .len(),
+        1
+    );
+
+    let err = lifecycle.get("inv_missing").unwrap_err();
+    assert!(matches!(
+        err,
+        QPayError::Lifecycle(LifecycleError::UnknownInvoice(_))
+    ));
+}
+
+#[tokio::test]
+async fn test_sync_detects_cancelled_invoice() {
+    let mut server = server_with_token().await;
+    mock_create(&mut server).await;
+    server
+        .mock("POST", "/v2/payment/check")
+        .match_body(Matcher::PartialJson(
+            serde_json::json!({"object_id": "inv_1"}),
+        ))
+        .with_status(200)
+        .with_body(check_json(&[]))
+        .create_async()
+        .await;
+    server
+        .mock("GET", "/v2/invoice/inv_1")
+        .with_status(200)
+        .with_body(invoice_json("OPEN"))
+        .expect(1)
+        .create_async()
+        .await;
+    server
+        .mock("GET", "/v2/invoice/inv_1")
+        .with_status(200)
+        .with_body(invoice_json("CANCELLED"))
+        .create_async()
+        .await;
+
+    let client = Arc::new(QPayClient::new(test_config(&server.url())));
+    let lifecycle = InvoiceLifecycle::new(client);
+    lifecycle.create(&invoice_request(100.0)).await.unwrap();
+
+    let invoice = lifecycle.sync("inv_1").await.unwrap();
+    assert_eq!(invoice.state, InvoiceState::Created);
+
+    // Cancelled outside the lifecycle, e.g. in the merchant portal
+    let invoice = lifecycle.sync("inv_1").await.unwrap();
+    assert_eq!(invoice.state, InvoiceState::Cancelled);
+}
+
+#[tokio::test]
+async fn test_cancel_checks_invoice_status_on_error() {
+    let mut server = server_with_token().await;
+    mock_create(&mut server).await;
+    server
+        .mock("DELETE", "/v2/invoice/inv_1")
+        .with_status(400)
+        .with_body(r#"{"error":"INVOICE_NOTFOUND","message":"not found"}"#)
+        .expect(1)
+        .create_async()
+        .await;
+    server
+        .mock("DELETE", "/v2/invoice/inv_1")
+        .with_status(400)
+        .with_body(r#"{"error":"INVOICE_ALREADY_CANCELED","message":"canceled"}"#)
+        .create_async()
+        .await;
+    server
+        .mock("GET", "/v2/invoice/inv_1")
+        .with_status(200)
+        .with_body(invoice_json("OPEN"))
+        .expect(1)
+        .create_async()
+        .await;
+    server
+        .mock("GET", "/v2/invoice/inv_1")
+        .with_status(200)
+        .with_body(invoice_json("CANCELED"))
+        .create_async()
+        .await;
+
+    let client = Arc::new(QPayClient::new(test_config(&server.url())));
+    let lifecycle = InvoiceLifecycle::new(client);
+    lifecycle.create(&invoice_request(100.0)).await.unwrap();
+
+    // The invoice is still open, so the error stands
+    let err = lifecycle.cancel("inv_1").await.unwrap_err();
+    assert!(matches!(err, QPayError::Api { ref code, .. } if code == "INVOICE_NOTFOUND"));
+    assert_eq!(lifecycle.get("inv_1").unwrap().state, InvoiceState::Created);
+
+    let invoice = lifecycle.cancel("inv_1").await.unwrap();
+    assert_eq!(invoice.state, InvoiceState::Cancelled);
+}
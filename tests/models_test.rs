@@ -37,6 +37,7 @@ fn test_token_response_serialize() {
         scope: "default".to_string(),
         not_before_policy: "0".to_string(),
         session_state: "sess".to_string(),
+        ..Default::default()
     };
 
     let json = serde_json::to_string(&token).unwrap();
@@ -55,6 +56,7 @@ fn test_token_response_roundtrip() {
         scope: "openid".to_string(),
         not_before_policy: "0".to_string(),
         session_state: "s1".to_string(),
+        ..Default::default()
     };
 
     let json = serde_json::to_string(&original).unwrap();
@@ -201,12 +203,35 @@ fn test_invoice_response_serialize_preserves_qpay_short_url_field() {
         qr_image: "img".to_string(),
         qpay_short_url: "https://short.url".to_string(),
         urls: vec![],
+        ..Default::default()
     };
 
     let json = serde_json::to_string(&resp).unwrap();
     assert!(json.contains("qPay_shortUrl"));
 }
 
+// --- InvoiceDetail ---
+
+#[test]
+fn test_invoice_detail_deserialize_minimal() {
+    let json = r#"{
+        "invoice_id": "inv_001",
+        "invoice_status": "PAID",
+        "sender_invoice_no": "INV-001",
+        "invoice_description": "Test",
+        "total_amount": 5000,
+        "callback_url": "https://cb.example.com"
+    }"#;
+
+    let detail: InvoiceDetail = serde_json::from_str(json).unwrap();
+    assert_eq!(detail.invoice_id, "inv_001");
+    assert_eq!(detail.invoice_status, "PAID");
+    assert_eq!(detail.total_amount, 5000.0);
+    assert!(!detail.allow_partial);
+    assert!(detail.lines.is_empty());
+    assert!(detail.payments.is_empty());
+}
+
 // --- PaymentCheckRequest ---
 
 #[test]
@@ -708,3 +733,74 @@ fn test_create_ebarimt_invoice_request_serialize() {
     assert!(json.contains("\"district_code\":\"23\""));
     assert!(json.contains("TAX001"));
 }
+
+// --- Unknown fields ---
+
+#[test]
+fn test_response_captures_unknown_fields() {
+    let json = r#"{
+        "count": 1,
+        "paid_amount": 100,
+        "new_top_level": "x",
+        "rows": [{
+            "payment_id": "pay_1",
+            "payment_status": "PAID",
+            "payment_amount": "100",
+            "trx_fee": "0",
+            "payment_currency": "MNT",
+            "payment_wallet": "qPay",
+            "payment_type": "P2P",
+            "next_payment_date": null,
+            "next_payment_datetime": null,
+            "new_row_field": 42
+        }]
+    }"#;
+
+    let resp: PaymentCheckResponse = serde_json::from_str(json).unwrap();
+    assert_eq!(resp.extra.get("new_top_level").unwrap(), "x");
+    assert_eq!(resp.rows[0].extra.get("new_row_field").unwrap(), 42);
+    assert_eq!(
+        resp.unknown_fields(),
+        vec!["new_top_level", "rows[0].new_row_field"]
+    );
+
+    // Unknown fields survive a round trip
+    let out = serde_json::to_value(&resp).unwrap();
+    assert_eq!(out["rows"][0]["new_row_field"], 42);
+}
+
+#[test]
+fn test_invoice_detail_captures_unknown_nested_fields() {
+    let json = r#"{
+        "invoice_id": "inv_1",
+        "invoice_status": "OPEN",
+        "sender_invoice_no": "INV-1",
+        "sender_branch_data": {"name": "Branch", "branch_rank": 1},
+        "invoice_receiver_data": {"register": "AA12345678", "loyalty_tier": "gold"},
+        "invoice_description": "Order",
+        "total_amount": 100,
+        "callback_url": "",
+        "transactions": [{"description": "Split", "amount": "100", "settled": true}]
+    }"#;
+
+    let detail: InvoiceDetail = serde_json::from_str(json).unwrap();
+    let receiver = detail.invoice_receiver_data.as_ref().unwrap();
+    assert_eq!(receiver.register.as_deref(), Some("AA12345678"));
+    assert_eq!(detail.transactions[0].extra.get("settled").unwrap(), true);
+    assert_eq!(
+        detail.unknown_fields(),
+        vec![
+            "sender_branch_data.branch_rank",
+            "invoice_receiver_data.loyalty_tier",
+            "transactions[0].settled",
+        ]
+    );
+}
+
+#[test]
+fn test_response_without_unknown_fields_has_empty_extra() {
+    let json = r#"{"count": 0, "rows": []}"#;
+    let resp: PaymentListResponse = serde_json::from_str(json).unwrap();
+    assert!(resp.extra.is_empty());
+    assert!(resp.unknown_fields().is_empty());
+}
@@ -0,0 +1,192 @@
+mod common;
+
+use futures::StreamExt;
+use mockito::{Matcher, ServerGuard};
+use qpay::models::*;
+use qpay::{QPayClient, QPayError};
+
+use common::{server_with_token, test_config};
+
+fn list_item(payment_id: &str) -> serde_json::Value {
+    serde_json::json!({
+        "payment_id": payment_id,
+        "payment_date": "2024-01-15 10:30:00",
+        "payment_status": "PAID",
+        "payment_fee": "0",
+        "payment_amount": "1000",
+        "payment_currency": "MNT",
+        "payment_wallet": "qPay",
+        "payment_name": "Test",
+        "payment_description": "Test payment",
+        "qr_code": "",
+        "paid_by": "P2P",
+        "object_type": "MERCHANT",
+        "object_id": "merchant_1"
+    })
+}
+
+async fn mock_list_page(
+    server: &mut ServerGuard,
+    page_number: i32,
+    count: i32,
+    ids: &[&str],
+) -> mockito::Mock {
+    let rows: Vec<_> = ids.iter().map(|id| list_item(id)).collect();
+    server
+        .mock("POST", "/v2/payment/list")
+        .match_body(Matcher::PartialJson(serde_json::json!({
+            "offset": {"page_number": page_number, "page_limit": 2}
+        })))
+        .with_status(200)
+        .with_body(serde_json::json!({"count": count, "rows": rows}).to_string())
+        .create_async()
+        .await
+}
+
+fn list_request(page_number: i32) -> PaymentListRequest {
+    PaymentListRequest {
+        object_type: "MERCHANT".to_string(),
+        object_id: "merchant_1".to_string(),
+        start_date: "2024-01-01".to_string(),
+        end_date: "2024-01-31".to_string(),
+        offset: Offset {
+            page_number,
+            page_limit: 2,
+        },
+    }
+}
+
+#[tokio::test]
+async fn test_list_payments_stream_stops_at_count() {
+    let mut server = server_with_token().await;
+    let page1 = mock_list_page(&mut server, 1, 5, &["p1", "p2"]).await;
+    let page2 = mock_list_page(&mut server, 2, 5, &["p3", "p4"]).await;
+    let page3 = mock_list_page(&mut server, 3, 5, &["p5"]).await;
+    let page4 = mock_list_page(&mut server, 4, 5, &["p6"]).await.expect(0);
+
+    let client = QPayClient::new(test_config(&server.url()));
+    let ids: Vec<String> = client
+        .list_payments_stream(&list_request(1), 2)
+        .map(|item| item.unwrap().payment_id)
+        .collect()
+        .await;
+
+    assert_eq!(ids, ["p1", "p2", "p3", "p4", "p5"]);
+    page1.assert_async().await;
+    page2.assert_async().await;
+    page3.assert_async().await;
+    page4.assert_async().await;
+}
+
+#[tokio::test]
+async fn test_list_payments_stream_starts_at_offset() {
+    let mut server = server_with_token().await;
+    mock_list_page(&mut server, 2, 5, &["p3", "p4"]).await;
+    mock_list_page(&mut server, 3, 5, &["p5"]).await;
+
+    let client = QPayClient::new(test_config(&server.url()));
+    let ids: Vec<String> = client
+        .list_payments_stream(&list_request(2), 0)
+        .map(|item| item.unwrap().payment_id)
+        .collect()
+        .await;
+
+    assert_eq!(ids, ["p3", "p4", "p5"]);
+}
+
+#[tokio::test]
+async fn test_list_payments_stream_stops_on_empty_page() {
+    let mut server = server_with_token().await;
+    mock_list_page(&mut server, 1, 100, &["p1", "p2"]).await;
+    mock_list_page(&mut server, 2, 100, &[]).await;
+    let page3 = mock_list_page(&mut server, 3, 100, &["p5", "p6"])
+        .await
+        .expect(0);
+
+    let client = QPayClient::new(test_config(&server.url()));
+    let ids: Vec<String> = client
+        .list_payments_stream(&list_request(1), 0)
+        .map(|item| item.unwrap().payment_id)
+        .collect()
+        .await;
+
+    assert_eq!(ids, ["p1", "p2"]);
+    page3.assert_async().await;
+}
+
+#[tokio::test]
+async fn test_list_payments_stream_stops_after_error() {
+    let mut server = server_with_token().await;
+    mock_list_page(&mut server, 1, 6, &["p1", "p2"]).await;
+    server
+        .mock("POST", "/v2/payment/list")
+        .match_body(Matcher::PartialJson(serde_json::json!({
+            "offset": {"page_number": 2}
+        })))
+        .with_status(500)
+        .with_body(r#"{"error": "INTERNAL", "message": "boom"}"#)
+        .create_async()
+        .await;
+    mock_list_page(&mut server, 3, 6, &["p5", "p6"]).await;
+
+    let client = QPayClient::new(test_config(&server.url()));
+    let items: Vec<_> = client
+        .list_payments_stream(&list_request(1), 1)
+        .collect()
+        .await;
+
+    assert_eq!(items.len(), 3);
+    assert!(items[0].is_ok() && items[1].is_ok());
+    assert!(matches!(
+        items[2],
+        Err(QPayError::Api {
+            status_code: 500,
+            ..
+        })
+    ));
+}
+
+#[tokio::test]
+async fn test_check_payment_stream_default_offset() {
+    let mut server = server_with_token().await;
+    server
+        .mock("POST", "/v2/payment/check")
+        .match_body(Matcher::PartialJson(serde_json::json!({
+            "object_type": "INVOICE",
+            "object_id": "inv_1",
+            "offset": {"page_number": 1, "page_limit": 100}
+        })))
+        .with_status(200)
+        .with_body(
+            serde_json::json!({
+                "count": 1,
+                "paid_amount": 1000.0,
+                "rows": [{
+                    "payment_id": "pay_1",
+                    "payment_status": "PAID",
+                    "payment_amount": "1000",
+                    "trx_fee": "0",
+                    "payment_currency": "MNT",
+                    "payment_wallet": "qPay",
+                    "payment_type": "P2P",
+                    "next_payment_date": null,
+                    "next_payment_datetime": null
+                }]
+            })
+            .to_string(),
+        )
+        .expect(1)
+        .create_async()
+        .await;
+
+    let client = QPayClient::new(test_config(&server.url()));
+    let req = PaymentCheckRequest {
+        object_type: "INVOICE".to_string(),
+        object_id: "inv_1".to_string(),
+        offset: None,
+    };
+    let rows: Vec<_> = client.check_payment_stream(&req, 1).collect().await;
+
+    assert_eq!(rows.len(), 1);
+    assert_eq!(rows[0].as_ref().unwrap().payment_id, "pay_1");
+}
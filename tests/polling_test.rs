@@ -0,0 +1,386 @@
+mod common;
+
+use std::time::Duration;
+
+use futures::StreamExt;
+use mockito::Matcher;
+use qpay::polling::{CancellationToken, PaymentEvent, WaitOptions};
+use qpay::{QPayClient, QPayError};
+
+use common::{server_with_token, test_config};
+
+fn check_json(paid_amount: f64, amounts: &[&str]) -> String {
+    let rows: Vec<_> = amounts
+        .iter()
+        .enumerate()
+        .map(|(i, amount)| {
+            serde_json::json!({
+                "payment_id": format!("pay_{}", i + 1),
+                "payment_status": "PAID",
+                "payment_amount": amount,
+                "trx_fee": "0",
+                "payment_currency": "MNT",
+                "payment_wallet": "qPay",
+                "payment_type": "P2P",
+                "next_payment_date": null,
+                "next_payment_datetime": null
+            })
+        })
+        .collect();
+    serde_json::json!({
+        "count": rows.len(),
+        "paid_amount": paid_amount,
+        "rows": rows
+    })
+    .to_string()
+}
+
+fn invoice_json(status: &str) -> String {
+    serde_json::json!({
+        "invoice_id": "inv_1",
+        "invoice_status": status,
+        "sender_invoice_no": "ORDER-1"
+    })
+    .to_string()
+}
+
+async fn mock_invoice_status(server: &mut mockito::ServerGuard, status: &str) {
+    server
+        .mock("GET", "/v2/invoice/inv_1")
+        .with_status(200)
+        .with_body(invoice_json(status))
+        .create_async()
+        .await;
+}
+
+fn fast() -> WaitOptions {
+    WaitOptions::default().with_interval(Duration::from_millis(10))
+}
+
+#[tokio::test]
+async fn test_wait_for_payment_until_paid() {
+    let mut server = server_with_token().await;
+
+    let pending = server
+        .mock("POST", "/v2/payment/check")
+        .match_body(Matcher::PartialJson(serde_json::json!({
+            "object_type": "INVOICE",
+            "object_id": "inv_1"
+        })))
+        .with_status(200)
+        .with_body(r#"{"count": 0, "rows": []}"#)
+        .expect(2)
+        .create_async()
+        .await;
+    let paid = server
+        .mock("POST", "/v2/payment/check")
+        .with_status(200)
+        .with_body(check_json(5000.0, &["5000"]))
+        .create_async()
+        .await;
+
+    let client = QPayClient::new(test_config(&server.url()));
+    let resp = client.wait_for_payment("inv_1", &fast()).await.unwrap();
+
+    assert!(resp.is_paid());
+    assert_eq!(resp.rows[0].payment_id, "pay_1");
+    pending.assert_async().await;
+    paid.assert_async().await;
+}
+
+#[tokio::test]
+async fn test_wait_for_payment_partial_until_full_amount() {
+    let mut server = server_with_token().await;
+
+    server
+        .mock("POST", "/v2/payment/check")
+        .with_status(200)
+        .with_body(check_json(2000.0, &["2000"]))
+        .expect(1)
+        .create_async()
+        .await;
+    server
+        .mock("POST", "/v2/payment/check")
+        .with_status(200)
+        .with_body(check_json(5000.0, &["2000", "3000"]))
+        .create_async()
+        .await;
+
+    let client = QPayClient::new(test_config(&server.url()));
+    let options = fast().with_partial_payments(5000.0);
+    let resp = client.wait_for_payment("inv_1", &options).await.unwrap();
+
+    assert_eq!(resp.total_paid(), 5000.0);
+    assert_eq!(resp.rows.len(), 2);
+}
+
+#[tokio::test]
+async fn test_wait_for_payment_timeout() {
+    let mut server = server_with_token().await;
+    server
+        .mock("POST", "/v2/payment/check")
+        .with_status(200)
+        .with_body(r#"{"count": 0, "rows": []}"#)
+        .create_async()
+        .await;
+
+    let client = QPayClient::new(test_config(&server.url()));
+    let options = fast()
+        .with_backoff(2.0, Duration::from_millis(40))
+        .with_timeout(Duration::from_millis(100));
+    let err = client
+        .wait_for_payment("inv_1", &options)
+        .await
+        .unwrap_err();
+
+    assert!(matches!(err, QPayError::Timeout(_)), "{err:?}");
+}
+
+#[tokio::test]
+async fn test_wait_for_payment_cancelled() {
+    let mut server = server_with_token().await;
+    server
+        .mock("POST", "/v2/payment/check")
+        .with_status(200)
+        .with_body(r#"{"count": 0, "rows": []}"#)
+        .create_async()
+        .await;
+
+    let client = QPayClient::new(test_config(&server.url()));
+    let token = CancellationToken::new();
+    let options = fast().with_cancellation(token.clone());
+
+    let canceller = tokio::spawn(async move {
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        token.cancel();
+    });
+    let err = client
+        .wait_for_payment("inv_1", &options)
+        .await
+        .unwrap_err();
+    canceller.await.unwrap();
+
+    assert!(matches!(err, QPayError::Cancelled(_)), "{err:?}");
+}
+
+#[tokio::test]
+async fn test_wait_for_payment_api_error() {
+    let mut server = server_with_token().await;
+    server
+        .mock("POST", "/v2/payment/check")
+        .with_status(404)
+        .with_body(r#"{"error": "INVOICE_NOTFOUND", "message": "Invoice not found"}"#)
+        .create_async()
+        .await;
+
+    let client = QPayClient::new(test_config(&server.url()));
+    let err = client
+        .wait_for_payment("missing", &fast())
+        .await
+        .unwrap_err();
+
+    let (status, code, _) = qpay::is_qpay_error(&err).unwrap();
+    assert_eq!(status, 404);
+    assert_eq!(code, "INVOICE_NOTFOUND");
+}
+
+fn row_json(payment_id: &str, status: &str, amount: &str) -> serde_json::Value {
+    serde_json::json!({
+        "payment_id": payment_id,
+        "payment_status": status,
+        "payment_amount": amount,
+        "trx_fee": "0",
+        "payment_currency": "MNT",
+        "payment_wallet": "qPay",
+        "payment_type": "P2P",
+        "next_payment_date": null,
+        "next_payment_datetime": null
+    })
+}
+
+#[tokio::test]
+async fn test_payment_events_new_and_changed() {
+    let mut server = server_with_token().await;
+
+    let polls = [
+        serde_json::json!({"count": 0, "rows": []}),
+        serde_json::json!({"count": 1, "paid_amount": 0.0, "rows": [row_json("pay_1", "NEW", "2000")]}),
+        serde_json::json!({"count": 1, "paid_amount": 2000.0, "rows": [row_json("pay_1", "PAID", "2000")]}),
+        serde_json::json!({"count": 1, "paid_amount": 2000.0, "rows": [row_json("pay_1", "PAID", "2000")]}),
+        serde_json::json!({"count": 2, "paid_amount": 5000.0, "rows": [
+            row_json("pay_1", "PAID", "2000"),
+            row_json("pay_2", "PAID", "3000")
+        ]}),
+    ];
+    for poll in polls {
+        server
+            .mock("POST", "/v2/payment/check")
+            .with_status(200)
+            .with_body(poll.to_string())
+            .expect(1)
+            .create_async()
+            .await;
+    }
+    // Checked only after the polls that show no change
+    let invoice = server
+        .mock("GET", "/v2/invoice/inv_1")
+        .with_status(200)
+        .with_body(invoice_json("OPEN"))
+        .expect(2)
+        .create_async()
+        .await;
+
+    let client = QPayClient::new(test_config(&server.url()));
+    let events: Vec<_> = client
+        .payment_events("inv_1", fast().with_partial_payments(5000.0))
+        .collect()
+        .await;
+    let events: Vec<_> = events.into_iter().map(Result::unwrap).collect();
+
+    assert_eq!(events.len(), 3);
+    assert!(matches!(&events[0], PaymentEvent::NewPayment(row) if row.payment_id == "pay_1"));
+    assert!(matches!(
+        &events[1],
+        PaymentEvent::StatusChanged { previous_status, row }
+            if previous_status == "NEW" && row.payment_status == "PAID"
+    ));
+    assert!(matches!(&events[2], PaymentEvent::NewPayment(row) if row.payment_id == "pay_2"));
+    invoice.assert_async().await;
+}
+
+#[tokio::test]
+async fn test_payment_events_invoice_cancelled() {
+    let mut server = server_with_token().await;
+    server
+        .mock("POST", "/v2/payment/check")
+        .with_status(200)
+        .with_body(
+            serde_json::json!({"count": 1, "paid_amount": 0.0, "rows": [row_json("pay_1", "NEW", "2000")]})
+                .to_string(),
+        )
+        .create_async()
+        .await;
+    server
+        .mock("GET", "/v2/invoice/inv_1")
+        .with_status(200)
+        .with_body(invoice_json("OPEN"))
+        .expect(1)
+        .create_async()
+        .await;
+    mock_invoice_status(&mut server, "CANCELED").await;
+
+    let client = QPayClient::new(test_config(&server.url()));
+    let events: Vec<_> = client.payment_events("inv_1", fast()).collect().await;
+    let events: Vec<_> = events.into_iter().map(Result::unwrap).collect();
+
+    // check_payment never fails; the cancellation comes from invoice_status
+    assert_eq!(events.len(), 2);
+    assert!(matches!(&events[0], PaymentEvent::NewPayment(row) if row.payment_id == "pay_1"));
+    assert!(matches!(events[1], PaymentEvent::InvoiceCancelled));
+}
+
+#[tokio::test]
+async fn test_payment_events_continue_after_transient_errors() {
+    let mut server = server_with_token().await;
+    server
+        .mock("POST", "/v2/payment/check")
+        .with_status(503)
+        .with_body(r#"{"error": "SERVICE_UNAVAILABLE", "message": "try later"}"#)
+        .expect(1)
+        .create_async()
+        .await;
+    server
+        .mock("POST", "/v2/payment/check")
+        .with_status(200)
+        .with_body(r#"{"count": 0, "rows": []}"#)
+        .expect(1)
+        .create_async()
+        .await;
+    server
+        .mock("GET", "/v2/invoice/inv_1")
+        .with_status(429)
+        .with_body(r#"{"error": "TOO_MANY_REQUESTS", "message": "slow down"}"#)
+        .expect(1)
+        .create_async()
+        .await;
+    server
+        .mock("POST", "/v2/payment/check")
+        .with_status(200)
+        .with_body(check_json(5000.0, &["5000"]))
+        .create_async()
+        .await;
+
+    let client = QPayClient::new(test_config(&server.url()));
+    let events: Vec<_> = client.payment_events("inv_1", fast()).collect().await;
+
+    assert_eq!(events.len(), 3);
+    assert!(matches!(
+        &events[0],
+        Err(QPayError::Api {
+            status_code: 503,
+            ..
+        })
+    ));
+    assert!(matches!(
+        &events[1],
+        Err(QPayError::Api {
+            status_code: 429,
+            ..
+        })
+    ));
+    assert!(matches!(&events[2], Ok(PaymentEvent::NewPayment(row)) if row.payment_id == "pay_1"));
+}
+
+#[tokio::test]
+async fn test_payment_events_end_on_permanent_error() {
+    let mut server = server_with_token().await;
+    server
+        .mock("POST", "/v2/payment/check")
+        .with_status(400)
+        .with_body(r#"{"error": "INVOICE_NOTFOUND", "message": "not found"}"#)
+        .expect(1)
+        .create_async()
+        .await;
+
+    let client = QPayClient::new(test_config(&server.url()));
+    let events: Vec<_> = client.payment_events("inv_1", fast()).collect().await;
+
+    assert_eq!(events.len(), 1);
+    assert!(matches!(
+        &events[0],
+        Err(QPayError::Api {
+            status_code: 400,
+            ..
+        })
+    ));
+}
+
+#[tokio::test]
+async fn test_payment_events_timeout_and_cancellation() {
+    let mut server = server_with_token().await;
+    server
+        .mock("POST", "/v2/payment/check")
+        .with_status(200)
+        .with_body(r#"{"count": 0, "rows": []}"#)
+        .create_async()
+        .await;
+    mock_invoice_status(&mut server, "OPEN").await;
+
+    let client = QPayClient::new(test_config(&server.url()));
+
+    let events: Vec<_> = client
+        .payment_events("inv_1", fast().with_timeout(Duration::from_millis(60)))
+        .collect()
+        .await;
+    assert_eq!(events.len(), 1);
+    assert!(matches!(events[0], Err(QPayError::Timeout(_))));
+
+    // A cancelled token ends the stream without an error
+    let token = CancellationToken::new();
+    token.cancel();
+    let events: Vec<_> = client
+        .payment_events("inv_1", fast().with_cancellation(token))
+        .collect()
+        .await;
+    assert!(events.is_empty());
+}
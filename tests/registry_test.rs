@@ -0,0 +1,140 @@
+use qpay::models::*;
+use qpay::registry::{self, RegistryEntry, RegistryError};
+use serial_test::serial;
+
+fn account(bank_code: &str) -> Account {
+    Account {
+        account_bank_code: bank_code.to_string(),
+        account_number: "5000123456".to_string(),
+        iban_number: "MN580005005000123456".to_string(),
+        account_name: "Test".to_string(),
+        account_currency: "MNT".to_string(),
+        is_default: true,
+    }
+}
+
+#[test]
+#[serial]
+fn test_bundled_bank_lookup() {
+    registry::reset();
+
+    let khan = registry::bank("050000").unwrap();
+    assert_eq!(khan.name_mn, "Хаан банк");
+    assert_eq!(khan.name_en, "Khan Bank");
+    assert!(registry::bank("999999").is_none());
+    assert!(!registry::banks().is_empty());
+}
+
+#[test]
+#[serial]
+fn test_bundled_district_lookup() {
+    registry::reset();
+
+    let district = registry::district("34").unwrap();
+    assert_eq!(district.name_mn, "Сонгинохайрхан дүүрэг");
+    assert_eq!(district.name_en, "Songinokhairkhan District");
+    // 21 aimags and the 9 districts of Ulaanbaatar
+    assert_eq!(registry::districts().len(), 30);
+}
+
+#[test]
+#[serial]
+fn test_validate_codes() {
+    registry::reset();
+
+    assert!(registry::validate_bank_code("150000").is_ok());
+    assert_eq!(
+        registry::validate_bank_code("123"),
+        Err(RegistryError::UnknownBankCode("123".to_string()))
+    );
+    assert_eq!(
+        registry::validate_district_code("99"),
+        Err(RegistryError::UnknownDistrictCode("99".to_string()))
+    );
+
+    assert!(account("050000").validate_codes().is_ok());
+    assert!(account("000001").validate_codes().is_err());
+
+    let req = CreateEbarimtRequest {
+        payment_id: "pay_1".to_string(),
+        ebarimt_receiver_type: "CITIZEN".to_string(),
+        ebarimt_receiver: None,
+        district_code: Some("23".to_string()),
+        classification_code: None,
+    };
+    assert!(req.validate_codes().is_ok());
+
+    let req = CreateEbarimtRequest {
+        district_code: Some("00".to_string()),
+        ..req
+    };
+    assert!(req.validate_codes().is_err());
+}
+
+#[test]
+#[serial]
+fn test_validate_real_district_codes() {
+    registry::reset();
+
+    assert!(registry::validate_district_code("26").is_ok()); // Bayangol
+    assert!(registry::validate_district_code("01").is_ok()); // Arkhangai
+
+    let req: CreateEbarimtRequest = serde_json::from_str(
+        r#"{"payment_id": "pay_1", "ebarimt_receiver_type": "CITIZEN", "district_code": "01"}"#,
+    )
+    .unwrap();
+    assert!(req.validate_codes().is_ok());
+
+    // Unknown codes are rejected, whatever their form
+    for code in ["21", "3401", "1101", "1", ""] {
+        assert_eq!(
+            registry::validate_district_code(code),
+            Err(RegistryError::UnknownDistrictCode(code.to_string()))
+        );
+    }
+}
+
+#[test]
+#[serial]
+fn test_refresh_and_reset() {
+    registry::refresh_banks(vec![RegistryEntry {
+        code: "500000".to_string(),
+        name_mn: "М банк".to_string(),
+        name_en: "M Bank".to_string(),
+    }]);
+
+    assert_eq!(registry::bank("500000").unwrap().name_en, "M Bank");
+    assert!(registry::bank("050000").is_none());
+
+    registry::reset();
+    assert!(registry::bank("500000").is_none());
+    assert!(registry::bank("050000").is_some());
+}
+
+#[test]
+#[serial]
+fn test_refresh_from_reference_models() {
+    let banks = [Bank {
+        bank_code: "050000".to_string(),
+        name: "Хаан банк".to_string(),
+        name_eng: None,
+        ..Default::default()
+    }];
+    registry::refresh_banks(banks.iter().map(RegistryEntry::from));
+
+    // English name falls back to the Mongolian name
+    assert_eq!(registry::bank("050000").unwrap().name_en, "Хаан банк");
+    assert_eq!(registry::banks().len(), 1);
+
+    let districts = [District {
+        code: "1101".to_string(),
+        name: "Баянзүрх".to_string(),
+        ..Default::default()
+    }];
+    registry::refresh_districts(districts.iter().map(RegistryEntry::from));
+    let district = registry::district("1101").unwrap();
+    assert_eq!(district.name_mn, "Баянзүрх");
+    assert_eq!(district.name_en, "");
+
+    registry::reset();
+}
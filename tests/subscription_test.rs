@@ -0,0 +1,406 @@
+#[cfg(feature = "chrono")]
+mod common;
+
+use qpay::models::CreateInvoiceRequest;
+use qpay::subscription::{IntervalUnit, SubscriptionInterval, SubscriptionWebhook};
+use qpay::validation::ValidationError;
+
+fn invoice_request() -> CreateInvoiceRequest {
+    CreateInvoiceRequest {
+        invoice_code: "TEST_CODE".to_string(),
+        sender_invoice_no: "SUB-001".to_string(),
+        sender_branch_code: None,
+        sender_branch_data: None,
+        sender_staff_data: None,
+        sender_staff_code: None,
+        invoice_receiver_code: "terminal".to_string(),
+        invoice_receiver_data: None,
+        invoice_description: "Monthly plan".to_string(),
+        enable_expiry: None,
+        allow_partial: None,
+        minimum_amount: None,
+        allow_exceed: None,
+        maximum_amount: None,
+        amount: 10000.0,
+        callback_url: "https://example.com/callback".to_string(),
+        sender_terminal_code: None,
+        sender_terminal_data: None,
+        allow_subscribe: None,
+        subscription_interval: None,
+        subscription_webhook: None,
+        note: None,
+        transactions: None,
+        lines: None,
+    }
+}
+
+#[test]
+fn test_parse_subscription_interval() {
+    assert_eq!(
+        "1M".parse::<SubscriptionInterval>().unwrap(),
+        SubscriptionInterval::MONTHLY
+    );
+    assert_eq!(
+        "30d".parse::<SubscriptionInterval>().unwrap(),
+        SubscriptionInterval::new(30, IntervalUnit::Day).unwrap()
+    );
+    assert_eq!(
+        "W".parse::<SubscriptionInterval>().unwrap(),
+        SubscriptionInterval::WEEKLY
+    );
+    assert_eq!(
+        " yearly ".parse::<SubscriptionInterval>().unwrap(),
+        SubscriptionInterval::YEARLY
+    );
+    assert_eq!(
+        SubscriptionInterval::new(2, IntervalUnit::Week)
+            .unwrap()
+            .to_string(),
+        "2W"
+    );
+
+    for invalid in ["", "0M", "1X", "12", "M1", "-1D"] {
+        assert_eq!(
+            invalid.parse::<SubscriptionInterval>(),
+            Err(ValidationError::SubscriptionInterval(invalid.to_string())),
+            "{invalid:?}"
+        );
+    }
+
+    assert_eq!(
+        SubscriptionInterval::new(0, IntervalUnit::Month),
+        Err(ValidationError::SubscriptionInterval("0M".to_string()))
+    );
+}
+
+#[test]
+fn test_subscription_interval_serde() {
+    let json =
+        serde_json::to_string(&SubscriptionInterval::new(3, IntervalUnit::Month).unwrap()).unwrap();
+    assert_eq!(json, "\"3M\"");
+
+    let interval: SubscriptionInterval = serde_json::from_str("\"1Y\"").unwrap();
+    assert_eq!(interval, SubscriptionInterval::YEARLY);
+    assert!(serde_json::from_str::<SubscriptionInterval>("\"often\"").is_err());
+}
+
+#[test]
+fn test_create_invoice_with_subscription() {
+    let req = invoice_request().with_subscription(
+        SubscriptionInterval::MONTHLY,
+        "https://example.com/subscription",
+    );
+
+    let json = serde_json::to_value(&req).unwrap();
+    assert_eq!(json["allow_subscribe"], true);
+    assert_eq!(json["subscription_interval"], "1M");
+    assert_eq!(
+        json["subscription_webhook"],
+        "https://example.com/subscription"
+    );
+    assert_eq!(
+        req.subscription_interval_parsed(),
+        Some(SubscriptionInterval::MONTHLY)
+    );
+    assert!(req.validate().is_ok());
+
+    let req = CreateInvoiceRequest {
+        subscription_interval: Some("fortnightly".to_string()),
+        ..req
+    };
+    assert_eq!(
+        req.validate(),
+        Err(ValidationError::SubscriptionInterval(
+            "fortnightly".to_string()
+        ))
+    );
+}
+
+#[test]
+fn test_parse_json_webhook() {
+    let body = serde_json::json!({
+        "invoice_id": "inv_1",
+        "payment_id": 555,
+        "payment_status": "PAID",
+        "payment_amount": "10000.00",
+        "next_payment_date": "2026-11-18",
+        "subscription_id": "sub_1"
+    });
+    let webhook = SubscriptionWebhook::from_request(Some("order=42"), body.to_string().as_bytes());
+
+    assert_eq!(webhook.invoice_id.as_deref(), Some("inv_1"));
+    assert_eq!(webhook.payment_id.as_deref(), Some("555"));
+    assert_eq!(webhook.payment_amount, Some(10000.0));
+    assert_eq!(webhook.next_payment_date.as_deref(), Some("2026-11-18"));
+    assert_eq!(webhook.param("subscription_id"), Some("sub_1"));
+    assert_eq!(webhook.param("order"), Some("42"));
+    assert!(webhook.is_paid());
+}
+
+#[test]
+fn test_parse_form_webhook() {
+    let webhook = SubscriptionWebhook::from_request(
+        None,
+        b"object_id=inv_2&payment_id=pay_2&payment_status=FAILED",
+    );
+
+    assert_eq!(webhook.invoice_id.as_deref(), Some("inv_2"));
+    assert_eq!(webhook.payment_id.as_deref(), Some("pay_2"));
+    assert!(!webhook.is_paid());
+
+    let empty = SubscriptionWebhook::from_request(None, b"");
+    assert_eq!(empty.invoice_id, None);
+    assert_eq!(empty.payment_id, None);
+}
+
+#[cfg(feature = "chrono")]
+mod tracking {
+    use std::time::Duration;
+
+    use chrono::{DateTime, FixedOffset};
+    use qpay::models::PaymentCheckRow;
+    use qpay::subscription::{
+        IntervalUnit, SubscriptionInterval, SubscriptionStatus, SubscriptionTracker,
+        SubscriptionWebhook,
+    };
+    use qpay::validation::ValidationError;
+    use qpay::QPayClient;
+
+    use crate::common::{server_with_token, test_config};
+
+    fn at(value: &str) -> DateTime<FixedOffset> {
+        qpay::datetime::parse_datetime(value).unwrap()
+    }
+
+    fn paid_row(payment_id: &str, next_payment_date: Option<&str>) -> PaymentCheckRow {
+        PaymentCheckRow {
+            payment_id: payment_id.to_string(),
+            payment_status: "PAID".to_string(),
+            next_payment_date: next_payment_date.map(str::to_string),
+            ..Default::default()
+        }
+    }
+
+    fn check_json(rows: &[(&str, &str)]) -> String {
+        let rows: Vec<_> = rows
+            .iter()
+            .map(|(payment_id, next_payment_date)| {
+                serde_json::json!({
+                    "payment_id": payment_id,
+                    "payment_status": "PAID",
+                    "payment_amount": "10000",
+                    "trx_fee": "0",
+                    "payment_currency": "MNT",
+                    "payment_wallet": "qPay",
+                    "payment_type": "CARD",
+                    "next_payment_date": next_payment_date,
+                    "next_payment_datetime": null
+                })
+            })
+            .collect();
+        serde_json::json!({ "count": rows.len(), "rows": rows }).to_string()
+    }
+
+    #[test]
+    fn test_next_after() {
+        let jan31 = at("2026-01-31 10:00:00");
+        assert_eq!(
+            SubscriptionInterval::MONTHLY.next_after(jan31),
+            Ok(at("2026-02-28 10:00:00"))
+        );
+        assert_eq!(
+            "2W".parse::<SubscriptionInterval>()
+                .unwrap()
+                .next_after(jan31),
+            Ok(at("2026-02-14 10:00:00"))
+        );
+        assert_eq!(
+            SubscriptionInterval::YEARLY.next_after(jan31),
+            Ok(at("2027-01-31 10:00:00"))
+        );
+
+        // u32::MAX years overflows the month count
+        let huge = SubscriptionInterval::new(u32::MAX, IntervalUnit::Year).unwrap();
+        assert_eq!(
+            huge.next_after(jan31),
+            Err(ValidationError::SubscriptionInterval(huge.to_string()))
+        );
+    }
+
+    #[test]
+    fn test_tracker_advances_on_charges() {
+        let mut tracker = SubscriptionTracker::default();
+        tracker.track(
+            "inv_1",
+            SubscriptionInterval::MONTHLY,
+            at("2026-10-01 09:00:00"),
+        );
+
+        // Without next_payment_date the next charge is one interval later
+        assert!(tracker.record_payment("inv_1", "pay_1", None));
+        assert_eq!(
+            tracker.get("inv_1").unwrap().next_charge,
+            at("2026-11-01 09:00:00")
+        );
+
+        // The same charge seen again through polling is ignored
+        assert_eq!(tracker.record_rows("inv_1", &[paid_row("pay_1", None)]), 0);
+
+        // QPay's next_payment_date wins over the computed date
+        let body = br#"{"invoice_id":"inv_1","payment_id":"pay_2","payment_status":"PAID","next_payment_date":"2026-12-05"}"#;
+        let webhook = SubscriptionWebhook::from_request(None, body);
+        assert!(tracker.record_webhook(&webhook));
+
+        let sub = tracker.get("inv_1").unwrap();
+        assert_eq!(sub.next_charge, at("2026-12-05 00:00:00"));
+        assert_eq!(sub.last_payment_id.as_deref(), Some("pay_2"));
+        assert_eq!(sub.charges, 2);
+
+        assert!(!tracker.record_payment("inv_unknown", "pay_3", None));
+    }
+
+    #[test]
+    fn test_untrack_forgets_recorded_payments() {
+        let mut tracker = SubscriptionTracker::default();
+        let start = at("2026-10-01 09:00:00");
+        tracker.track("inv_1", SubscriptionInterval::MONTHLY, start);
+        tracker.track("inv_2", SubscriptionInterval::MONTHLY, start);
+        assert!(tracker.record_payment("inv_1", "pay_1", None));
+        assert!(tracker.record_payment("inv_2", "pay_2", None));
+
+        // A tracked invoice is tracked again from scratch
+        tracker.untrack("inv_1");
+        tracker.track("inv_1", SubscriptionInterval::MONTHLY, start);
+        assert!(tracker.record_payment("inv_1", "pay_1", None));
+
+        // Other invoices keep their recorded payments
+        assert!(!tracker.record_payment("inv_2", "pay_2", None));
+        assert_eq!(tracker.get("inv_2").unwrap().charges, 1);
+    }
+
+    #[test]
+    fn test_record_rows_ignores_payments_before_expected_charge() {
+        let mut tracker = SubscriptionTracker::default();
+        tracker.track(
+            "inv_1",
+            SubscriptionInterval::MONTHLY,
+            at("2026-11-01 09:00:00"),
+        );
+
+        // The only row is the initial payment that started the subscription
+        let initial = PaymentCheckRow {
+            payment_date: Some("2026-10-01 09:00:00".to_string()),
+            ..paid_row("pay_0", Some("2026-11-01"))
+        };
+        assert_eq!(
+            tracker.record_rows("inv_1", std::slice::from_ref(&initial)),
+            0
+        );
+        let undated = paid_row("pay_0", Some("2026-11-01 09:00:00"));
+        assert_eq!(tracker.record_rows("inv_1", &[undated]), 0);
+
+        let sub = tracker.get("inv_1").unwrap();
+        assert_eq!(sub.next_charge, at("2026-11-01 09:00:00"));
+        assert_eq!(sub.charges, 0);
+        assert!(matches!(
+            tracker.status("inv_1", at("2026-11-03 09:00:00")),
+            Some(SubscriptionStatus::Missed(_))
+        ));
+
+        // A charge paid a little early, within the grace period, counts once
+        let charge = PaymentCheckRow {
+            payment_date: Some("2026-10-31 20:00:00".to_string()),
+            ..paid_row("pay_1", None)
+        };
+        let rows = [initial, charge.clone(), charge];
+        assert_eq!(tracker.record_rows("inv_1", &rows), 1);
+        assert_eq!(
+            tracker.get("inv_1").unwrap().next_charge,
+            at("2026-12-01 09:00:00")
+        );
+    }
+
+    #[test]
+    fn test_tracker_reports_overdue_after_grace() {
+        let mut tracker = SubscriptionTracker::new(Duration::from_secs(3600));
+        tracker.track(
+            "inv_1",
+            SubscriptionInterval::WEEKLY,
+            at("2026-10-10 12:00:00"),
+        );
+
+        assert_eq!(
+            tracker.status("inv_1", at("2026-10-10 12:30:00")),
+            Some(SubscriptionStatus::Active {
+                next_charge: at("2026-10-10 12:00:00")
+            })
+        );
+        assert!(tracker.overdue(at("2026-10-10 12:30:00")).is_empty());
+
+        let overdue = tracker.overdue(at("2026-10-10 15:00:00"));
+        assert_eq!(overdue.len(), 1);
+        assert_eq!(overdue[0].invoice_id, "inv_1");
+        assert_eq!(overdue[0].overdue_by, chrono::TimeDelta::hours(3));
+        assert!(tracker
+            .status("inv_unknown", at("2026-10-10 15:00:00"))
+            .is_none());
+    }
+
+    #[tokio::test]
+    async fn test_missed_payments_confirmed_with_check_payment() {
+        let mut server = server_with_token().await;
+        // inv_paid was charged but the webhook was lost
+        let paid = server
+            .mock("POST", "/v2/payment/check")
+            .match_body(mockito::Matcher::PartialJson(
+                serde_json::json!({"object_id": "inv_paid"}),
+            ))
+            .with_status(200)
+            .with_body(check_json(&[("pay_1", "2026-11-10")]))
+            .create_async()
+            .await;
+        let missed = server
+            .mock("POST", "/v2/payment/check")
+            .match_body(mockito::Matcher::PartialJson(
+                serde_json::json!({"object_id": "inv_missed"}),
+            ))
+            .with_status(200)
+            .with_body(check_json(&[]))
+            .create_async()
+            .await;
+
+        let client = QPayClient::new(test_config(&server.url()));
+        let mut tracker = SubscriptionTracker::new(Duration::from_secs(3600));
+        let due = at("2026-10-10 12:00:00");
+        tracker.track("inv_paid", SubscriptionInterval::MONTHLY, due);
+        tracker.track("inv_missed", SubscriptionInterval::MONTHLY, due);
+        tracker.track(
+            "inv_later",
+            SubscriptionInterval::MONTHLY,
+            at("2026-10-20 12:00:00"),
+        );
+
+        let now = at("2026-10-11 12:00:00");
+        let result = client
+            .missed_subscription_payments(&mut tracker, now)
+            .await
+            .unwrap();
+
+        assert_eq!(result.len(), 1);
+        assert_eq!(result[0].invoice_id, "inv_missed");
+        assert_eq!(result[0].expected_at, due);
+        assert_eq!(
+            tracker.get("inv_paid").unwrap().next_charge,
+            at("2026-11-10 00:00:00")
+        );
+        paid.assert_async().await;
+        missed.assert_async().await;
+
+        // Untracked invoices are not checked
+        let status = client
+            .check_subscription(&mut tracker, "inv_unknown", now)
+            .await
+            .unwrap();
+        assert!(status.is_none());
+    }
+}
@@ -0,0 +1,197 @@
+use qpay::models::{CreateEbarimtInvoiceRequest, EbarimtResponse};
+use qpay::tax::{
+    round_amount, EbarimtTaxCalculator, EbarimtTaxType, TaxableLine, TAX_CODE_CITY_TAX,
+    TAX_CODE_VAT,
+};
+
+fn invoice_request() -> CreateEbarimtInvoiceRequest {
+    CreateEbarimtInvoiceRequest {
+        invoice_code: "TEST_CODE".to_string(),
+        sender_invoice_no: "INV-TAX-001".to_string(),
+        sender_branch_code: None,
+        sender_staff_data: None,
+        sender_staff_code: None,
+        invoice_receiver_code: "terminal".to_string(),
+        invoice_receiver_data: None,
+        invoice_description: "Tax invoice".to_string(),
+        tax_type: String::new(),
+        district_code: "34".to_string(),
+        callback_url: "https://example.com/callback".to_string(),
+        lines: Vec::new(),
+    }
+}
+
+#[test]
+fn test_round_amount() {
+    assert_eq!(round_amount(1.005), 1.01);
+    assert_eq!(round_amount(2.675), 2.68);
+    assert_eq!(round_amount(-1.005), -1.01);
+    assert_eq!(round_amount(909.0909), 909.09);
+    assert_eq!(round_amount(0.0), 0.0);
+}
+
+#[test]
+fn test_tax_type_codes() {
+    assert_eq!(EbarimtTaxType::parse("1").unwrap(), EbarimtTaxType::VatAble);
+    assert_eq!(
+        "vat_free".parse::<EbarimtTaxType>().unwrap(),
+        EbarimtTaxType::VatFree
+    );
+    assert_eq!(EbarimtTaxType::VatZero.to_string(), "3");
+    assert_eq!(EbarimtTaxType::NoVat.code(), "5");
+    assert!(EbarimtTaxType::parse("4").is_err());
+}
+
+#[test]
+fn test_line_tax_per_tax_type() {
+    let plain = TaxableLine::new("Coffee", 2.0, 5000.0);
+    let city = TaxableLine::new("Beer", 1.0, 10000.0).with_city_tax();
+
+    let vat = EbarimtTaxCalculator::new(EbarimtTaxType::VatAble);
+    let tax = vat.line_tax(&plain);
+    assert_eq!(tax.amount, 10000.0);
+    assert_eq!(tax.vat, 909.09);
+    assert_eq!(tax.city_tax, 0.0);
+    assert_eq!(tax.net_amount, 9090.91);
+
+    let tax = vat.line_tax(&city);
+    assert_eq!(tax.vat, 900.9);
+    assert_eq!(tax.city_tax, 90.09);
+    assert_eq!(tax.net_amount, 9009.01);
+
+    let free = EbarimtTaxCalculator::new(EbarimtTaxType::VatFree);
+    assert_eq!(free.line_tax(&plain).vat, 0.0);
+
+    let abroad = EbarimtTaxCalculator::new(EbarimtTaxType::NoVat);
+    let tax = abroad.line_tax(&city);
+    assert_eq!((tax.vat, tax.city_tax, tax.net_amount), (0.0, 0.0, 10000.0));
+
+    let tax = vat.with_city_tax_rate(0.02).line_tax(&city);
+    assert_eq!(tax.vat, 892.86);
+    assert_eq!(tax.city_tax, 178.57);
+}
+
+#[test]
+fn test_no_city_tax_without_vat() {
+    let line = TaxableLine::new("Tour", 1.0, 1010.0).with_city_tax();
+    for tax_type in [EbarimtTaxType::VatFree, EbarimtTaxType::VatZero] {
+        assert!(!tax_type.charges_city_tax());
+        let calc = EbarimtTaxCalculator::new(tax_type);
+        let tax = calc.line_tax(&line);
+        assert_eq!((tax.vat, tax.city_tax, tax.net_amount), (0.0, 0.0, 1010.0));
+        assert!(calc.invoice_line(&line).taxes.is_none());
+    }
+    assert!(EbarimtTaxType::VatAble.charges_city_tax());
+}
+
+#[test]
+fn test_invoice_line_formats_quantity_and_price() {
+    let calc = EbarimtTaxCalculator::new(EbarimtTaxType::VatAble);
+    let line = calc.invoice_line(&TaxableLine::new("Rice", 0.1 + 0.2, 1999.999));
+    assert_eq!(line.line_quantity, "0.3");
+    assert_eq!(line.line_unit_price, "2000");
+
+    let line = calc.invoice_line(&TaxableLine::new("Cable", 1.25, 0.5));
+    assert_eq!(line.line_quantity, "1.25");
+    assert_eq!(line.line_unit_price, "0.5");
+}
+
+#[test]
+fn test_line_tax_uses_sent_quantity_and_price() {
+    let calc = EbarimtTaxCalculator::new(EbarimtTaxType::VatAble);
+    let lines = [
+        TaxableLine::new("Bolt", 10.0, 10.005).with_city_tax(),
+        TaxableLine::new("Rice", 2.0004, 1500.0),
+        TaxableLine::new("Gum", 3.0, 4.994),
+    ];
+
+    for line in &lines {
+        let sent = calc.invoice_line(line);
+        let quantity: f64 = sent.line_quantity.parse().unwrap();
+        let unit_price: f64 = sent.line_unit_price.parse().unwrap();
+        let tax = calc.line_tax(line);
+        assert_eq!(tax.amount, round_amount(quantity * unit_price));
+
+        let resent = TaxableLine::new("", quantity, unit_price);
+        let resent = if line.city_tax {
+            resent.with_city_tax()
+        } else {
+            resent
+        };
+        assert_eq!(calc.line_tax(&resent), tax);
+    }
+
+    // 10 x "10.01", not 10 x 10.005
+    assert_eq!(calc.line_tax(&lines[0]).amount, 100.1);
+}
+
+#[test]
+fn test_summary_sums_rounded_lines() {
+    let calc = EbarimtTaxCalculator::new(EbarimtTaxType::VatAble);
+    let lines = vec![TaxableLine::new("Gum", 1.0, 5.0); 3];
+
+    let summary = calc.summary(&lines);
+    assert_eq!(summary.lines.len(), 3);
+    assert_eq!(summary.amount, 15.0);
+    // 0.45 per line, not 15 / 1.1 * 0.1 = 1.36 on the total
+    assert_eq!(summary.vat_amount, 1.35);
+    assert_eq!(summary.net_amount, 13.65);
+    assert_eq!(summary.city_tax_amount, 0.0);
+}
+
+#[test]
+fn test_apply_builds_lines_and_matches_response() {
+    let calc = EbarimtTaxCalculator::new(EbarimtTaxType::VatAble);
+    let lines = [
+        TaxableLine::new("Coffee", 2.0, 5000.0).with_tax_product_code("2410101"),
+        TaxableLine::new("Beer", 1.0, 10000.0)
+            .with_tax_product_code("2130101")
+            .with_city_tax(),
+    ];
+
+    let mut req = invoice_request();
+    let summary = calc.apply(&mut req, &lines);
+    assert_eq!(req.tax_type, "1");
+    assert_eq!(req.lines.len(), 2);
+    assert_eq!(req.lines[0].line_quantity, "2");
+    assert_eq!(req.lines[0].line_unit_price, "5000");
+    assert_eq!(req.lines[0].tax_product_code.as_deref(), Some("2410101"));
+
+    let taxes = req.lines[0].taxes.as_ref().unwrap();
+    assert_eq!(taxes.len(), 1);
+    assert_eq!(taxes[0].tax_code.as_deref(), Some(TAX_CODE_VAT));
+    assert_eq!(taxes[0].amount, 909.09);
+
+    let taxes = req.lines[1].taxes.as_ref().unwrap();
+    assert_eq!(taxes[1].tax_code.as_deref(), Some(TAX_CODE_CITY_TAX));
+    assert_eq!(taxes[1].amount, 90.09);
+
+    assert_eq!(summary.amount, 20000.0);
+    assert_eq!(summary.vat_amount, 1809.99);
+    assert_eq!(summary.city_tax_amount, 90.09);
+
+    let mut resp = EbarimtResponse {
+        amount: "20000".to_string(),
+        vat_amount: "1809.99".to_string(),
+        city_tax_amount: "90.09".to_string(),
+        ..Default::default()
+    };
+    assert!(summary.matches(&resp));
+
+    resp.vat_amount = "1810.00".to_string();
+    resp.city_tax_amount = String::new();
+    let mismatches = summary.mismatches(&resp);
+    let fields: Vec<_> = mismatches.iter().map(|m| m.field).collect();
+    assert_eq!(fields, ["vat_amount", "city_tax_amount"]);
+    assert_eq!(
+        mismatches[0].to_string(),
+        r#"vat_amount: expected 1809.99, got "1810.00""#
+    );
+}
+
+#[test]
+fn test_untaxed_lines_have_no_taxes() {
+    let calc = EbarimtTaxCalculator::new(EbarimtTaxType::VatZero);
+    let line = calc.invoice_line(&TaxableLine::new("Export", 1.0, 1000.0));
+    assert!(line.taxes.is_none());
+}
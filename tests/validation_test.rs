@@ -0,0 +1,265 @@
+use qpay::models::*;
+use qpay::validation::*;
+use qpay::QPayError;
+
+const KHAN_IBAN: &str = "MN300005005000123456";
+
+fn account(bank_code: &str, number: &str, iban: &str) -> Account {
+    Account {
+        account_bank_code: bank_code.to_string(),
+        account_number: number.to_string(),
+        iban_number: iban.to_string(),
+        account_name: "Test".to_string(),
+        account_currency: "MNT".to_string(),
+        is_default: true,
+    }
+}
+
+#[test]
+fn test_validate_iban_success() {
+    let iban = validate_iban(KHAN_IBAN).unwrap();
+    assert_eq!(iban.check_digits, "30");
+    assert_eq!(iban.bank_id, "0005");
+    assert_eq!(iban.account, "005000123456");
+    assert_eq!(iban.account_bank_code(), "050000");
+    assert_eq!(iban.account_number(), "5000123456");
+    assert_eq!(iban.to_string(), KHAN_IBAN);
+}
+
+#[test]
+fn test_validate_iban_accepts_spaces_and_lowercase() {
+    assert!(validate_iban("mn30 0005 0050 0012 3456").is_ok());
+}
+
+#[test]
+fn test_validate_iban_errors() {
+    assert_eq!(
+        validate_iban("MN3000050050001234"),
+        Err(ValidationError::IbanLength {
+            expected: 20,
+            actual: 18
+        })
+    );
+    assert_eq!(
+        validate_iban("DE300005005000123456"),
+        Err(ValidationError::IbanCountry("DE".to_string()))
+    );
+    assert!(matches!(
+        validate_iban("MN30000500500012345X"),
+        Err(ValidationError::IbanFormat(_))
+    ));
+    // One wrong digit breaks the check digits
+    assert!(matches!(
+        validate_iban("MN300005005000123457"),
+        Err(ValidationError::IbanChecksum(_))
+    ));
+}
+
+#[test]
+fn test_build_iban() {
+    let iban = build_iban("050000", "5000123456").unwrap();
+    assert_eq!(iban.to_string(), KHAN_IBAN);
+
+    let iban = build_iban("150000", "1234567890").unwrap();
+    assert_eq!(iban.to_string(), "MN910015001234567890");
+
+    assert_eq!(
+        build_iban("05", "5000123456"),
+        Err(ValidationError::BankCodeFormat("05".to_string()))
+    );
+    assert!(matches!(
+        build_iban("050000", "12ab"),
+        Err(ValidationError::AccountNumberFormat(_))
+    ));
+}
+
+#[test]
+fn test_account_validate() {
+    assert!(account("050000", "5000123456", KHAN_IBAN)
+        .validate()
+        .is_ok());
+
+    assert_eq!(
+        account("150000", "5000123456", KHAN_IBAN).validate(),
+        Err(ValidationError::BankCodeMismatch {
+            iban_bank_code: "0005".to_string(),
+            account_bank_code: "150000".to_string(),
+        })
+    );
+
+    assert!(matches!(
+        account("050000", "5000123457", KHAN_IBAN).validate(),
+        Err(ValidationError::AccountNumberMismatch { .. })
+    ));
+}
+
+#[test]
+fn test_create_invoice_request_validate() {
+    let mut req = CreateInvoiceRequest {
+        invoice_code: "CODE".to_string(),
+        sender_invoice_no: "INV-001".to_string(),
+        sender_branch_code: None,
+        sender_branch_data: None,
+        sender_staff_data: None,
+        sender_staff_code: None,
+        invoice_receiver_code: "terminal".to_string(),
+        invoice_receiver_data: None,
+        invoice_description: "Split payout".to_string(),
+        enable_expiry: None,
+        allow_partial: None,
+        minimum_amount: None,
+        allow_exceed: None,
+        maximum_amount: None,
+        amount: 1000.0,
+        callback_url: "https://cb.example.com".to_string(),
+        sender_terminal_code: None,
+        sender_terminal_data: None,
+        allow_subscribe: None,
+        subscription_interval: None,
+        subscription_webhook: None,
+        note: None,
+        transactions: Some(vec![Transaction {
+            description: "Payout".to_string(),
+            amount: "1000".to_string(),
+            accounts: Some(vec![account("050000", "5000123456", KHAN_IBAN)]),
+        }]),
+        lines: None,
+    };
+    assert!(req.validate().is_ok());
+
+    req.transactions.as_mut().unwrap()[0]
+        .accounts
+        .as_mut()
+        .unwrap()
+        .push(account("050000", "5000123456", "MN000005005000123456"));
+
+    let err: QPayError = req.validate().unwrap_err().into();
+    assert!(matches!(
+        err,
+        QPayError::Validation(ValidationError::IbanChecksum(_))
+    ));
+}
+
+#[test]
+fn test_register_number_citizen() {
+    let reg = RegisterNumber::parse("УА89010112").unwrap();
+    assert_eq!(reg, RegisterNumber::Citizen("УА89010112".to_string()));
+    assert!(reg.is_citizen());
+
+    // Lowercase and spacing are normalized
+    assert_eq!(
+        RegisterNumber::parse("уа 89010112").unwrap().as_str(),
+        "УА89010112"
+    );
+    // Born in 2000 or later: month + 20
+    assert!(RegisterNumber::parse("ӨҮ05220310").is_ok());
+}
+
+#[test]
+fn test_register_number_transliteration() {
+    assert_eq!(
+        RegisterNumber::parse("UA89010112").unwrap().as_str(),
+        "УА89010112"
+    );
+    assert_eq!(
+        RegisterNumber::parse("yo89010112").unwrap().as_str(),
+        "ҮО89010112"
+    );
+    assert_eq!(
+        RegisterNumber::parse("KHSH89010112").unwrap().as_str(),
+        "ХШ89010112"
+    );
+    assert_eq!(
+        "YOKH89010112".parse::<RegisterNumber>().unwrap().as_str(),
+        "ЁХ89010112"
+    );
+}
+
+#[test]
+fn test_register_number_company_and_tin() {
+    let company = RegisterNumber::parse("5317878").unwrap();
+    assert_eq!(company, RegisterNumber::Company("5317878".to_string()));
+    assert!(company.is_company());
+
+    let tin = RegisterNumber::parse("37900846788").unwrap();
+    assert_eq!(tin, RegisterNumber::Tin("37900846788".to_string()));
+    assert!(tin.is_company());
+}
+
+#[test]
+fn test_register_number_invalid() {
+    for value in [
+        "",
+        "УА8901011",
+        "УАБ89010112",
+        "QQQ89010112",
+        "УА89130112",
+        "УА89010012",
+        "12345678",
+        "УА8901011X",
+    ] {
+        assert_eq!(
+            RegisterNumber::parse(value),
+            Err(ValidationError::RegisterNumberFormat(value.to_string())),
+            "{value:?}"
+        );
+    }
+}
+
+#[test]
+fn test_register_number_serde() {
+    let reg: RegisterNumber = serde_json::from_str(r#""ua89010112""#).unwrap();
+    assert_eq!(serde_json::to_string(&reg).unwrap(), r#""УА89010112""#);
+    assert!(serde_json::from_str::<RegisterNumber>(r#""bad""#).is_err());
+}
+
+#[test]
+fn test_register_number_in_request_models() {
+    let receiver =
+        InvoiceReceiverData::default().with_register(RegisterNumber::parse("UA89010112").unwrap());
+    assert_eq!(receiver.register.as_deref(), Some("УА89010112"));
+    assert!(receiver.validate().is_ok());
+
+    let branch = SenderBranchData {
+        register: Some("not a register".to_string()),
+        ..Default::default()
+    };
+    assert!(matches!(
+        branch.validate(),
+        Err(ValidationError::RegisterNumberFormat(_))
+    ));
+}
+
+#[test]
+fn test_create_ebarimt_request_validate() {
+    let req = CreateEbarimtRequest {
+        payment_id: "pay_1".to_string(),
+        ebarimt_receiver_type: String::new(),
+        ebarimt_receiver: None,
+        district_code: None,
+        classification_code: None,
+    }
+    .with_ebarimt_receiver(RegisterNumber::parse("5317878").unwrap());
+    assert_eq!(req.ebarimt_receiver_type, "COMPANY");
+    assert!(req.validate().is_ok());
+
+    let req = CreateEbarimtRequest {
+        ebarimt_receiver: Some("УА89010112".to_string()),
+        ..req
+    };
+    assert_eq!(
+        req.validate(),
+        Err(ValidationError::RegisterNumberKind {
+            expected: "company".to_string(),
+            value: "УА89010112".to_string(),
+        })
+    );
+
+    // A citizen receiver may be a phone number
+    let req = CreateEbarimtRequest {
+        ebarimt_receiver_type: "CITIZEN".to_string(),
+        ebarimt_receiver: Some("88001122".to_string()),
+        ..req
+    };
+    assert!(req.validate().is_ok());
+}